@@ -0,0 +1,10 @@
+-- project_notes used to be dropped on every start and had a misspelled
+-- lecture_notes column, recreate it once with the right columns.
+DROP TABLE IF EXISTS project_notes;
+CREATE VIRTUAL TABLE project_notes USING fts5(
+    project_id UNINDEXED,
+    transcript,
+    summary,
+    email,
+    lecture_notes
+);
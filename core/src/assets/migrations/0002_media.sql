@@ -0,0 +1,12 @@
+-- Recordings in the media store, named by the SHA-256 of the imported file so
+-- the same recording is stored once however many projects use it
+CREATE TABLE IF NOT EXISTS media (
+    hash TEXT PRIMARY KEY,
+    relative_path TEXT NOT NULL,
+    format TEXT NOT NULL,
+    original_name TEXT NOT NULL,
+    size_bytes INTEGER NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+ALTER TABLE audio_projects ADD COLUMN media_hash TEXT REFERENCES media(hash);
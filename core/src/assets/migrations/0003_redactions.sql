@@ -0,0 +1,10 @@
+-- Placeholders used when redacting a project's notes and the values they
+-- replace, so a redacted copy can be turned back into the original
+CREATE TABLE IF NOT EXISTS redactions (
+    project_id TEXT NOT NULL,
+    placeholder TEXT NOT NULL,
+    entity TEXT NOT NULL,
+    original TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (project_id, placeholder)
+);
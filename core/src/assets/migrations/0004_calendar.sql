@@ -0,0 +1,32 @@
+-- Events imported from .ics files, lists are JSON arrays
+CREATE TABLE IF NOT EXISTS calendar_events (
+    id TEXT PRIMARY KEY,
+    summary TEXT NOT NULL,
+    starts_at TEXT NOT NULL,
+    ends_at TEXT NOT NULL,
+    all_day INTEGER NOT NULL,
+    timezone TEXT,
+    rrule TEXT,
+    exdates TEXT NOT NULL,
+    attendees TEXT NOT NULL,
+    agenda TEXT,
+    location TEXT,
+    categories TEXT NOT NULL,
+    imported_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS calendar_events_by_start ON calendar_events(starts_at);
+
+-- The meeting a project was recorded in, copied from its event so later
+-- imports do not change the project
+CREATE TABLE IF NOT EXISTS project_meetings (
+    project_id TEXT PRIMARY KEY REFERENCES audio_projects(id),
+    event_id TEXT NOT NULL,
+    title TEXT NOT NULL,
+    starts_at TEXT NOT NULL,
+    ends_at TEXT NOT NULL,
+    attendees TEXT NOT NULL,
+    agenda TEXT,
+    location TEXT,
+    categories TEXT NOT NULL
+);
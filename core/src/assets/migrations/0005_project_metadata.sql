@@ -0,0 +1,18 @@
+-- Metadata beyond the columns of audio_projects, lists and custom fields are JSON
+CREATE TABLE IF NOT EXISTS project_metadata (
+    project_id TEXT PRIMARY KEY REFERENCES audio_projects(id),
+    description TEXT,
+    participants TEXT NOT NULL,
+    duration_seconds REAL,
+    source_device TEXT,
+    fields TEXT NOT NULL
+);
+
+-- Tags get their own table so searches can filter on them
+CREATE TABLE IF NOT EXISTS project_tags (
+    project_id TEXT NOT NULL REFERENCES audio_projects(id),
+    tag TEXT NOT NULL COLLATE NOCASE,
+    PRIMARY KEY (project_id, tag)
+);
+
+CREATE INDEX IF NOT EXISTS project_tags_by_tag ON project_tags(tag);
@@ -0,0 +1,9 @@
+-- Groups nest through parent_id, NULL for top level groups
+ALTER TABLE project_groups ADD COLUMN parent_id TEXT REFERENCES project_groups(id);
+
+CREATE INDEX IF NOT EXISTS project_groups_by_parent ON project_groups(parent_id);
+
+-- Projects whose group was never stored, connections now check foreign keys
+INSERT OR IGNORE INTO project_groups (id, name)
+SELECT DISTINCT group_id, group_id FROM audio_projects
+WHERE group_id NOT IN (SELECT id FROM project_groups);
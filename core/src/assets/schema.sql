@@ -13,11 +13,49 @@ CREATE TABLE IF NOT EXISTS audio_projects (
     language TEXT NOT NULL
 );
 
-DROP TABLE IF EXISTS project_notes;
+CREATE TABLE IF NOT EXISTS note_token_usage (
+    project_id TEXT NOT NULL REFERENCES audio_projects(id),
+    note_kind TEXT NOT NULL,
+    prompt_tokens INTEGER NOT NULL,
+    completion_tokens INTEGER NOT NULL,
+    transcript_tokens INTEGER NOT NULL,
+    transcript_tokens_total INTEGER NOT NULL,
+    truncated INTEGER NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS note_versions (
+    id TEXT PRIMARY KEY,
+    project_id TEXT NOT NULL REFERENCES audio_projects(id),
+    note_kind TEXT NOT NULL,
+    content TEXT NOT NULL,
+    source TEXT NOT NULL,
+    template TEXT,
+    model TEXT,
+    params TEXT,
+    parent_id TEXT REFERENCES note_versions(id),
+    is_current INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS note_versions_by_note ON note_versions(project_id, note_kind);
+
+-- Last state of each note's markdown file that was reconciled with note_versions
+CREATE TABLE IF NOT EXISTS note_files (
+    project_id TEXT NOT NULL REFERENCES audio_projects(id),
+    note_kind TEXT NOT NULL,
+    synced_version_id TEXT REFERENCES note_versions(id),
+    content_hash TEXT NOT NULL,
+    mtime_ms INTEGER NOT NULL,
+    synced_at TEXT NOT NULL,
+    PRIMARY KEY (project_id, note_kind)
+);
+
+-- Search index over the current version of each note, one row per project
 CREATE VIRTUAL TABLE IF NOT EXISTS project_notes USING fts5(
     project_id UNINDEXED,
     transcript,
     summary,
     email,
-    leture_notes
+    lecture_notes
 );
\ No newline at end of file
@@ -0,0 +1,125 @@
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use thiserror::Error;
+
+pub type Result<T, E = TaunoteError> = std::result::Result<T, E>;
+
+// Every error taunote_core returns. Each variant has a stable code so the
+// frontend can show targeted remediation instead of a raw message.
+#[derive(Debug, Error)]
+pub enum TaunoteError {
+    #[error("{name} not found")]
+    MissingDependency { name: String, hint: String },
+    #[error("Could not decode audio: {0}")]
+    AudioDecode(String),
+    #[error("Transcription failed: {0}")]
+    Transcription(String),
+    #[error("LLM unavailable: {0}")]
+    LlmUnavailable(String),
+    #[error("LLM request failed: {0}")]
+    Llm(String),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Configuration error: {0}")]
+    Config(String),
+    #[error("{0} not found")]
+    NotFound(String),
+    #[error("Data is encrypted: {0}")]
+    Locked(String),
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl TaunoteError {
+    pub fn missing_dependency(name: &str, hint: &str) -> Self {
+        TaunoteError::MissingDependency {
+            name: name.to_string(),
+            hint: hint.to_string(),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            TaunoteError::MissingDependency { .. } => "missing_dependency",
+            TaunoteError::AudioDecode(_) => "audio_decode",
+            TaunoteError::Transcription(_) => "transcription",
+            TaunoteError::LlmUnavailable(_) => "llm_unavailable",
+            TaunoteError::Llm(_) => "llm",
+            TaunoteError::Database(_) => "database",
+            TaunoteError::Io(_) => "io",
+            TaunoteError::Config(_) => "config",
+            TaunoteError::NotFound(_) => "not_found",
+            TaunoteError::Locked(_) => "locked",
+            TaunoteError::InvalidInput(_) => "invalid_input",
+            TaunoteError::Internal(_) => "internal",
+        }
+    }
+
+    // What the user can do about it, shown next to the message
+    pub fn remediation(&self) -> Option<String> {
+        match self {
+            TaunoteError::MissingDependency { name, hint } => {
+                Some(format!("{name} not found — {hint}"))
+            }
+            TaunoteError::AudioDecode(_) => {
+                Some("Check that the file is a supported audio format and not corrupted".to_string())
+            }
+            TaunoteError::Transcription(_) => Some(
+                "Check that python3 can import whisperx and HUGGINGFACE_TOKEN is set in .env"
+                    .to_string(),
+            ),
+            TaunoteError::LlmUnavailable(_) => Some(
+                "Check the llama-server binary and model paths, the server log is in the logs folder"
+                    .to_string(),
+            ),
+            TaunoteError::Database(_) => {
+                Some("The project database may be locked or corrupted, try restarting taunote".to_string())
+            }
+            TaunoteError::Locked(_) => Some(
+                "Unlock with your passphrase: set TAUNOTE_PASSPHRASE or run taunote unlock"
+                    .to_string(),
+            ),
+            _ => None,
+        }
+    }
+}
+
+// Sent to the frontend as { code, message, remediation }
+impl Serialize for TaunoteError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("TaunoteError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("remediation", &self.remediation())?;
+        state.end()
+    }
+}
+
+impl From<serde_json::Error> for TaunoteError {
+    fn from(err: serde_json::Error) -> Self {
+        TaunoteError::Internal(format!("JSON error: {err}"))
+    }
+}
+
+// Requests that never got an answer mean the server is down or restarting
+impl From<reqwest::Error> for TaunoteError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_connect() || err.is_request() || err.is_body() {
+            TaunoteError::LlmUnavailable(err.to_string())
+        } else {
+            TaunoteError::Llm(err.to_string())
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for TaunoteError {
+    fn from(err: zip::result::ZipError) -> Self {
+        match err {
+            zip::result::ZipError::Io(err) => TaunoteError::Io(err),
+            err => TaunoteError::InvalidInput(format!("Not a valid zip file: {err}")),
+        }
+    }
+}
@@ -1 +1,17 @@
+//! Local speech-to-text and note generation.
+//!
+//! [`Taunote`] is the entry point: it owns the data directory and the LLM
+//! service and runs the pipeline from a recording to a transcript and
+//! generated notes. The modules under [`services`] are the building blocks it
+//! uses, for callers that need finer control.
+
+pub mod error;
 pub mod services;
+pub mod taunote;
+
+pub use error::{Result, TaunoteError};
+pub use services::notes::kinds::NoteKind;
+pub use taunote::{
+    CalendarImport, NewProject, ProcessOptions, ProcessedRecording, Progress, RedactOptions,
+    RedactedNote, RedactedProject, ReprocessOptions, Taunote,
+};
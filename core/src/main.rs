@@ -1,106 +1,1350 @@
-use clap::Parser;
-use uuid::Uuid;
-// use std::result::Result;
-// use std::error::Error;
-use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use serde::Serialize;
 use std::fs;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
+use taunote_core::services::audio::{
+    capture::RecordingFormat,
+    microphone::{input_devices, Microphone},
+};
+use taunote_core::services::bundle::import::{GroupCollision, ImportOptions};
+use taunote_core::services::config::settings::Settings;
+use taunote_core::services::database::groups::group_path;
+use taunote_core::services::database::models::{
+    AudioProject, Meeting, NoteVersion, ProjectMetadata, ProjectType, SearchFilter,
+};
+use taunote_core::services::doctor::checks::{check_environment, CheckStatus};
+use taunote_core::services::llm::generation::GenerationParams;
+use taunote_core::services::llm::llama_queue::JobOptions;
+use taunote_core::services::llm::llama_server::LlamaServerConfig;
+use taunote_core::services::notes::generate::GeneratedNote;
+use taunote_core::services::redact::detect::RedactionRules;
+use taunote_core::services::server::routes::serve;
+use taunote_core::services::transcribe::whisperx::{WhisperxOptions, DEFAULT_MODEL};
+use taunote_core::{
+    NewProject, NoteKind, ProcessOptions, ProcessedRecording, RedactOptions, ReprocessOptions,
+    Result, Taunote, TaunoteError,
+};
+use zeroize::Zeroizing;
 
-pub mod services;
-use services::audio::ffmpeg::preprocess_audio;
-use services::database::models::AudioProject;
-use services::database::queries::{insert_audio_project, insert_project_notes};
-use services::database::schema::init_db;
-use services::llm::llama_queue::init_llama_queue;
-use services::llm::prompt_tasks::{generate_email, summarize};
-use services::transcribe::whisperx::run_whisperx;
+// Exit codes scripts can rely on, clap itself exits with 2 on usage errors
+const EXIT_ERROR: i32 = 1;
+const EXIT_INVALID_INPUT: i32 = 2;
+const EXIT_NOT_FOUND: i32 = 3;
+const EXIT_MISSING_DEPENDENCY: i32 = 4;
+const EXIT_LLM_UNAVAILABLE: i32 = 5;
+const EXIT_LOCKED: i32 = 6;
+
+// Unlocks an encrypted data directory when the OS keyring has no key
+const PASSPHRASE_ENV: &str = "TAUNOTE_PASSPHRASE";
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Cli {
-    #[arg(short, long)]
+    /// Print results as JSON, errors go to stderr as {code, message, remediation}
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Transcribe a recording and generate its notes
+    Process(ProcessArgs),
+    /// Record from the microphone, then process the recording like `process`
+    Record(RecordArgs),
+    /// List groups, or the projects in all or one group
+    List {
+        /// Only projects in this group
+        #[arg(short, long)]
+        group: Option<String>,
+        /// List the groups instead of projects
+        #[arg(long, conflicts_with = "group")]
+        groups: bool,
+    },
+    /// Show a project and its notes
+    Show {
+        project: String,
+        /// Print only this note
+        #[arg(short, long, value_parser = parse_kind)]
+        kind: Option<NoteKind>,
+    },
+    /// Generate one note kind from a project's transcript
+    Generate {
+        #[arg(value_parser = parse_kind)]
+        kind: NoteKind,
+        project: String,
+        #[command(flatten)]
+        generation: GenerationArgs,
+    },
+    /// Transcribe a project's stored recording again with other settings
+    Reprocess(ReprocessArgs),
+    /// Full text search over transcripts and notes, without a query the
+    /// projects matching the filters are listed
+    Search {
+        query: Option<String>,
+        #[arg(short, long, default_value_t = 20)]
+        limit: usize,
+        #[command(flatten)]
+        filter: FilterArgs,
+    },
+    /// Show or change a project's tags, description, participants and fields
+    Meta(MetaArgs),
+    /// List the tags in use and how many projects have them
+    Tags,
+    /// Write copies of a project's notes with emails, phone numbers and other
+    /// personal data replaced by placeholders
+    Redact(RedactArgs),
+    /// Put the values behind a project's redaction placeholders back into a text
+    Unredact {
+        project: String,
+        /// Read from stdin when not given
+        file: Option<PathBuf>,
+    },
+    /// Write a project's notes as markdown files into a folder
+    Export {
+        project: String,
+        dest: PathBuf,
+        /// Write a .taunote bundle with the recording and note history instead,
+        /// `dest` is the file or a folder to write it into
+        #[arg(long)]
+        bundle: bool,
+    },
+    /// Write the projects as notes with front matter into an Obsidian vault,
+    /// running it again updates them in place
+    Obsidian {
+        vault: PathBuf,
+        /// Only the projects of this group
+        #[arg(short, long)]
+        group: Option<String>,
+    },
+    /// Add the project in a .taunote bundle to the library under new ids
+    Import {
+        bundle: PathBuf,
+        /// Group to import into instead of the bundle's
+        #[arg(short, long)]
+        group: Option<String>,
+        /// Create a new group when one with the same name exists, instead of
+        /// importing into it
+        #[arg(long)]
+        new_group: bool,
+    },
+    /// Import calendar events from a local .ics file and match recordings to them
+    Calendar {
+        #[command(subcommand)]
+        action: CalendarAction,
+    },
+    /// Delete a project with all its notes and files
+    Delete { project: String },
+    /// Rename a project
+    Rename { project: String, name: String },
+    /// Move a project to another group, e.g. Clients/Acme, missing groups are created
+    Move { project: String, group: String },
+    /// Create, rename, nest or delete groups
+    Group {
+        #[command(subcommand)]
+        action: GroupAction,
+    },
+    /// Show or change the settings in the data directory
+    Config {
+        #[command(subcommand)]
+        action: Option<ConfigAction>,
+    },
+    /// List stored recordings or delete the ones past the retention period
+    Media {
+        #[command(subcommand)]
+        action: MediaAction,
+    },
+    /// Check ffmpeg, whisperx, llama-server and the model, and print how to fix what is missing
+    Doctor,
+    /// Encrypt the database and notes with a passphrase, needs the encryption feature
+    Encrypt {
+        /// Also save the key in the OS keyring, like `unlock`
+        #[arg(long)]
+        remember: bool,
+    },
+    /// Save the key in the OS keyring so later commands need no passphrase
+    Unlock,
+    /// Remove the key from the OS keyring
+    Lock,
+    /// Write the database, notes, recordings and settings to a .tar.gz archive
+    Backup {
+        /// Archive to write, or a folder to write a timestamped archive into
+        dest: PathBuf,
+        /// Earlier backup to build on, only files that changed since are stored
+        #[arg(long)]
+        since: Option<PathBuf>,
+    },
+    /// Replace the library with a full backup and the incremental backups that
+    /// follow it, the current one is moved to a pre-restore folder
+    Restore {
+        #[arg(required = true)]
+        archives: Vec<PathBuf>,
+        /// Only check the archives
+        #[arg(long)]
+        verify_only: bool,
+    },
+    /// Serve the local REST API, see /openapi.json for the routes
+    Serve {
+        #[arg(long, default_value = "127.0.0.1")]
+        host: std::net::IpAddr,
+        #[arg(short, long, default_value_t = 7823)]
+        port: u16,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    Get {
+        key: String,
+    },
+    Set {
+        key: String,
+        value: String,
+    },
+    Unset {
+        key: String,
+    },
+    /// Print the path of the settings file
+    Path,
+}
+
+#[derive(Subcommand, Debug)]
+enum CalendarAction {
+    /// Add the events of an .ics file, events imported before are updated
+    Import { file: PathBuf },
+    /// List the imported events
+    List,
+    /// Show the event a recording belongs to
+    Match {
+        #[arg(required_unless_present = "at")]
+        recording: Option<PathBuf>,
+        /// Match this time instead, e.g. "2025-01-31 10:05" in local time
+        #[arg(long, value_parser = parse_time, conflicts_with = "recording")]
+        at: Option<DateTime<Utc>>,
+    },
+    /// Link a project to the event going on at a time, its next generated
+    /// notes use the attendees and agenda
+    Link {
+        project: String,
+        #[arg(long, value_parser = parse_time)]
+        at: DateTime<Utc>,
+    },
+}
+
+// Groups are given by id or by a path of names like Clients/Acme
+#[derive(Subcommand, Debug)]
+enum GroupAction {
+    /// Create a group and the missing groups of its path
+    Create {
+        path: String,
+    },
+    Rename {
+        group: String,
+        name: String,
+    },
+    /// Put a group under another one, which is created if needed, its
+    /// projects keep their folders
+    Move {
+        group: String,
+        #[arg(required_unless_present = "top")]
+        parent: Option<String>,
+        /// Make it a top level group
+        #[arg(long, conflicts_with = "parent")]
+        top: bool,
+    },
+    /// Delete a group without projects or subgroups
+    Delete {
+        group: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum MediaAction {
+    List,
+    /// Delete recordings older than media_retention_days and those no project uses
+    Prune,
+}
+
+#[derive(Args, Debug)]
+struct ProcessArgs {
     input_path: PathBuf,
     #[arg(short, long)]
     lang: Option<String>,
+    /// Folder the transcript and notes are copied to when done
     #[arg(short, long)]
     output_path: Option<PathBuf>,
-    #[arg(short = 'g', long = "group", default_value = "default")]
-    group_name: String,
+    /// Group id or path like Clients/Acme, missing groups are created.
+    /// Defaults to the category of the matched calendar event, then the
+    /// default_group setting, then "default"
+    #[arg(short = 'g', long = "group")]
+    group_name: Option<String>,
+    /// Defaults to the title of the matched calendar event, then the file name
+    #[arg(short = 'n', long = "name")]
+    project_name: Option<String>,
+    /// When the recording was made, for matching it to an imported calendar
+    /// event. Defaults to a date in the file name, then the file's modification time
+    #[arg(long, value_parser = parse_time)]
+    recorded_at: Option<DateTime<Utc>>,
+    /// Do not match the recording to a calendar event
+    #[arg(long, conflicts_with = "recorded_at")]
+    no_calendar: bool,
+    /// meeting, lecture, interview, voice_memo or other
+    #[arg(short = 't', long = "type", value_parser = parse_type, default_value = "meeting")]
+    project_type: ProjectType,
+    /// Note kinds to generate after transcription, defaults to those of the type
+    #[arg(short, long = "kind", value_parser = parse_kind)]
+    kinds: Vec<NoteKind>,
+    /// Parallel llama-server slots, each one gets ctx-size / parallel tokens
+    #[arg(long)]
+    parallel: Option<u32>,
+    #[command(flatten)]
+    generation: GenerationArgs,
+}
+
+#[derive(Args, Debug)]
+struct RecordArgs {
+    /// List the microphones instead of recording
+    #[arg(long)]
+    devices: bool,
+    /// Microphone name as listed by --devices, defaults to the system's
+    #[arg(short, long)]
+    device: Option<String>,
+    /// wav, or flac which is encoded with ffmpeg when the recording stops
+    #[arg(long, value_parser = parse_format, default_value = "wav")]
+    format: RecordingFormat,
+    #[arg(short, long)]
+    lang: Option<String>,
+    /// Group id or path like Clients/Acme, defaults as for `process`
+    #[arg(short = 'g', long = "group")]
+    group_name: Option<String>,
+    /// Defaults to the title of the calendar event going on, then the time
     #[arg(short = 'n', long = "name")]
     project_name: Option<String>,
+    /// Do not match the recording to a calendar event
+    #[arg(long)]
+    no_calendar: bool,
+    /// meeting, lecture, interview, voice_memo or other
+    #[arg(short = 't', long = "type", value_parser = parse_type, default_value = "meeting")]
+    project_type: ProjectType,
+    /// Note kinds to generate after transcription, defaults to those of the type
+    #[arg(short, long = "kind", value_parser = parse_kind)]
+    kinds: Vec<NoteKind>,
+    #[command(flatten)]
+    generation: GenerationArgs,
+}
+
+#[derive(Args, Debug)]
+struct ReprocessArgs {
+    project: String,
+    /// Defaults to the project's language, "auto" detects it
+    #[arg(short, long)]
+    lang: Option<String>,
+    /// Whisper model, e.g. small, medium or large-v3
+    #[arg(short, long, default_value = DEFAULT_MODEL)]
+    model: String,
+    #[arg(long, conflicts_with = "diarize_only")]
+    no_diarize: bool,
+    /// Keep the last transcription and only redo speaker diarization
+    #[arg(long)]
+    diarize_only: bool,
+    #[arg(long)]
+    min_speakers: Option<u32>,
+    #[arg(long)]
+    max_speakers: Option<u32>,
+    /// Regenerate the notes the project already has
+    #[arg(short, long)]
+    regenerate: bool,
+    #[command(flatten)]
+    generation: GenerationArgs,
+}
+
+#[derive(Args, Debug)]
+struct RedactArgs {
+    project: String,
+    /// Also redact this word or phrase, can be repeated
+    #[arg(short, long = "word")]
+    words: Vec<String>,
+    /// File with more words or phrases to redact, one per line
+    #[arg(long)]
+    words_file: Option<PathBuf>,
+    /// Ask the LLM for people's names as well
+    #[arg(long)]
+    names: bool,
+    /// Detectors to turn off
+    #[arg(long, value_parser = ["email", "phone", "iban", "credit_card"])]
+    keep: Vec<String>,
+    /// Also write the redacted notes as markdown files into this folder
+    #[arg(short, long)]
+    output_path: Option<PathBuf>,
+}
+
+impl RedactArgs {
+    fn to_options(&self) -> Result<RedactOptions> {
+        let mut words = self.words.clone();
+        if let Some(path) = &self.words_file {
+            words.extend(
+                fs::read_to_string(path)?
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(str::to_string),
+            );
+        }
+        let keep = |detector: &str| self.keep.iter().any(|kept| kept == detector);
+        Ok(RedactOptions {
+            rules: RedactionRules {
+                emails: !keep("email"),
+                phones: !keep("phone"),
+                ibans: !keep("iban"),
+                credit_cards: !keep("credit_card"),
+                words,
+            },
+            names: self.names,
+        })
+    }
+}
+
+#[derive(Args, Debug)]
+struct MetaArgs {
+    project: String,
+    /// Add a tag, can be repeated
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    /// Remove a tag, can be repeated
+    #[arg(long = "untag")]
+    untags: Vec<String>,
+    #[arg(short, long)]
+    description: Option<String>,
+    /// Add a participant, can be repeated
+    #[arg(short, long = "participant")]
+    participants: Vec<String>,
+    /// Device the recording was made with
+    #[arg(long)]
+    device: Option<String>,
+    /// Set a custom field as key=value, can be repeated
+    #[arg(long = "field", value_parser = parse_field)]
+    fields: Vec<(String, String)>,
+    /// Remove a custom field, can be repeated
+    #[arg(long = "unset-field")]
+    unset_fields: Vec<String>,
+    /// Change the project's type
+    #[arg(short = 't', long = "type", value_parser = parse_type)]
+    project_type: Option<ProjectType>,
+}
+
+impl MetaArgs {
+    fn changes_metadata(&self) -> bool {
+        !self.tags.is_empty()
+            || !self.untags.is_empty()
+            || self.description.is_some()
+            || !self.participants.is_empty()
+            || self.device.is_some()
+            || !self.fields.is_empty()
+            || !self.unset_fields.is_empty()
+    }
+
+    fn apply(&self, metadata: &mut ProjectMetadata) {
+        metadata.tags.extend(self.tags.iter().cloned());
+        metadata.tags.retain(|tag| {
+            !self
+                .untags
+                .iter()
+                .any(|untag| untag.trim_start_matches('#').eq_ignore_ascii_case(tag))
+        });
+        if let Some(description) = &self.description {
+            metadata.description = Some(description.clone());
+        }
+        metadata
+            .participants
+            .extend(self.participants.iter().cloned());
+        if let Some(device) = &self.device {
+            metadata.source_device = Some(device.clone());
+        }
+        metadata.fields.extend(self.fields.iter().cloned());
+        for key in &self.unset_fields {
+            metadata.fields.remove(key);
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+struct FilterArgs {
+    #[arg(short, long)]
+    group: Option<String>,
+    #[arg(short = 't', long = "type", value_parser = parse_type)]
+    project_type: Option<ProjectType>,
+    /// Only projects with this tag, can be repeated to require all of them
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+    #[arg(short, long)]
+    participant: Option<String>,
+    /// Projects recorded on or after this date, YYYY-MM-DD
+    #[arg(long)]
+    from: Option<String>,
+    /// Projects recorded on or before this date, YYYY-MM-DD
+    #[arg(long)]
+    to: Option<String>,
+}
+
+impl FilterArgs {
+    fn to_filter(&self) -> SearchFilter {
+        SearchFilter {
+            group_id: self.group.clone(),
+            project_type: self.project_type,
+            tags: self.tags.clone(),
+            participant: self.participant.clone(),
+            from: self.from.clone(),
+            to: self.to.clone(),
+        }
+    }
+}
+
+// Overrides for the per-task generation defaults
+#[derive(Args, Debug)]
+struct GenerationArgs {
+    #[arg(long)]
+    temperature: Option<f32>,
+    #[arg(long)]
+    top_p: Option<f32>,
+    #[arg(long)]
+    top_k: Option<u32>,
+    #[arg(long)]
+    repeat_penalty: Option<f32>,
+    #[arg(long)]
+    seed: Option<u64>,
+    #[arg(long = "stop")]
+    stop: Vec<String>,
+    #[arg(long)]
+    max_tokens: Option<u32>,
+    /// Path to a GBNF grammar file
+    #[arg(long)]
+    grammar: Option<PathBuf>,
+}
+
+impl GenerationArgs {
+    fn to_params(&self) -> Result<GenerationParams> {
+        let grammar = match &self.grammar {
+            Some(path) => Some(fs::read_to_string(path)?),
+            None => None,
+        };
+        Ok(GenerationParams {
+            temperature: self.temperature,
+            top_p: self.top_p,
+            top_k: self.top_k,
+            repeat_penalty: self.repeat_penalty,
+            seed: self.seed,
+            stop: self.stop.clone(),
+            max_tokens: self.max_tokens,
+            grammar,
+        })
+    }
+}
+
+// Output of `show`
+#[derive(Serialize)]
+struct ProjectDetails {
+    project: AudioProject,
+    notes: Vec<NoteVersion>,
+}
+
+fn parse_type(value: &str) -> std::result::Result<ProjectType, String> {
+    ProjectType::parse(value).ok_or_else(|| {
+        let types: Vec<&str> = ProjectType::ALL.iter().map(ProjectType::as_str).collect();
+        format!("expected one of {}", types.join(", "))
+    })
+}
+
+fn parse_format(value: &str) -> std::result::Result<RecordingFormat, String> {
+    RecordingFormat::parse(value).ok_or_else(|| "expected wav or flac".to_string())
+}
+
+// key=value
+fn parse_field(value: &str) -> std::result::Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| "expected key=value".to_string())
+}
+
+fn parse_kind(value: &str) -> std::result::Result<NoteKind, String> {
+    NoteKind::parse(value).ok_or_else(|| {
+        let kinds: Vec<&str> = NoteKind::ALL.iter().map(NoteKind::as_str).collect();
+        format!("expected one of {}", kinds.join(", "))
+    })
+}
+
+// RFC 3339, or a local "YYYY-MM-DD HH:MM"
+fn parse_time(value: &str) -> std::result::Result<DateTime<Utc>, String> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M")
+        .ok()
+        .and_then(|naive| naive.and_local_timezone(Local).earliest())
+        .map(|at| at.with_timezone(&Utc))
+        .ok_or_else(|| "expected e.g. \"2025-01-31 10:05\" or 2025-01-31T10:05:00Z".to_string())
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    let args = Cli::parse();
-    // Finding local directory paths to initialize the database, matches src-tauri identifier
-    let proj_dirs = directories_next::ProjectDirs::from("com", "andrea", "taunote")
-        .expect("Failed to find platform data directory");
-    let base_path = proj_dirs.data_local_dir();
-    init_db(base_path)?;
-
-    init_llama_queue().await;
-
-    // Open connection to db
-    let db_path = base_path.join("db").join("project.db");
-    let conn = rusqlite::Connection::open(db_path)?;
-
-    // TODO (not priority rn): fix the path mess
-    let tmp_audio_path = Path::new("../tmp/preprocessed.wav");
-    let tmp_transcript_path = Path::new("../tmp/transcript.txt");
-    preprocess_audio(&args.input_path, tmp_audio_path)?;
-    run_whisperx(
-        tmp_audio_path,
-        &Some(tmp_transcript_path.to_path_buf()),
-        &args.lang,
+async fn main() {
+    let cli = Cli::parse();
+    let json = cli.json;
+    match run(cli).await {
+        Ok(code) => std::process::exit(code),
+        Err(err) => {
+            if json {
+                eprintln!("{}", serde_json::to_string(&err).unwrap_or_default());
+            } else {
+                eprintln!("Error: {err}");
+                if let Some(fix) = err.remediation() {
+                    eprintln!("Hint: {fix}");
+                }
+            }
+            std::process::exit(exit_code(&err));
+        }
+    }
+}
+
+fn exit_code(err: &TaunoteError) -> i32 {
+    match err {
+        TaunoteError::InvalidInput(_) | TaunoteError::Config(_) => EXIT_INVALID_INPUT,
+        TaunoteError::NotFound(_) => EXIT_NOT_FOUND,
+        TaunoteError::MissingDependency { .. } => EXIT_MISSING_DEPENDENCY,
+        TaunoteError::LlmUnavailable(_) => EXIT_LLM_UNAVAILABLE,
+        TaunoteError::Locked(_) => EXIT_LOCKED,
+        _ => EXIT_ERROR,
+    }
+}
+
+// Prints `value` as JSON, or with `human` otherwise
+fn print<T: Serialize>(json: bool, value: &T, human: impl FnOnce(&T)) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(value)?);
+    } else {
+        human(value);
+    }
+    Ok(())
+}
+
+// --json output carries the same in usage.truncated
+fn warn_truncated(note: &GeneratedNote) {
+    let usage = &note.usage;
+    if usage.truncated {
+        eprintln!(
+            "Only {} of {} transcript tokens fit the context window, the {} covers the start of the transcript",
+            usage.transcript_tokens, usage.transcript_tokens_total, note.kind
+        );
+    }
+}
+
+// Runs the command and returns the exit code
+async fn run(cli: Cli) -> Result<i32> {
+    let json = cli.json;
+    // same data directory as the desktop app
+    let base_dir = Taunote::default_dir()?;
+    let settings = Settings::load(&base_dir)?;
+    let taunote = Taunote::new(&base_dir).with_settings(&settings);
+
+    match cli.command {
+        Command::Doctor => return doctor(json, &taunote),
+        Command::Config { action } => return config(json, &base_dir, settings, action),
+        Command::Encrypt { remember } => {
+            let report = taunote.encrypt(&passphrase(true)?)?;
+            if remember {
+                taunote.remember_key()?;
+            }
+            print(json, &report, |report| {
+                if let Some(database) = &report.database {
+                    println!("Encrypted {}", database.display());
+                }
+                println!("Encrypted {} note files", report.note_files);
+            })?;
+            return Ok(0);
+        }
+        Command::Unlock => {
+            if !taunote.is_encrypted() {
+                return Err(TaunoteError::InvalidInput(format!(
+                    "{} is not encrypted",
+                    base_dir.display()
+                )));
+            }
+            taunote.unlock(&passphrase(false)?)?;
+            taunote.remember_key()?;
+            print(json, &serde_json::json!({ "unlocked": true }), |_| {
+                println!("Saved the key in the OS keyring")
+            })?;
+            return Ok(0);
+        }
+        Command::Restore {
+            archives,
+            verify_only,
+        } => {
+            if verify_only {
+                print(json, &taunote.verify_backup(&archives)?, |manifest| {
+                    println!(
+                        "Backup {} from {} is intact, {} files ({} KiB)",
+                        manifest.id,
+                        manifest.created_at,
+                        manifest.files.len(),
+                        manifest.size_bytes() / 1024
+                    );
+                })?;
+            } else {
+                print(json, &taunote.restore(&archives)?, |report| {
+                    println!(
+                        "Restored {} files from backup {} of {}",
+                        report.files, report.backup_id, report.created_at
+                    );
+                    if let Some(previous) = &report.previous_data {
+                        println!("The previous library was moved to {}", previous.display());
+                    }
+                })?;
+            }
+            return Ok(0);
+        }
+        Command::Record(RecordArgs { devices: true, .. }) => {
+            print(json, &input_devices()?, |devices| {
+                for device in devices {
+                    let default = if device.is_default { " (default)" } else { "" };
+                    println!("{}{default}", device.name);
+                }
+            })?;
+            return Ok(0);
+        }
+        Command::Lock => {
+            taunote.lock()?;
+            print(json, &serde_json::json!({ "unlocked": false }), |_| {
+                println!("Removed the key from the OS keyring")
+            })?;
+            return Ok(0);
+        }
+        _ => {}
+    }
+
+    if taunote.is_locked() && !taunote.unlock_from_keyring()? {
+        let passphrase = passphrase(false)
+            .map_err(|_| TaunoteError::Locked(format!("{} is encrypted", base_dir.display())))?;
+        taunote.unlock(&passphrase)?;
+    }
+    taunote.init()?;
+    match cli.command {
+        Command::Process(args) => {
+            let taunote = match args.parallel {
+                Some(parallel) => taunote.clone().with_llama_config(LlamaServerConfig {
+                    parallel,
+                    ..taunote.llama_config()
+                }),
+                None => taunote,
+            };
+            let result = process(json, &taunote, &settings, args).await;
+            taunote.shutdown().await;
+            result?;
+        }
+        Command::Record(args) => {
+            let result = record(json, &taunote, &settings, args).await;
+            taunote.shutdown().await;
+            result?;
+        }
+        Command::List { group, groups } => {
+            if groups {
+                print(json, &taunote.list_groups()?, |groups| {
+                    let mut paths: Vec<(String, &str)> = groups
+                        .iter()
+                        .map(|group| (group_path(groups, &group.id), group.id.as_str()))
+                        .collect();
+                    paths.sort();
+                    for (path, id) in paths {
+                        println!("{id}\t{path}");
+                    }
+                })?;
+            } else {
+                print(
+                    json,
+                    &taunote.list_projects(group.as_deref())?,
+                    |projects| {
+                        for project in projects {
+                            println!(
+                                "{}\t{}\t{}\t{}",
+                                project.id, project.group_id, project.date, project.name
+                            );
+                        }
+                    },
+                )?;
+            }
+        }
+        Command::Show { project, kind } => {
+            let details = ProjectDetails {
+                project: taunote.project(&project)?,
+                notes: taunote
+                    .notes(&project)?
+                    .into_iter()
+                    .filter(|note| kind.is_none_or(|kind| note.note_kind == kind.as_str()))
+                    .collect(),
+            };
+            if let (Some(kind), true) = (kind, details.notes.is_empty()) {
+                return Err(TaunoteError::NotFound(format!(
+                    "{kind} for project {project}"
+                )));
+            }
+            print(json, &details, |details| {
+                if kind.is_some() {
+                    println!("{}", details.notes[0].content);
+                    return;
+                }
+                let project = &details.project;
+                println!("{} ({})", project.name, project.id);
+                println!("group:    {}", project.group_id);
+                println!("type:     {}", project.project_type.as_str());
+                println!("language: {}", project.language);
+                println!("date:     {}", project.date);
+                println!(
+                    "folder:   {}",
+                    base_dir.join(&project.relative_path).display()
+                );
+                for note in &details.notes {
+                    println!(
+                        "  {:<14} {} ({} chars)",
+                        note.note_kind,
+                        note.created_at,
+                        note.content.chars().count()
+                    );
+                }
+            })?;
+        }
+        Command::Generate {
+            kind,
+            project,
+            generation,
+        } => {
+            let params = generation.to_params()?;
+            let result = taunote
+                .generate_note(
+                    &project,
+                    kind,
+                    &params,
+                    JobOptions::interactive(kind.as_str()),
+                )
+                .await;
+            taunote.shutdown().await;
+            print(json, &result?, |note| {
+                warn_truncated(note);
+                println!("{}", note.version.content);
+            })?;
+        }
+        Command::Reprocess(args) => {
+            let options = ReprocessOptions {
+                whisperx: WhisperxOptions {
+                    language: args.lang,
+                    model: args.model,
+                    diarize: !args.no_diarize,
+                    min_speakers: args.min_speakers,
+                    max_speakers: args.max_speakers,
+                },
+                diarize_only: args.diarize_only,
+                regenerate: args.regenerate,
+                params: args.generation.to_params()?,
+            };
+            let result = taunote.reprocess(&args.project, &options).await;
+            taunote.shutdown().await;
+            print(json, &result?, |reprocessed| {
+                println!("{}", reprocessed.transcript.content);
+                for note in &reprocessed.notes {
+                    println!("Regenerated {}!", note.kind);
+                    warn_truncated(note);
+                    println!("{}", note.version.content);
+                }
+            })?;
+        }
+        Command::Search {
+            query,
+            limit,
+            filter,
+        } => {
+            let hits = taunote.search_filtered(
+                query.as_deref().unwrap_or_default(),
+                &filter.to_filter(),
+                limit,
+            )?;
+            // no match is an empty list, not an error
+            print(json, &hits, |hits| {
+                for hit in hits {
+                    println!("{}\t{}\t{}", hit.project_id, hit.project_name, hit.snippet);
+                }
+            })?;
+        }
+        Command::Redact(args) => {
+            let options = args.to_options()?;
+            let result = taunote.redact(&args.project, &options).await;
+            taunote.shutdown().await;
+            let redacted = result?;
+            if let Some(output_path) = &args.output_path {
+                fs::create_dir_all(output_path)?;
+                for note in &redacted.notes {
+                    fs::write(output_path.join(format!("{}.md", note.kind)), &note.content)?;
+                }
+            }
+            print(json, &redacted, |redacted| {
+                for note in &redacted.notes {
+                    println!("# {}", note.kind);
+                    println!("{}", note.content);
+                }
+                eprintln!(
+                    "{} placeholders, the mapping is stored in the data directory",
+                    redacted.redactions.len()
+                );
+            })?;
+        }
+        Command::Unredact { project, file } => {
+            let text = match file {
+                Some(path) => fs::read_to_string(path)?,
+                None => std::io::read_to_string(std::io::stdin())?,
+            };
+            let restored = taunote.unredact(&project, &text)?;
+            print(json, &serde_json::json!({ "text": restored }), |_| {
+                print!("{restored}")
+            })?;
+        }
+        Command::Export {
+            project,
+            dest,
+            bundle: true,
+        } => {
+            let path = taunote.export_bundle(&project, &dest)?;
+            print(json, &serde_json::json!({ "path": path }), |_| {
+                println!("{}", path.display())
+            })?;
+        }
+        Command::Export { project, dest, .. } => {
+            print(json, &taunote.export(&project, &dest)?, |paths| {
+                for path in paths {
+                    println!("{}", path.display());
+                }
+            })?;
+        }
+        Command::Obsidian { vault, group } => {
+            print(
+                json,
+                &taunote.export_obsidian(&vault, group.as_deref())?,
+                |report| {
+                    for path in &report.written {
+                        println!("{}", path.display());
+                    }
+                    eprintln!(
+                        "{} notes written, {} unchanged",
+                        report.written.len(),
+                        report.unchanged
+                    );
+                },
+            )?;
+        }
+        Command::Import {
+            bundle,
+            group,
+            new_group,
+        } => {
+            let options = ImportOptions {
+                group,
+                on_collision: if new_group {
+                    GroupCollision::Rename
+                } else {
+                    GroupCollision::Merge
+                },
+            };
+            print(json, &taunote.import_bundle(&bundle, &options)?, |report| {
+                println!(
+                    "Imported {} as {} into {}, {} note versions",
+                    report.project.name, report.project.id, report.group.name, report.versions
+                );
+            })?;
+        }
+        Command::Calendar { action } => calendar(json, &taunote, action)?,
+        Command::Group { action } => group(json, &taunote, action)?,
+        Command::Delete { project } => {
+            taunote.delete_project(&project)?;
+            print(json, &serde_json::json!({ "deleted": project }), |_| {
+                println!("Deleted {project}")
+            })?;
+        }
+        Command::Meta(args) => {
+            if let Some(project_type) = args.project_type {
+                taunote.set_project_type(&args.project, project_type)?;
+            }
+            let metadata = if args.changes_metadata() {
+                taunote.update_metadata(&args.project, |metadata| args.apply(metadata))?
+            } else {
+                taunote.project_metadata(&args.project)?
+            };
+            let project = taunote.project(&args.project)?;
+            print(json, &metadata, |metadata| {
+                println!("type:         {}", project.project_type.as_str());
+                println!("tags:         {}", metadata.tags.join(", "));
+                println!("participants: {}", metadata.participants.join(", "));
+                if let Some(description) = &metadata.description {
+                    println!("description:  {description}");
+                }
+                if let Some(device) = &metadata.source_device {
+                    println!("device:       {device}");
+                }
+                if let Some(seconds) = metadata.duration_seconds {
+                    println!("duration:     {seconds:.0}s");
+                }
+                for (key, value) in &metadata.fields {
+                    println!("{key}: {value}");
+                }
+            })?;
+        }
+        Command::Tags => {
+            print(json, &taunote.tags()?, |tags| {
+                for tag in tags {
+                    println!("{}\t{}", tag.projects, tag.tag);
+                }
+            })?;
+        }
+        Command::Rename { project, name } => {
+            print(json, &taunote.rename_project(&project, &name)?, |project| {
+                println!("Renamed {} to {}", project.id, project.name)
+            })?;
+        }
+        Command::Move { project, group } => {
+            print(json, &taunote.move_project(&project, &group)?, |project| {
+                println!("Moved {} to {}", project.id, project.group_id)
+            })?;
+        }
+        Command::Media {
+            action: MediaAction::List,
+        } => {
+            print(json, &taunote.media()?, |media| {
+                for usage in media {
+                    println!(
+                        "{}\t{}\t{} KiB\t{} projects\t{}",
+                        usage.media.hash,
+                        usage.media.format.as_str(),
+                        usage.media.size_bytes / 1024,
+                        usage.projects,
+                        usage.media.original_name
+                    );
+                }
+            })?;
+        }
+        Command::Media {
+            action: MediaAction::Prune,
+        } => {
+            print(json, &taunote.prune_media()?, |report| {
+                println!(
+                    "Removed {} recordings ({} KiB), {} projects were past the retention period",
+                    report.removed.len(),
+                    report.freed_bytes / 1024,
+                    report.expired_projects.len()
+                );
+            })?;
+        }
+        Command::Backup { dest, since } => {
+            print(json, &taunote.backup(&dest, since.as_deref())?, |report| {
+                println!(
+                    "Wrote {}, {} of {} files",
+                    report.path.display(),
+                    report.manifest.stored.len(),
+                    report.manifest.files.len()
+                );
+            })?;
+        }
+        Command::Serve { host, port } => serve(taunote, (host, port).into()).await?,
+        Command::Doctor
+        | Command::Config { .. }
+        | Command::Encrypt { .. }
+        | Command::Unlock
+        | Command::Lock
+        | Command::Restore { .. } => unreachable!(),
+    }
+    Ok(0)
+}
+
+async fn process(
+    json: bool,
+    taunote: &Taunote,
+    settings: &Settings,
+    args: ProcessArgs,
+) -> Result<()> {
+    let meeting = match (args.no_calendar, args.recorded_at) {
+        (true, _) => None,
+        (false, Some(at)) => taunote.find_meeting(at)?,
+        (false, None) => taunote.recording_meeting(&args.input_path)?,
+    };
+    if let (Some(meeting), false) = (&meeting, json) {
+        eprintln!("Recorded in \"{}\" at {}", meeting.title, meeting.starts_at);
+    }
+    let project_name = args
+        .project_name
+        .clone()
+        .or_else(|| meeting.as_ref().map(|meeting| meeting.title.clone()))
+        .unwrap_or_else(|| {
+            args.input_path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "Recording".to_string())
+        });
+    let new_project = NewProject {
+        group_id: group_for(args.group_name.clone(), meeting.as_ref(), settings),
+        name: project_name,
+        project_type: args.project_type,
+        language: args.lang.clone(),
+    };
+    let options = ProcessOptions {
+        kinds: args.kinds.clone(),
+        params: args.generation.to_params()?,
+        meeting,
+    };
+    let processed = taunote
+        .process_recording(&args.input_path, new_project, &options)
+        .await?;
+
+    if let Some(output_path) = &args.output_path {
+        taunote.export(&processed.project.id, output_path)?;
+    }
+    print_processed(json, &processed)
+}
+
+// The group given, else the one of the meeting, the default_group setting or "default"
+fn group_for(group: Option<String>, meeting: Option<&Meeting>, settings: &Settings) -> String {
+    group
+        .or_else(|| meeting.and_then(|m| m.group()).map(str::to_string))
+        .or_else(|| settings.default_group.clone())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+// Enter pauses and resumes, q or the end of stdin stops
+async fn record(
+    json: bool,
+    taunote: &Taunote,
+    settings: &Settings,
+    args: RecordArgs,
+) -> Result<()> {
+    let started_at = Local::now();
+    let meeting = match args.no_calendar {
+        true => None,
+        false => taunote.find_meeting(started_at.with_timezone(&Utc))?,
+    };
+    let meter = !json && std::io::stderr().is_terminal();
+    let recorder = taunote.start_recording(
+        Box::new(Microphone::new(args.device.as_deref())),
+        args.format,
+        move |level| {
+            if meter {
+                let bar = "#".repeat((level.peak * 30.0).round() as usize);
+                eprint!("\r[{bar:<30}] {:>7.1}s", level.seconds);
+            }
+        },
     )?;
-    println!("Generated transcript!");
-
-    // Texts to store
-    let transcript = fs::read_to_string(tmp_transcript_path)?;
-    println!("{transcript}");
-    let summary = summarize(&tmp_transcript_path.to_path_buf()).await?;
-    println!("Generated summary!");
-    println!("{summary}");
-    let email = generate_email(&tmp_transcript_path.to_path_buf()).await?;
-    println!("Generated email!");
-    println!("{email}");
-
-    // Project metadata
-    let project_id = Uuid::new_v4().to_string();
-    let project_name = args.project_name.clone().unwrap_or_else(|| {
-        args.input_path
-            .file_stem()
-            .unwrap()
-            .to_string_lossy()
-            .to_string()
-    });
-    let group_name = args.group_name.clone();
-    let relative_path = format!("groups/{group_name}/{project_name}");
-    let date = chrono::Utc::now().to_rfc3339();
-    let language = args.lang.clone().unwrap_or_else(|| "Auto".to_string());
-
-    // Create project folder and write texts to .md
-    let project_folder = base_path.join(&relative_path);
-    fs::create_dir_all(&project_folder)?;
-    fs::write(project_folder.join("transcript.md"), &transcript)?;
-    fs::write(project_folder.join("summary.md"), &summary)?;
-    fs::write(project_folder.join("email.md"), &email)?;
-    println!("Finished writing markdown files!");
-
-    // Insert to the database
-    let audio_project = AudioProject {
-        id: project_id.clone(),
-        group_id: group_name.clone(),
-        name: project_name.clone(),
-        relative_path: relative_path.clone(),
-        date,
-        project_type: "meeting".to_string(),
-        language,
+    if !json {
+        eprintln!(
+            "Recording to {}, press Enter to pause or resume, q and Enter to stop",
+            recorder.path().display()
+        );
+    }
+    for line in std::io::stdin().lines() {
+        if line?.trim() == "q" {
+            break;
+        }
+        if recorder.is_paused() {
+            recorder.resume();
+        } else {
+            recorder.pause();
+        }
+        if !json {
+            eprintln!(
+                "{}",
+                if recorder.is_paused() {
+                    "Paused"
+                } else {
+                    "Recording"
+                }
+            );
+        }
+    }
+
+    let project_name = args
+        .project_name
+        .clone()
+        .or_else(|| meeting.as_ref().map(|meeting| meeting.title.clone()))
+        .unwrap_or_else(|| format!("Recording {}", started_at.format("%Y-%m-%d %H:%M")));
+    let new_project = NewProject {
+        group_id: group_for(args.group_name.clone(), meeting.as_ref(), settings),
+        name: project_name,
+        project_type: args.project_type,
+        language: args.lang.clone(),
     };
-    insert_audio_project(&conn, &audio_project)?;
-    insert_project_notes(&conn, &project_id, &transcript, &summary, &email)?;
-    // TODO: clean tmp/
-    println!("Finished!");
-    Ok(())
+    let options = ProcessOptions {
+        kinds: args.kinds.clone(),
+        params: args.generation.to_params()?,
+        meeting,
+    };
+    let processed = taunote
+        .finish_recording(recorder, new_project, &options, |_| {})
+        .await?;
+    print_processed(json, &processed)
+}
+
+fn print_processed(json: bool, processed: &ProcessedRecording) -> Result<()> {
+    print(json, processed, |processed| {
+        println!("{}", processed.transcript.content);
+        for note in &processed.notes {
+            println!("Generated {}!", note.kind);
+            warn_truncated(note);
+            println!("{}", note.version.content);
+        }
+        println!("Finished project {}", processed.project.id);
+    })
+}
+
+fn group(json: bool, taunote: &Taunote, action: GroupAction) -> Result<()> {
+    let group = match action {
+        GroupAction::Create { path } => taunote.create_group(&path)?,
+        GroupAction::Rename { group, name } => taunote.rename_group(&group, &name)?,
+        GroupAction::Move { group, parent, .. } => taunote.move_group(&group, parent.as_deref())?,
+        GroupAction::Delete { group } => {
+            taunote.delete_group(&group)?;
+            return print(json, &serde_json::json!({ "deleted": group }), |_| {
+                println!("Deleted {group}")
+            });
+        }
+    };
+    let path = group_path(&taunote.list_groups()?, &group.id);
+    print(json, &group, |group| println!("{}\t{path}", group.id))
+}
+
+fn calendar(json: bool, taunote: &Taunote, action: CalendarAction) -> Result<()> {
+    let print_meeting = |meeting: &Option<Meeting>| match meeting {
+        Some(meeting) => {
+            println!(
+                "{} ({} to {})",
+                meeting.title, meeting.starts_at, meeting.ends_at
+            );
+            if !meeting.attendees.is_empty() {
+                println!("Attendees: {}", meeting.attendees.join(", "));
+            }
+            if let Some(agenda) = &meeting.agenda {
+                println!("{agenda}");
+            }
+        }
+        None => println!("No calendar event at that time"),
+    };
+    match action {
+        CalendarAction::Import { file } => {
+            print(json, &taunote.import_calendar(&file)?, |report| {
+                println!(
+                    "Imported {} events, {} of them recurring",
+                    report.events, report.recurring
+                );
+            })
+        }
+        CalendarAction::List => print(json, &taunote.calendar_events()?, |events| {
+            for event in events {
+                let repeats = if event.rrule.is_some() {
+                    ", repeats"
+                } else {
+                    ""
+                };
+                println!("{}  {}{repeats}", event.starts_at, event.summary);
+            }
+        }),
+        CalendarAction::Match { recording, at } => {
+            let meeting = match (at, recording) {
+                (Some(at), _) => taunote.find_meeting(at)?,
+                (None, Some(recording)) => taunote.recording_meeting(&recording)?,
+                (None, None) => None,
+            };
+            print(json, &meeting, print_meeting)
+        }
+        CalendarAction::Link { project, at } => {
+            let meeting = taunote
+                .find_meeting(at)?
+                .ok_or_else(|| TaunoteError::NotFound(format!("Calendar event at {at}")))?;
+            taunote.set_project_meeting(&project, &meeting)?;
+            print(json, &Some(meeting), print_meeting)
+        }
+    }
+}
+
+// From TAUNOTE_PASSPHRASE, else asked for on the terminal
+fn passphrase(confirm: bool) -> Result<Zeroizing<String>> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Zeroizing::new(passphrase));
+    }
+    if !std::io::stdin().is_terminal() {
+        return Err(TaunoteError::InvalidInput(format!(
+            "No passphrase, set {PASSPHRASE_ENV}"
+        )));
+    }
+    let passphrase = Zeroizing::new(rpassword::prompt_password("Passphrase: ")?);
+    if confirm {
+        let repeated = Zeroizing::new(rpassword::prompt_password("Repeat passphrase: ")?);
+        if repeated != passphrase {
+            return Err(TaunoteError::InvalidInput(
+                "The passphrases do not match".to_string(),
+            ));
+        }
+    }
+    Ok(passphrase)
+}
+
+fn doctor(json: bool, taunote: &Taunote) -> Result<i32> {
+    let report = check_environment(&taunote.llama_config(), taunote.base_dir());
+    print(json, &report, |report| {
+        for check in &report.checks {
+            let label = match check.status {
+                CheckStatus::Ok => "ok",
+                CheckStatus::Warning => "warn",
+                CheckStatus::Error => "FAIL",
+            };
+            println!("[{label:>4}] {:<18} {}", check.name, check.detail);
+            if let Some(fix) = &check.fix {
+                println!("       {:<18} fix: {fix}", "");
+            }
+        }
+    })?;
+    Ok(if report.is_ready() {
+        0
+    } else {
+        EXIT_MISSING_DEPENDENCY
+    })
+}
+
+fn config(
+    json: bool,
+    base_dir: &Path,
+    mut settings: Settings,
+    action: Option<ConfigAction>,
+) -> Result<i32> {
+    match action {
+        None => print(json, &settings, |settings| {
+            for key in Settings::KEYS {
+                let value = settings.get(key).ok().flatten();
+                println!("{key} = {}", value.as_deref().unwrap_or("(default)"));
+            }
+        })?,
+        Some(ConfigAction::Get { key }) => {
+            let value = settings.get(&key)?;
+            print(json, &value, |value| {
+                println!("{}", value.as_deref().unwrap_or("(default)"))
+            })?;
+        }
+        Some(ConfigAction::Set { key, value }) => {
+            settings.set(&key, Some(&value))?;
+            settings.save(base_dir)?;
+            print(json, &settings, |_| println!("{key} = {value}"))?;
+        }
+        Some(ConfigAction::Unset { key }) => {
+            settings.set(&key, None)?;
+            settings.save(base_dir)?;
+            print(json, &settings, |_| println!("{key} = (default)"))?;
+        }
+        Some(ConfigAction::Path) => {
+            let path = Settings::path(base_dir);
+            print(json, &path, |path| println!("{}", path.display()))?;
+        }
+    }
+    Ok(0)
 }
@@ -0,0 +1,369 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::{audio::ffmpeg::encode_audio, media::store::FLAC_CODEC};
+use hound::{SampleFormat, WavSpec, WavWriter};
+use serde::{Deserialize, Serialize};
+use std::{
+    any::Any,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+// A level is reported for every this many seconds of audio
+const LEVEL_INTERVAL: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingFormat {
+    // written as the audio comes in
+    #[default]
+    Wav,
+    // encoded with ffmpeg when the recording stops
+    Flac,
+}
+
+impl RecordingFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordingFormat::Wav => "wav",
+            RecordingFormat::Flac => "flac",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "wav" => Some(RecordingFormat::Wav),
+            "flac" => Some(RecordingFormat::Flac),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct InputFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+// Loudness of the last LEVEL_INTERVAL of audio as a fraction of full scale,
+// `seconds` is how much has been recorded so far
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Level {
+    pub peak: f32,
+    pub rms: f32,
+    pub seconds: f64,
+}
+
+// What Recorder::stop wrote
+#[derive(Debug, Clone, Serialize)]
+pub struct Recording {
+    pub path: PathBuf,
+    pub format: RecordingFormat,
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub duration_seconds: f64,
+}
+
+// Receives interleaved samples between -1 and 1
+pub type Samples = Box<dyn FnMut(&[f32]) + Send>;
+
+// Where a recording comes from: a microphone, or NullInput in tests
+pub trait Input: Send + 'static {
+    // Starts calling `deliver` and returns the format of the samples with a
+    // guard, the input stops when the guard is dropped. Called on the
+    // recorder's own thread, which keeps the guard, so it need not be Send.
+    fn start(self: Box<Self>, deliver: Samples) -> Result<(InputFormat, Box<dyn Any>)>;
+}
+
+enum Message {
+    Samples(Vec<f32>),
+    Stop,
+}
+
+// Records an input on its own thread until stopped. Paused audio is dropped
+// before it reaches the file, so the recording has no gap to cut later.
+pub struct Recorder {
+    path: PathBuf,
+    format: RecordingFormat,
+    input_format: InputFormat,
+    paused: Arc<AtomicBool>,
+    sender: mpsc::Sender<Message>,
+    thread: Option<JoinHandle<Result<u64>>>,
+}
+
+impl Recorder {
+    // Records into `path`, a .wav next to it holds the audio until it is
+    // encoded when the format is FLAC. `on_level` is called from the
+    // recorder's thread.
+    pub fn start(
+        input: Box<dyn Input>,
+        path: &Path,
+        format: RecordingFormat,
+        on_level: impl Fn(Level) + Send + 'static,
+    ) -> Result<Recorder> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let wav = wav_path(path, format);
+        let paused = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let (started, start) = mpsc::channel();
+        let deliver: Samples = {
+            let sender = sender.clone();
+            let paused = paused.clone();
+            Box::new(move |samples: &[f32]| {
+                if !paused.load(Ordering::Relaxed) {
+                    let _ = sender.send(Message::Samples(samples.to_vec()));
+                }
+            })
+        };
+        let thread = thread::Builder::new()
+            .name("taunote-recorder".to_string())
+            .spawn(move || record(input, deliver, &wav, started, receiver, on_level))?;
+        let input_format = start.recv().map_err(|_| {
+            TaunoteError::Internal("The recorder stopped before it started".to_string())
+        })??;
+        Ok(Recorder {
+            path: path.to_path_buf(),
+            format,
+            input_format,
+            paused,
+            sender,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn input_format(&self) -> InputFormat {
+        self.input_format
+    }
+
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+    }
+
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Relaxed)
+    }
+
+    // Closes the input and finishes the file, blocks while FLAC is encoded
+    pub fn stop(mut self) -> Result<Recording> {
+        let samples = self.join()?;
+        let wav = wav_path(&self.path, self.format);
+        if self.format == RecordingFormat::Flac {
+            let encoded = encode_audio(&wav, &self.path, FLAC_CODEC);
+            if encoded.is_ok() {
+                let _ = fs::remove_file(&wav);
+            }
+            encoded?;
+        }
+        let frames = samples / u64::from(self.input_format.channels.max(1));
+        Ok(Recording {
+            path: self.path.clone(),
+            format: self.format,
+            sample_rate: self.input_format.sample_rate,
+            channels: self.input_format.channels,
+            duration_seconds: frames as f64 / f64::from(self.input_format.sample_rate.max(1)),
+        })
+    }
+
+    // Stops and removes what was recorded
+    pub fn cancel(mut self) -> Result<()> {
+        let joined = self.join();
+        let _ = fs::remove_file(wav_path(&self.path, self.format));
+        joined.map(|_| ())
+    }
+
+    // Number of samples written
+    fn join(&mut self) -> Result<u64> {
+        let _ = self.sender.send(Message::Stop);
+        let thread = self.thread.take().ok_or_else(|| {
+            TaunoteError::Internal("The recorder was already stopped".to_string())
+        })?;
+        thread
+            .join()
+            .map_err(|_| TaunoteError::Internal("The recorder thread panicked".to_string()))?
+    }
+}
+
+// A recorder that is dropped still closes its input and finishes its file
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        let _ = self.sender.send(Message::Stop);
+    }
+}
+
+fn wav_path(path: &Path, format: RecordingFormat) -> PathBuf {
+    match format {
+        RecordingFormat::Wav => path.to_path_buf(),
+        RecordingFormat::Flac => path.with_extension("wav"),
+    }
+}
+
+fn wav_error(err: hound::Error) -> TaunoteError {
+    match err {
+        hound::Error::IoError(err) => TaunoteError::Io(err),
+        err => TaunoteError::Internal(format!("Could not write the recording: {err}")),
+    }
+}
+
+// The recorder's thread, reports on `started` whether the input and the
+// file could be opened, then writes until Stop
+fn record(
+    input: Box<dyn Input>,
+    deliver: Samples,
+    wav: &Path,
+    started: mpsc::Sender<Result<InputFormat>>,
+    messages: mpsc::Receiver<Message>,
+    on_level: impl Fn(Level),
+) -> Result<u64> {
+    let opened = input.start(deliver).and_then(|(format, guard)| {
+        let spec = WavSpec {
+            channels: format.channels,
+            sample_rate: format.sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let writer = WavWriter::create(wav, spec).map_err(wav_error)?;
+        Ok((format, guard, writer))
+    });
+    let (format, guard, mut writer) = match opened {
+        Ok(opened) => opened,
+        Err(err) => {
+            let _ = started.send(Err(err));
+            return Ok(0);
+        }
+    };
+    let _ = started.send(Ok(format));
+
+    let mut meter = Meter::new(format);
+    for message in messages {
+        let samples = match message {
+            Message::Samples(samples) => samples,
+            Message::Stop => break,
+        };
+        for sample in &samples {
+            let sample = (sample.clamp(-1.0, 1.0) * f32::from(i16::MAX)) as i16;
+            writer.write_sample(sample).map_err(wav_error)?;
+        }
+        meter.add(&samples, &on_level);
+    }
+    // the file is complete by the time the input is closed
+    writer.finalize().map_err(wav_error)?;
+    drop(guard);
+    Ok(meter.samples)
+}
+
+struct Meter {
+    block: u64,
+    per_second: f64,
+    samples: u64,
+    peak: f32,
+    squares: f64,
+    count: u64,
+}
+
+impl Meter {
+    fn new(format: InputFormat) -> Self {
+        let per_second = f64::from(format.sample_rate) * f64::from(format.channels);
+        Meter {
+            block: ((per_second * LEVEL_INTERVAL) as u64).max(1),
+            per_second: per_second.max(1.0),
+            samples: 0,
+            peak: 0.0,
+            squares: 0.0,
+            count: 0,
+        }
+    }
+
+    fn add(&mut self, samples: &[f32], on_level: &impl Fn(Level)) {
+        for sample in samples {
+            let sample = sample.clamp(-1.0, 1.0);
+            self.samples += 1;
+            self.peak = self.peak.max(sample.abs());
+            self.squares += f64::from(sample) * f64::from(sample);
+            self.count += 1;
+            if self.count == self.block {
+                on_level(Level {
+                    peak: self.peak,
+                    rms: (self.squares / self.count as f64).sqrt() as f32,
+                    seconds: self.samples as f64 / self.per_second,
+                });
+                self.peak = 0.0;
+                self.squares = 0.0;
+                self.count = 0;
+            }
+        }
+    }
+}
+
+// An input without a device: samples arrive when they are pushed through its
+// NullFeed, so tests can record without a microphone
+pub struct NullInput {
+    format: InputFormat,
+    deliver: Arc<Mutex<Option<Samples>>>,
+}
+
+#[derive(Clone)]
+pub struct NullFeed {
+    deliver: Arc<Mutex<Option<Samples>>>,
+}
+
+impl NullInput {
+    pub fn new(format: InputFormat) -> (NullInput, NullFeed) {
+        let deliver = Arc::new(Mutex::new(None));
+        (
+            NullInput {
+                format,
+                deliver: deliver.clone(),
+            },
+            NullFeed { deliver },
+        )
+    }
+}
+
+impl NullFeed {
+    // Hands the samples to the recorder, false once the input is stopped
+    pub fn push(&self, samples: &[f32]) -> bool {
+        let Ok(mut deliver) = self.deliver.lock() else {
+            return false;
+        };
+        match deliver.as_mut() {
+            Some(deliver) => {
+                deliver(samples);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+struct NullGuard(Arc<Mutex<Option<Samples>>>);
+
+impl Drop for NullGuard {
+    fn drop(&mut self) {
+        if let Ok(mut deliver) = self.0.lock() {
+            *deliver = None;
+        }
+    }
+}
+
+impl Input for NullInput {
+    fn start(self: Box<Self>, deliver: Samples) -> Result<(InputFormat, Box<dyn Any>)> {
+        if let Ok(mut slot) = self.deliver.lock() {
+            *slot = Some(deliver);
+        }
+        Ok((self.format, Box::new(NullGuard(self.deliver))))
+    }
+}
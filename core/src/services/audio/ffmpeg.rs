@@ -1,5 +1,13 @@
-use anyhow::{anyhow, Result};
-use std::{path::Path, process::Command, fs::{OpenOptions, create_dir_all}};
+use crate::error::{Result, TaunoteError};
+use std::{
+    fs::{create_dir_all, OpenOptions},
+    path::Path,
+    process::Command,
+};
+
+// Remediation whenever ffmpeg is missing, decoding needs the ffmpeg binary
+pub const INSTALL_HINT: &str =
+    "install ffmpeg (apt install ffmpeg, brew install ffmpeg or conda install ffmpeg) and make sure it is on PATH";
 
 // run ffmpeg CLI to normalize and trim silence
 pub fn preprocess_audio(input: &Path, output: &Path) -> Result<()> {
@@ -7,10 +15,10 @@ pub fn preprocess_audio(input: &Path, output: &Path) -> Result<()> {
     if let Some(parent) = output.parent() {
         create_dir_all(parent)?;
     }
-    let output_file = OpenOptions::new()
-        .read(true)
+    OpenOptions::new()
         .write(true)
         .create(true)
+        .truncate(true)
         .open(output)?;
 
     let status = Command::new("ffmpeg")
@@ -26,14 +34,37 @@ pub fn preprocess_audio(input: &Path, output: &Path) -> Result<()> {
         .arg("-sample_fmt")
         .arg("s16")
         .arg(output.as_os_str())
-        .status()?;
+        .status();
+    check_status(status)
+}
+
+// Re-encodes a recording for storage, `codec` are the ffmpeg output options
+pub fn encode_audio(input: &Path, output: &Path, codec: &[&str]) -> Result<()> {
+    if let Some(parent) = output.parent() {
+        create_dir_all(parent)?;
+    }
+    let status = Command::new("ffmpeg")
+        .args(["-y", "-loglevel", "error", "-i"])
+        .arg(input.as_os_str())
+        .arg("-vn") // drop cover art and video tracks
+        .args(codec)
+        .arg(output.as_os_str())
+        .status();
+    check_status(status)
+}
+
+fn check_status(status: std::io::Result<std::process::ExitStatus>) -> Result<()> {
+    let status = status.map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => TaunoteError::missing_dependency("ffmpeg", INSTALL_HINT),
+        _ => TaunoteError::Io(err),
+    })?;
 
     if status.success() {
         Ok(())
     } else {
-        Err(anyhow!(
+        Err(TaunoteError::AudioDecode(format!(
             "ffmpeg failed with exit status: {:?}",
             status.code()
-        ))
+        )))
     }
 }
@@ -0,0 +1,136 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::audio::capture::{Input, InputFormat, Samples};
+use serde::Serialize;
+use std::any::Any;
+
+#[cfg(not(feature = "capture"))]
+const NO_CAPTURE: &str = "this build of taunote cannot record, rebuild it with --features capture";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InputDevice {
+    pub name: String,
+    pub is_default: bool,
+}
+
+// A microphone of the system's default audio host, the default one for None.
+// The device is looked up when recording starts, on the recorder's thread.
+pub struct Microphone {
+    device: Option<String>,
+}
+
+impl Microphone {
+    pub fn new(device: Option<&str>) -> Self {
+        Microphone {
+            device: device.map(str::to_string),
+        }
+    }
+
+    pub fn device(&self) -> Option<&str> {
+        self.device.as_deref()
+    }
+}
+
+#[cfg(feature = "capture")]
+mod cpal_input {
+    use super::*;
+    use cpal::{
+        traits::{DeviceTrait, HostTrait, StreamTrait},
+        Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+    };
+
+    fn device_error(err: impl std::fmt::Display) -> TaunoteError {
+        TaunoteError::Config(format!("Microphone unavailable: {err}"))
+    }
+
+    pub fn input_devices() -> Result<Vec<InputDevice>> {
+        let host = cpal::default_host();
+        let default = host
+            .default_input_device()
+            .and_then(|device| device.name().ok());
+        Ok(host
+            .input_devices()
+            .map_err(device_error)?
+            .filter_map(|device| device.name().ok())
+            .map(|name| InputDevice {
+                is_default: default.as_ref() == Some(&name),
+                name,
+            })
+            .collect())
+    }
+
+    fn find_device(name: Option<&str>) -> Result<Device> {
+        let host = cpal::default_host();
+        match name {
+            Some(name) => host
+                .input_devices()
+                .map_err(device_error)?
+                .find(|device| device.name().is_ok_and(|found| found == name))
+                .ok_or_else(|| TaunoteError::NotFound(format!("Microphone {name}"))),
+            None => host
+                .default_input_device()
+                .ok_or_else(|| TaunoteError::NotFound("A microphone".to_string())),
+        }
+    }
+
+    // Converts whatever the device delivers to f32 samples
+    fn build<T>(device: &Device, config: &StreamConfig, mut deliver: Samples) -> Result<Stream>
+    where
+        T: SizedSample,
+        f32: FromSample<T>,
+    {
+        let mut buffer = Vec::new();
+        device
+            .build_input_stream(
+                config,
+                move |data: &[T], _: &cpal::InputCallbackInfo| {
+                    buffer.clear();
+                    buffer.extend(data.iter().map(|sample| sample.to_sample::<f32>()));
+                    deliver(&buffer);
+                },
+                // a device that goes away leaves the recording as it was
+                |err| eprintln!("Microphone error: {err}"),
+                None,
+            )
+            .map_err(device_error)
+    }
+
+    impl Input for Microphone {
+        fn start(self: Box<Self>, deliver: Samples) -> Result<(InputFormat, Box<dyn Any>)> {
+            let device = find_device(self.device.as_deref())?;
+            let supported = device.default_input_config().map_err(device_error)?;
+            let config = supported.config();
+            let stream = match supported.sample_format() {
+                SampleFormat::F32 => build::<f32>(&device, &config, deliver)?,
+                SampleFormat::I16 => build::<i16>(&device, &config, deliver)?,
+                SampleFormat::U16 => build::<u16>(&device, &config, deliver)?,
+                SampleFormat::I32 => build::<i32>(&device, &config, deliver)?,
+                other => {
+                    return Err(TaunoteError::AudioDecode(format!(
+                        "Unsupported microphone sample format {other}"
+                    )))
+                }
+            };
+            stream.play().map_err(device_error)?;
+            let format = InputFormat {
+                sample_rate: config.sample_rate.0,
+                channels: config.channels,
+            };
+            Ok((format, Box::new(stream)))
+        }
+    }
+}
+
+#[cfg(feature = "capture")]
+pub use cpal_input::input_devices;
+
+#[cfg(not(feature = "capture"))]
+pub fn input_devices() -> Result<Vec<InputDevice>> {
+    Err(TaunoteError::Config(NO_CAPTURE.to_string()))
+}
+
+#[cfg(not(feature = "capture"))]
+impl Input for Microphone {
+    fn start(self: Box<Self>, _deliver: Samples) -> Result<(InputFormat, Box<dyn Any>)> {
+        Err(TaunoteError::Config(NO_CAPTURE.to_string()))
+    }
+}
@@ -1 +1,3 @@
+pub mod capture;
 pub mod ffmpeg;
+pub mod microphone;
@@ -0,0 +1,407 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::{
+    backup::manifest::{BackupKind, FileEntry, Manifest, DATA_DIR, FORMAT_VERSION, MANIFEST_FILE},
+    crypto::{
+        keys::{Keys, KEY_FILE},
+        vault,
+    },
+    database::schema::SCHEMA_VERSION,
+    media::store::hash_file,
+};
+use chrono::Utc;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use rusqlite::{backup::Backup, Connection};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+use uuid::Uuid;
+
+// Top-level entries of the data directory that make up the library, logs and
+// temporary files are left out. db/ only ever holds the online backup copy.
+const DATABASE: &str = "db/project.db";
+const LIBRARY: &[&str] = &["db", "groups", "media", "config.json", KEY_FILE];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupReport {
+    pub path: PathBuf,
+    pub manifest: Manifest,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreReport {
+    pub backup_id: String,
+    pub created_at: String,
+    pub files: usize,
+    // where the replaced library was moved, to be deleted once the restored
+    // one looks right
+    pub previous_data: Option<PathBuf>,
+}
+
+// Writes the library to a .tar.gz archive at `dest`, or into it when it is a
+// folder. With `since`, the manifest of an earlier backup, only files that
+// changed since are stored.
+pub fn create_backup(
+    conn: &Connection,
+    keys: Option<&Keys>,
+    base_dir: &Path,
+    dest: &Path,
+    since: Option<&Manifest>,
+) -> Result<BackupReport> {
+    let dest = if dest.is_dir() {
+        let suffix = if since.is_some() { "-incremental" } else { "" };
+        dest.join(format!(
+            "taunote-{}{suffix}.tar.gz",
+            Utc::now().format("%Y%m%d-%H%M%S")
+        ))
+    } else {
+        dest.to_path_buf()
+    };
+    let tmp_dir = base_dir.join("tmp");
+    fs::create_dir_all(&tmp_dir)?;
+    let snapshot = tmp_dir.join(format!("backup-{}.db", Uuid::new_v4()));
+    let manifest = snapshot_database(conn, keys, &snapshot).and_then(|schema_version| {
+        write_archive(base_dir, &snapshot, &dest, since, schema_version, keys)
+    });
+    let _ = fs::remove_file(&snapshot);
+    Ok(BackupReport {
+        path: dest,
+        manifest: manifest?,
+    })
+}
+
+// Consistent copy of the database even while it is being written, keyed like
+// the original when the library is encrypted
+fn snapshot_database(conn: &Connection, keys: Option<&Keys>, dest: &Path) -> Result<u32> {
+    let mut snapshot = Connection::open(dest)?;
+    if let Some(keys) = keys {
+        vault::apply_db_key(&snapshot, keys)?;
+    }
+    Backup::new(conn, &mut snapshot)?.run_to_completion(256, Duration::ZERO, None)?;
+    Ok(snapshot.query_row("PRAGMA user_version", [], |row| row.get(0))?)
+}
+
+fn write_archive(
+    base_dir: &Path,
+    snapshot: &Path,
+    dest: &Path,
+    since: Option<&Manifest>,
+    schema_version: u32,
+    keys: Option<&Keys>,
+) -> Result<Manifest> {
+    let mut sources = vec![(DATABASE.to_string(), snapshot.to_path_buf())];
+    sources.extend(library_files(base_dir)?);
+
+    // written under another name so a failed run leaves no archive behind
+    let partial = dest.with_file_name(format!(
+        "{}.partial",
+        dest.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let written = (|| {
+        let mut archive = tar::Builder::new(GzEncoder::new(
+            File::create(&partial)?,
+            Compression::default(),
+        ));
+        let mut files = Vec::new();
+        let mut stored = Vec::new();
+        for (path, source) in sources {
+            let entry = FileEntry {
+                sha256: hash_file(&source)?,
+                size: fs::metadata(&source)?.len(),
+                path,
+            };
+            let unchanged = since
+                .and_then(|base| base.file(&entry.path))
+                .is_some_and(|known| known == &entry);
+            if !unchanged {
+                archive.append_path_with_name(&source, format!("{DATA_DIR}/{}", entry.path))?;
+                stored.push(entry.path.clone());
+            }
+            files.push(entry);
+        }
+
+        let manifest = Manifest {
+            format_version: FORMAT_VERSION,
+            id: Uuid::new_v4().to_string(),
+            kind: if since.is_some() {
+                BackupKind::Incremental
+            } else {
+                BackupKind::Full
+            },
+            base_id: since.map(|base| base.id.clone()),
+            created_at: Utc::now().to_rfc3339(),
+            schema_version,
+            encrypted: keys.is_some(),
+            files,
+            stored,
+        };
+        let json = serde_json::to_vec_pretty(&manifest)?;
+        let mut header = tar::Header::new_gnu();
+        header.set_size(json.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(Utc::now().timestamp().max(0) as u64);
+        header.set_cksum();
+        archive.append_data(&mut header, MANIFEST_FILE, json.as_slice())?;
+        archive.into_inner()?.finish()?;
+        Ok(manifest)
+    })();
+    match written {
+        Ok(manifest) => {
+            fs::rename(&partial, dest)?;
+            Ok(manifest)
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&partial);
+            Err(err)
+        }
+    }
+}
+
+// Files of the library besides the database, sorted by path
+fn library_files(base_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+    fn walk(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let relative = format!("{prefix}/{name}");
+            if path.is_dir() {
+                walk(&path, &relative, files)?;
+            } else if !name.contains(".partial.") {
+                files.push((relative, path.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    for name in LIBRARY.iter().filter(|name| **name != "db") {
+        let path = base_dir.join(name);
+        if path.is_dir() {
+            walk(&path, name, &mut files)?;
+        } else if path.is_file() {
+            files.push((name.to_string(), path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn invalid(archive: &Path, reason: impl std::fmt::Display) -> TaunoteError {
+    TaunoteError::InvalidInput(format!("{}: {reason}", archive.display()))
+}
+
+fn open_archive(archive: &Path) -> Result<tar::Archive<GzDecoder<File>>> {
+    Ok(tar::Archive::new(GzDecoder::new(File::open(archive)?)))
+}
+
+// Path of a library file in the archive, None for the manifest
+fn entry_path(archive: &Path, entry: &tar::Entry<impl Read>) -> Result<Option<String>> {
+    let name = entry.path()?.to_string_lossy().replace('\\', "/");
+    if name == MANIFEST_FILE {
+        return Ok(None);
+    }
+    let path = name
+        .strip_prefix(&format!("{DATA_DIR}/"))
+        .filter(|path| {
+            Path::new(path)
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)))
+        })
+        .ok_or_else(|| invalid(archive, format!("unexpected entry {name}")))?;
+    Ok(Some(path.to_string()))
+}
+
+// Checks every stored file against the manifest's checksums without
+// extracting anything, and returns the manifest
+pub fn read_backup(archive: &Path) -> Result<Manifest> {
+    let mut checksums: HashMap<String, (String, u64)> = HashMap::new();
+    let mut manifest: Option<Manifest> = None;
+    for entry in open_archive(archive)?.entries()? {
+        let mut entry = entry?;
+        match entry_path(archive, &entry)? {
+            None => {
+                let mut json = String::new();
+                entry.read_to_string(&mut json)?;
+                manifest = Some(
+                    serde_json::from_str(&json)
+                        .map_err(|e| invalid(archive, format!("invalid manifest: {e}")))?,
+                );
+            }
+            Some(path) => {
+                let mut hasher = Sha256::new();
+                let size = io::copy(&mut entry, &mut hasher)?;
+                checksums.insert(path, (hex::encode(hasher.finalize()), size));
+            }
+        }
+    }
+
+    let manifest = manifest.ok_or_else(|| invalid(archive, "not a taunote backup"))?;
+    if manifest.format_version > FORMAT_VERSION || manifest.schema_version > SCHEMA_VERSION {
+        return Err(invalid(archive, "made by a newer version of taunote"));
+    }
+    if checksums.len() != manifest.stored.len() {
+        return Err(invalid(archive, "files do not match the manifest"));
+    }
+    for path in &manifest.stored {
+        let expected = manifest
+            .file(path)
+            .ok_or_else(|| invalid(archive, format!("{path} is missing from the manifest")))?;
+        match checksums.get(path) {
+            Some((sha256, size)) if *sha256 == expected.sha256 && *size == expected.size => {}
+            _ => return Err(invalid(archive, format!("{path} is damaged"))),
+        }
+    }
+    if manifest.kind == BackupKind::Full && manifest.stored.len() != manifest.files.len() {
+        return Err(invalid(archive, "full backup with missing files"));
+    }
+    Ok(manifest)
+}
+
+// Verifies a full backup followed by the incremental backups built on it, in
+// order, and returns their manifests
+pub fn verify_chain(archives: &[PathBuf]) -> Result<Vec<Manifest>> {
+    let first = archives
+        .first()
+        .ok_or_else(|| TaunoteError::InvalidInput("No backup given".to_string()))?;
+    let manifests = archives
+        .iter()
+        .map(|archive| read_backup(archive))
+        .collect::<Result<Vec<_>>>()?;
+    if manifests[0].kind != BackupKind::Full {
+        return Err(invalid(
+            first,
+            "this is an incremental backup, give the full backup it builds on first",
+        ));
+    }
+    for (index, pair) in manifests.windows(2).enumerate() {
+        if pair[1].base_id.as_ref() != Some(&pair[0].id) {
+            return Err(invalid(
+                &archives[index + 1],
+                format!("does not follow {}", archives[index].display()),
+            ));
+        }
+    }
+    Ok(manifests)
+}
+
+// Replaces the library in `base_dir` with the one in the backups, see
+// verify_chain. Nothing is touched unless every archive checks out; the
+// current library is moved aside rather than deleted.
+pub fn restore_backup(base_dir: &Path, archives: &[PathBuf]) -> Result<RestoreReport> {
+    let manifests = verify_chain(archives)?;
+    let last = &manifests[manifests.len() - 1];
+
+    // the newest archive holding each file in its final version
+    let mut sources: HashMap<&str, usize> = HashMap::new();
+    for file in &last.files {
+        let index = (0..manifests.len())
+            .rev()
+            .find(|&index| {
+                manifests[index].stored.contains(&file.path)
+                    && manifests[index].file(&file.path) == Some(file)
+            })
+            .ok_or_else(|| TaunoteError::InvalidInput(format!("No backup holds {}", file.path)))?;
+        sources.insert(&file.path, index);
+    }
+
+    fs::create_dir_all(base_dir)?;
+    let staging = base_dir.join(format!(".restore-{}", Uuid::new_v4()));
+    let restored =
+        extract(archives, last, &sources, &staging).and_then(|()| swap_library(base_dir, &staging));
+    if restored.is_err() {
+        let _ = fs::remove_dir_all(&staging);
+    }
+    let previous_data = restored?;
+    // keys of the replaced library must not be used on the restored one
+    vault::remove(base_dir);
+    Ok(RestoreReport {
+        backup_id: last.id.clone(),
+        created_at: last.created_at.clone(),
+        files: last.files.len(),
+        previous_data,
+    })
+}
+
+fn extract(
+    archives: &[PathBuf],
+    last: &Manifest,
+    sources: &HashMap<&str, usize>,
+    staging: &Path,
+) -> Result<()> {
+    for (index, archive) in archives.iter().enumerate() {
+        for entry in open_archive(archive)?.entries()? {
+            let mut entry = entry?;
+            let Some(path) = entry_path(archive, &entry)? else {
+                continue;
+            };
+            if sources.get(path.as_str()) != Some(&index) {
+                continue;
+            }
+            let dest = staging.join(&path);
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            io::copy(&mut entry, &mut File::create(&dest)?)?;
+            // the archive may have changed since it was verified
+            let expected = last.file(&path).map(|file| file.sha256.as_str());
+            if expected != Some(hash_file(&dest)?.as_str()) {
+                return Err(invalid(archive, format!("{path} is damaged")));
+            }
+        }
+    }
+    Ok(())
+}
+
+// A rename that fails halfway puts back what was already moved, the library
+// is never left half replaced
+fn swap_library(base_dir: &Path, staging: &Path) -> Result<Option<PathBuf>> {
+    let previous = base_dir.join(format!(
+        "pre-restore-{}",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    ));
+    let mut moved_out = Vec::new();
+    let mut moved_in = Vec::new();
+    if let Err(err) = move_entries(base_dir, staging, &previous, &mut moved_out, &mut moved_in) {
+        for name in moved_in.iter().rev() {
+            let _ = fs::rename(base_dir.join(name), staging.join(name));
+        }
+        for name in moved_out.iter().rev() {
+            let _ = fs::rename(previous.join(name), base_dir.join(name));
+        }
+        // only removed when it is empty again
+        let _ = fs::remove_dir(&previous);
+        return Err(err);
+    }
+    fs::remove_dir_all(staging)?;
+    Ok(previous.exists().then_some(previous))
+}
+
+fn move_entries(
+    base_dir: &Path,
+    staging: &Path,
+    previous: &Path,
+    moved_out: &mut Vec<&'static str>,
+    moved_in: &mut Vec<&'static str>,
+) -> Result<()> {
+    for name in LIBRARY {
+        let current = base_dir.join(name);
+        if current.exists() {
+            fs::create_dir_all(previous)?;
+            fs::rename(&current, previous.join(name))?;
+            moved_out.push(name);
+        }
+    }
+    for name in LIBRARY {
+        let staged = staging.join(name);
+        if staged.exists() {
+            fs::rename(&staged, base_dir.join(name))?;
+            moved_in.push(name);
+        }
+    }
+    Ok(())
+}
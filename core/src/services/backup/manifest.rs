@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+
+// Last entry of every archive, the library's files are stored under data/
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const DATA_DIR: &str = "data";
+pub const FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    Full,
+    // only the files that changed since the backup `base_id`
+    Incremental,
+}
+
+// A file of the library, `path` is relative to the data directory with / separators
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEntry {
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format_version: u32,
+    pub id: String,
+    pub kind: BackupKind,
+    pub base_id: Option<String>,
+    pub created_at: String,
+    // user_version of the database in the archive
+    pub schema_version: u32,
+    pub encrypted: bool,
+    // every file of the library when the backup was taken, including those an
+    // incremental backup left out because they did not change
+    pub files: Vec<FileEntry>,
+    // the paths of `files` stored in this archive
+    pub stored: Vec<String>,
+}
+
+impl Manifest {
+    pub fn file(&self, path: &str) -> Option<&FileEntry> {
+        self.files.iter().find(|file| file.path == path)
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        self.files.iter().map(|file| file.size).sum()
+    }
+}
@@ -0,0 +1,154 @@
+use crate::error::Result;
+use crate::services::{
+    bundle::manifest::{
+        BundleFile, BundleManifest, BUNDLE_EXTENSION, FORMAT_VERSION, MANIFEST_FILE, MEDIA_DIR,
+        PROJECT_DIR,
+    },
+    database::{
+        models::{AudioProject, Media, ProjectGroup},
+        queries::{get_media, get_project_metadata, list_project_groups},
+        versions::project_versions,
+    },
+    media::store::{hash_file, media_path},
+    paths::slug::file_name,
+    sync::markdown::project_dir,
+};
+use chrono::Utc;
+use rusqlite::Connection;
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+// Inside a project folder, the notes come from the database instead and
+// redacted copies are not shared
+const REDACTED_DIR: &str = "redacted";
+
+// Writes the project to a bundle at `dest`, or into it when it is a folder,
+// and returns the bundle's path
+pub fn export_bundle(
+    conn: &Connection,
+    base_dir: &Path,
+    project: &AudioProject,
+    dest: &Path,
+) -> Result<PathBuf> {
+    let dest = if dest.is_dir() {
+        dest.join(format!("{}.{BUNDLE_EXTENSION}", file_name(&project.name, &project.id)))
+    } else {
+        dest.to_path_buf()
+    };
+
+    let mut sources = Vec::new();
+    let mut media = None;
+    if let Some(hash) = &project.media_hash {
+        if let Some(stored) = get_media(conn, hash)? {
+            let path = media_path(base_dir, &stored);
+            if path.is_file() {
+                let extension = path.extension().unwrap_or_default().to_string_lossy();
+                let name = format!("{MEDIA_DIR}/{hash}.{extension}");
+                sources.push((name.clone(), path));
+                media = Some(Media {
+                    relative_path: name,
+                    ..stored
+                });
+            }
+        }
+    }
+    let folder = project_dir(base_dir, project)?;
+    if folder.is_dir() {
+        project_files(&folder, PROJECT_DIR, true, &mut sources)?;
+    }
+
+    let group = list_project_groups(conn)?
+        .into_iter()
+        .find(|group| group.id == project.group_id)
+        .unwrap_or_else(|| ProjectGroup {
+            id: project.group_id.clone(),
+            name: project.group_id.clone(),
+            parent_id: None,
+        });
+    let versions = project_versions(conn, &project.id)?;
+    let metadata = get_project_metadata(conn, &project.id)?;
+
+    let partial = dest.with_file_name(format!(
+        "{}.partial",
+        dest.file_name().unwrap_or_default().to_string_lossy()
+    ));
+    let written = (|| {
+        let mut zip = ZipWriter::new(File::create(&partial)?);
+        let mut files = Vec::new();
+        for (name, source) in sources {
+            // recordings are compressed already
+            let method = if name.starts_with(MEDIA_DIR) {
+                CompressionMethod::Stored
+            } else {
+                CompressionMethod::Deflated
+            };
+            zip.start_file(
+                name.as_str(),
+                SimpleFileOptions::default()
+                    .compression_method(method)
+                    .large_file(true),
+            )?;
+            let size = io::copy(&mut File::open(&source)?, &mut zip)?;
+            files.push(BundleFile {
+                sha256: hash_file(&source)?,
+                size,
+                path: name,
+            });
+        }
+
+        let manifest = BundleManifest {
+            format_version: FORMAT_VERSION,
+            exported_at: Utc::now().to_rfc3339(),
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            group,
+            project: project.clone(),
+            metadata,
+            versions,
+            media,
+            files,
+        };
+        zip.start_file(MANIFEST_FILE, SimpleFileOptions::default())?;
+        serde_json::to_writer_pretty(&mut zip, &manifest)?;
+        zip.finish()?;
+        Ok(())
+    })();
+    match written {
+        Ok(()) => {
+            fs::rename(&partial, &dest)?;
+            Ok(dest)
+        }
+        Err(err) => {
+            let _ = fs::remove_file(&partial);
+            Err(err)
+        }
+    }
+}
+
+// Files of the project folder besides its notes, sorted by path
+fn project_files(
+    dir: &Path,
+    prefix: &str,
+    top_level: bool,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<()> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let relative = format!("{prefix}/{name}");
+        if path.is_dir() {
+            if !(top_level && name == REDACTED_DIR) {
+                project_files(&path, &relative, false, files)?;
+            }
+        } else if !(top_level && (name.ends_with(".md") || name.ends_with(".md.enc"))) {
+            files.push((relative, path));
+        }
+    }
+    Ok(())
+}
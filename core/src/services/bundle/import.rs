@@ -0,0 +1,342 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::{
+    bundle::manifest::{BundleManifest, FORMAT_VERSION, MANIFEST_FILE, MEDIA_DIR, PROJECT_DIR},
+    database::{
+        groups::{new_group_id, project_relative_path},
+        models::{AudioProject, Media, NoteVersion, ProjectGroup},
+        queries::{
+            ensure_project_group, insert_audio_project, list_project_groups, set_project_metadata,
+        },
+        versions::import_versions,
+    },
+    media::store::{adopt_media, hash_file, remove_unreferenced},
+    sync::markdown::{export_note, project_dir},
+};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+use uuid::Uuid;
+use zip::ZipArchive;
+
+// What to do when the library already has a group with the bundle's group name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupCollision {
+    // import into the existing group
+    #[default]
+    Merge,
+    // create "name (2)", "name (3)", ... instead
+    Rename,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImportOptions {
+    // group name to import into instead of the one in the bundle
+    pub group: Option<String>,
+    pub on_collision: GroupCollision,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    // with the ids of this library
+    pub project: AudioProject,
+    pub group: ProjectGroup,
+    pub created_group: bool,
+    // id the project had in the exporting library
+    pub original_id: String,
+    pub versions: usize,
+}
+
+fn invalid(bundle: &Path, reason: impl std::fmt::Display) -> TaunoteError {
+    TaunoteError::InvalidInput(format!("{}: {reason}", bundle.display()))
+}
+
+// A single normal path component, so it can name a folder or file
+fn plain_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none()
+}
+
+// Relative path without .. or a root, with / separators
+fn plain_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains('\\')
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+// Reads the manifest and checks every file in the bundle against it without
+// extracting anything
+pub fn read_bundle(bundle: &Path) -> Result<BundleManifest> {
+    let mut zip = ZipArchive::new(File::open(bundle)?)?;
+    let manifest: BundleManifest = {
+        let mut entry = zip
+            .by_name(MANIFEST_FILE)
+            .map_err(|_| invalid(bundle, "not a taunote bundle"))?;
+        let mut json = String::new();
+        entry.read_to_string(&mut json)?;
+        serde_json::from_str(&json)
+            .map_err(|e| invalid(bundle, format!("invalid manifest: {e}")))?
+    };
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(invalid(bundle, "made by a newer version of taunote"));
+    }
+
+    let mut seen = HashSet::new();
+    for index in 0..zip.len() {
+        let mut entry = zip.by_index(index)?;
+        if entry.is_dir() || entry.name() == MANIFEST_FILE {
+            continue;
+        }
+        let name = entry.name().to_string();
+        let expected = manifest
+            .file(&name)
+            .ok_or_else(|| invalid(bundle, format!("{name} is missing from the manifest")))?;
+        let mut hasher = Sha256::new();
+        let size = io::copy(&mut entry, &mut hasher)?;
+        if hex::encode(hasher.finalize()) != expected.sha256 || size != expected.size {
+            return Err(invalid(bundle, format!("{name} is damaged")));
+        }
+        seen.insert(name);
+    }
+    for file in &manifest.files {
+        let inside = file.path.starts_with(&format!("{MEDIA_DIR}/"))
+            || file.path.starts_with(&format!("{PROJECT_DIR}/"));
+        if !inside || !plain_path(&file.path) {
+            return Err(invalid(bundle, format!("unexpected file {}", file.path)));
+        }
+        if !seen.contains(&file.path) {
+            return Err(invalid(bundle, format!("{} is missing", file.path)));
+        }
+    }
+    if let Some(media) = &manifest.media {
+        let sha256 = media.hash.len() == 64 && media.hash.bytes().all(|b| b.is_ascii_hexdigit());
+        if !sha256 || manifest.file(&media.relative_path).is_none() {
+            return Err(invalid(bundle, "the recording does not match the manifest"));
+        }
+    }
+    ordered_versions(bundle, &manifest)?;
+    Ok(manifest)
+}
+
+// The versions with each parent before its children, after checking they all
+// belong to the project and form one history per note
+fn ordered_versions<'a>(
+    bundle: &Path,
+    manifest: &'a BundleManifest,
+) -> Result<Vec<&'a NoteVersion>> {
+    let mut ids = HashSet::new();
+    let mut current = HashSet::new();
+    for version in &manifest.versions {
+        let kind_ok = !version.note_kind.is_empty()
+            && version
+                .note_kind
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if version.project_id != manifest.project.id || !kind_ok {
+            return Err(invalid(
+                bundle,
+                format!("unexpected note version {}", version.id),
+            ));
+        }
+        if !ids.insert(version.id.as_str()) {
+            return Err(invalid(
+                bundle,
+                format!("note version {} appears twice", version.id),
+            ));
+        }
+        if version.is_current && !current.insert(version.note_kind.as_str()) {
+            return Err(invalid(
+                bundle,
+                format!("{} has two current versions", version.note_kind),
+            ));
+        }
+    }
+
+    let mut ordered = Vec::with_capacity(manifest.versions.len());
+    let mut placed = HashSet::new();
+    while ordered.len() < manifest.versions.len() {
+        let before = ordered.len();
+        for version in &manifest.versions {
+            let ready = match &version.parent_id {
+                Some(parent) => placed.contains(parent.as_str()),
+                None => true,
+            };
+            if ready && !placed.contains(version.id.as_str()) {
+                placed.insert(version.id.as_str());
+                ordered.push(version);
+            }
+        }
+        // a parent that is missing or a cycle
+        if ordered.len() == before {
+            return Err(invalid(bundle, "note versions do not form a history"));
+        }
+    }
+    Ok(ordered)
+}
+
+// The group the project goes to and whether it has to be created
+fn target_group(
+    conn: &Connection,
+    manifest: &BundleManifest,
+    options: &ImportOptions,
+) -> Result<(ProjectGroup, bool)> {
+    let name = options
+        .group
+        .as_deref()
+        .unwrap_or(&manifest.group.name)
+        .trim()
+        .to_string();
+    if !plain_name(&name) {
+        return Err(TaunoteError::InvalidInput(format!(
+            "\"{name}\" cannot be used as a group name"
+        )));
+    }
+    let groups = list_project_groups(conn)?;
+    // bundles go to a top level group
+    let taken = |name: &str| {
+        groups
+            .iter()
+            .find(|group| group.id == name || (group.name == name && group.parent_id.is_none()))
+    };
+    match (taken(&name), options.on_collision) {
+        (None, _) => Ok((
+            ProjectGroup {
+                id: new_group_id(conn, &name)?,
+                name,
+                parent_id: None,
+            },
+            true,
+        )),
+        (Some(group), GroupCollision::Merge) => Ok((group.clone(), false)),
+        (Some(_), GroupCollision::Rename) => {
+            let name = (2..)
+                .map(|n| format!("{name} ({n})"))
+                .find(|candidate| taken(candidate).is_none())
+                .expect("an unused group name");
+            Ok((
+                ProjectGroup {
+                    id: new_group_id(conn, &name)?,
+                    name,
+                    parent_id: None,
+                },
+                true,
+            ))
+        }
+    }
+}
+
+// Adds the bundle's project to the library under new ids, with its recording,
+// its folder and the full history of its notes
+pub fn import_bundle(
+    conn: &mut Connection,
+    base_dir: &Path,
+    bundle: &Path,
+    options: &ImportOptions,
+) -> Result<ImportReport> {
+    let manifest = read_bundle(bundle)?;
+    let (group, created_group) = target_group(conn, &manifest, options)?;
+
+    let id = Uuid::new_v4().to_string();
+    let project = AudioProject {
+        relative_path: project_relative_path(&group.id, &id),
+        id,
+        group_id: group.id.clone(),
+        media_hash: manifest.media.as_ref().map(|media| media.hash.clone()),
+        ..manifest.project.clone()
+    };
+    let version_ids: HashMap<&str, String> = manifest
+        .versions
+        .iter()
+        .map(|version| (version.id.as_str(), Uuid::new_v4().to_string()))
+        .collect();
+    let versions: Vec<NoteVersion> = ordered_versions(bundle, &manifest)?
+        .into_iter()
+        .map(|version| NoteVersion {
+            id: version_ids[version.id.as_str()].clone(),
+            project_id: project.id.clone(),
+            parent_id: version
+                .parent_id
+                .as_ref()
+                .map(|parent| version_ids[parent.as_str()].clone()),
+            ..version.clone()
+        })
+        .collect();
+
+    let folder = project_dir(base_dir, &project)?;
+    let imported = (|| {
+        fs::create_dir_all(&folder)?;
+        let tmp_dir = base_dir.join("tmp").join(Uuid::new_v4().to_string());
+        let extracted = extract(bundle, &manifest, &folder, &tmp_dir);
+        let media = extracted.and_then(|recording| match (&manifest.media, recording) {
+            (Some(media), Some(path)) => adopt_media(conn, base_dir, media, &path).map(Some),
+            _ => Ok(None::<Media>),
+        });
+        let _ = fs::remove_dir_all(&tmp_dir);
+        media?;
+
+        let tx = conn.transaction()?;
+        ensure_project_group(&tx, &group.id, &group.name)?;
+        insert_audio_project(&tx, &project)?;
+        set_project_metadata(&tx, &project.id, &manifest.metadata)?;
+        import_versions(&tx, &versions)?;
+        tx.commit()?;
+        for version in versions.iter().filter(|version| version.is_current) {
+            export_note(conn, base_dir, &project, &version.note_kind)?;
+        }
+        Ok(())
+    })();
+    if let Err(err) = imported {
+        let _ = fs::remove_dir_all(&folder);
+        let _ = remove_unreferenced(conn, base_dir);
+        return Err(err);
+    }
+
+    Ok(ImportReport {
+        project,
+        group,
+        created_group,
+        original_id: manifest.project.id,
+        versions: versions.len(),
+    })
+}
+
+// Writes the project files into `folder` and the recording into `tmp_dir`,
+// checking each against the manifest again as the bundle may have changed
+fn extract(
+    bundle: &Path,
+    manifest: &BundleManifest,
+    folder: &Path,
+    tmp_dir: &Path,
+) -> Result<Option<PathBuf>> {
+    let mut zip = ZipArchive::new(File::open(bundle)?)?;
+    let mut recording = None;
+    for file in &manifest.files {
+        let dest = match file.path.strip_prefix(&format!("{PROJECT_DIR}/")) {
+            Some(relative) => folder.join(relative),
+            None => {
+                let dest = tmp_dir.join(file.path.rsplit('/').next().unwrap_or_default());
+                if manifest.media.as_ref().map(|media| &media.relative_path) == Some(&file.path) {
+                    recording = Some(dest.clone());
+                }
+                dest
+            }
+        };
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut zip.by_name(&file.path)?, &mut File::create(&dest)?)?;
+        if hash_file(&dest)? != file.sha256 {
+            return Err(invalid(bundle, format!("{} is damaged", file.path)));
+        }
+    }
+    Ok(recording)
+}
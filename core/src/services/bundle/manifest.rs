@@ -0,0 +1,45 @@
+use crate::services::database::models::{
+    AudioProject, Media, NoteVersion, ProjectGroup, ProjectMetadata,
+};
+use serde::{Deserialize, Serialize};
+
+// A .taunote bundle is a zip file with the manifest and the project's files:
+// its recording under media/ and its folder, without the notes, under project/
+pub const BUNDLE_EXTENSION: &str = "taunote";
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const MEDIA_DIR: &str = "media";
+pub const PROJECT_DIR: &str = "project";
+pub const FORMAT_VERSION: u32 = 1;
+
+// A file in the bundle, `path` uses / separators
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleFile {
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub format_version: u32,
+    pub exported_at: String,
+    // taunote version that wrote the bundle
+    pub app_version: String,
+    pub group: ProjectGroup,
+    // ids and paths are those of the exporting library, importing assigns new ones
+    pub project: AudioProject,
+    // missing in bundles written before projects had tags and metadata
+    #[serde(default)]
+    pub metadata: ProjectMetadata,
+    // every version of every note, the transcript included
+    pub versions: Vec<NoteVersion>,
+    // `relative_path` is the recording's path in the bundle
+    pub media: Option<Media>,
+    pub files: Vec<BundleFile>,
+}
+
+impl BundleManifest {
+    pub fn file(&self, path: &str) -> Option<&BundleFile> {
+        self.files.iter().find(|file| file.path == path)
+    }
+}
@@ -0,0 +1,402 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::database::models::CalendarEvent;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::{fs, path::Path};
+
+// NAME;PARAM=value:VALUE
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn param_is(&self, name: &str, values: &[&str]) -> bool {
+        self.param(name)
+            .is_some_and(|param| values.iter().any(|v| param.eq_ignore_ascii_case(v)))
+    }
+}
+
+// A DTSTART, DTEND or RECURRENCE-ID value
+struct Stamp {
+    at: DateTime<Utc>,
+    all_day: bool,
+    zone: Option<Tz>,
+}
+
+// What a VEVENT said so far
+#[derive(Default)]
+struct Draft {
+    uid: Option<String>,
+    summary: Option<String>,
+    start: Option<Stamp>,
+    end: Option<Stamp>,
+    duration: Option<Duration>,
+    recurrence_id: Option<Stamp>,
+    rrule: Option<String>,
+    exdates: Vec<DateTime<Utc>>,
+    attendees: Vec<String>,
+    organizer: Option<String>,
+    agenda: Option<String>,
+    location: Option<String>,
+    categories: Vec<String>,
+    cancelled: bool,
+}
+
+pub fn read_ics(path: &Path) -> Result<Vec<CalendarEvent>> {
+    parse_ics(&fs::read_to_string(path)?)
+}
+
+// Events of an iCalendar file. Cancelled events and those without a start are
+// left out, a moved occurrence of a recurring event becomes an event of its own
+// that the series skips.
+pub fn parse_ics(text: &str) -> Result<Vec<CalendarEvent>> {
+    let mut components: Vec<String> = Vec::new();
+    let mut draft = None;
+    let mut drafts = Vec::new();
+    let mut calendar = false;
+    for line in unfold(text) {
+        let Some(property) = parse_line(&line) else {
+            continue;
+        };
+        match property.name.as_str() {
+            "BEGIN" => {
+                let component = property.value.trim().to_ascii_uppercase();
+                calendar |= component == "VCALENDAR";
+                if component == "VEVENT" {
+                    draft = Some(Draft::default());
+                }
+                components.push(component);
+            }
+            "END" => {
+                let component = components.pop();
+                if component.as_deref() == Some("VEVENT") {
+                    drafts.extend(draft.take());
+                }
+            }
+            // alarms inside an event have properties of their own
+            _ if components.last().map(String::as_str) == Some("VEVENT") => {
+                if let Some(draft) = draft.as_mut() {
+                    draft.apply(property);
+                }
+            }
+            _ => {}
+        }
+    }
+    if !calendar {
+        return Err(TaunoteError::InvalidInput(
+            "Not an iCalendar file, BEGIN:VCALENDAR is missing".to_string(),
+        ));
+    }
+
+    let mut events = Vec::new();
+    let mut moved = Vec::new();
+    for draft in drafts {
+        if let (Some(uid), Some(recurrence_id)) = (&draft.uid, &draft.recurrence_id) {
+            moved.push((uid.clone(), timestamp(recurrence_id.at)));
+        }
+        events.extend(draft.finish());
+    }
+    for (uid, at) in moved {
+        let series = events
+            .iter_mut()
+            .find(|event| event.id == uid && event.rrule.is_some());
+        if let Some(series) = series {
+            if !series.exdates.contains(&at) {
+                series.exdates.push(at);
+            }
+        }
+    }
+    Ok(events)
+}
+
+impl Draft {
+    fn apply(&mut self, property: Property) {
+        let tzid = property.param("TZID");
+        match property.name.as_str() {
+            "UID" => self.uid = Some(property.value.trim().to_string()),
+            "SUMMARY" => self.summary = Some(unescape(&property.value)),
+            "DESCRIPTION" => self.agenda = Some(unescape(&property.value)),
+            "LOCATION" => self.location = Some(unescape(&property.value)),
+            "STATUS" => self.cancelled = property.value.eq_ignore_ascii_case("CANCELLED"),
+            "DTSTART" => self.start = parse_stamp(&property.value, tzid),
+            "DTEND" => self.end = parse_stamp(&property.value, tzid),
+            "DURATION" => self.duration = parse_duration(property.value.trim()),
+            "RECURRENCE-ID" => self.recurrence_id = parse_stamp(&property.value, tzid),
+            "RRULE" => self.rrule = Some(property.value.trim().to_string()),
+            "EXDATE" => self.exdates.extend(
+                property
+                    .value
+                    .split(',')
+                    .filter_map(|value| parse_stamp(value, tzid))
+                    .map(|stamp| stamp.at),
+            ),
+            "CATEGORIES" => self.categories.extend(
+                split_list(&property.value)
+                    .into_iter()
+                    .filter(|category| !category.is_empty()),
+            ),
+            "ORGANIZER" => self.organizer = person(&property),
+            "ATTENDEE" => {
+                // people who said no, rooms and resources were not in the meeting
+                let absent = property.param_is("PARTSTAT", &["DECLINED"])
+                    || property.param_is("ROLE", &["NON-PARTICIPANT"])
+                    || property.param_is("CUTYPE", &["ROOM", "RESOURCE"]);
+                if !absent {
+                    self.attendees.extend(person(&property));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> Option<CalendarEvent> {
+        let start = self.start?;
+        if self.cancelled {
+            return None;
+        }
+        let end = match (self.end, self.duration) {
+            (Some(end), _) if end.at >= start.at => end.at,
+            (_, Some(duration)) => start.at + duration,
+            _ if start.all_day => start.at + Duration::days(1),
+            _ => start.at,
+        };
+        let summary = self
+            .summary
+            .map(|summary| summary.trim().to_string())
+            .filter(|summary| !summary.is_empty())
+            .unwrap_or_else(|| "Meeting".to_string());
+        let uid = self
+            .uid
+            .unwrap_or_else(|| format!("{summary}@{}", timestamp(start.at)));
+        let id = match &self.recurrence_id {
+            Some(recurrence_id) => format!("{uid}/{}", timestamp(recurrence_id.at)),
+            None => uid,
+        };
+
+        let mut attendees: Vec<String> = Vec::new();
+        for name in self.organizer.into_iter().chain(self.attendees) {
+            if !attendees
+                .iter()
+                .any(|known| known.eq_ignore_ascii_case(&name))
+            {
+                attendees.push(name);
+            }
+        }
+        Some(CalendarEvent {
+            id,
+            summary,
+            starts_at: timestamp(start.at),
+            ends_at: timestamp(end),
+            all_day: start.all_day,
+            timezone: start.zone.map(|zone| zone.name().to_string()),
+            rrule: self.rrule.filter(|_| self.recurrence_id.is_none()),
+            exdates: self.exdates.into_iter().map(timestamp).collect(),
+            attendees,
+            agenda: self
+                .agenda
+                .map(|agenda| agenda.trim().to_string())
+                .filter(|agenda| !agenda.is_empty()),
+            location: self
+                .location
+                .map(|location| location.trim().to_string())
+                .filter(|location| !location.is_empty()),
+            categories: self.categories,
+        })
+    }
+}
+
+// How times are stored, e.g. 2025-01-31T09:00:00Z
+pub fn timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// A local time in `zone`, or on this computer for floating times. Times that
+// a DST change skips move forward by an hour.
+pub fn resolve(naive: NaiveDateTime, zone: Option<Tz>) -> Option<DateTime<Utc>> {
+    let resolve_exactly = |naive: NaiveDateTime| match zone {
+        Some(zone) => zone
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|at| at.with_timezone(&Utc)),
+        None => Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|at| at.with_timezone(&Utc)),
+    };
+    resolve_exactly(naive).or_else(|| resolve_exactly(naive + Duration::hours(1)))
+}
+
+// The zone of a TZID, None for names that are not IANA ones, e.g. Windows
+// zone names, which are then read as local time
+pub fn zone(tzid: &str) -> Option<Tz> {
+    tzid.trim().trim_start_matches('/').parse().ok()
+}
+
+// 20250131T090000Z in UTC, 20250131T100000 in `tzid` or local time, or the
+// whole day 20250131
+fn parse_stamp(value: &str, tzid: Option<&str>) -> Option<Stamp> {
+    let value = value.trim();
+    let zone = tzid.and_then(zone);
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some(Stamp {
+            at: resolve(date.and_hms_opt(0, 0, 0)?, zone)?,
+            all_day: true,
+            zone,
+        });
+    }
+    if let Some(utc) = value.strip_suffix(['Z', 'z']) {
+        let naive = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+        return Some(Stamp {
+            at: naive.and_utc(),
+            all_day: false,
+            zone,
+        });
+    }
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    Some(Stamp {
+        at: resolve(naive, zone)?,
+        all_day: false,
+        zone,
+    })
+}
+
+// DURATION values like PT1H30M, P1D or P2W
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut time = false;
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            'T' => time = true,
+            '0'..='9' => number.push(c),
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match (unit, time) {
+                    ('W', false) => Duration::weeks(n),
+                    ('D', false) => Duration::days(n),
+                    ('H', true) => Duration::hours(n),
+                    ('M', true) => Duration::minutes(n),
+                    ('S', true) => Duration::seconds(n),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(if negative { -total } else { total })
+}
+
+// The CN of an ATTENDEE or ORGANIZER, else its address
+fn person(property: &Property) -> Option<String> {
+    let name = property.param("CN").map(str::trim).unwrap_or_default();
+    if !name.is_empty() {
+        return Some(name.to_string());
+    }
+    let address = property.value.trim();
+    let address = match address.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &address[7..],
+        _ => address,
+    };
+    (!address.is_empty()).then(|| address.to_string())
+}
+
+// Joins folded lines, a line starting with a space or tab continues the one
+// before
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ if line.trim().is_empty() => {}
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+// Splits a content line at the first colon outside a quoted parameter value
+fn parse_line(line: &str) -> Option<Property> {
+    let mut quoted = false;
+    let mut head = Vec::new();
+    let mut start = 0;
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                head.push(&line[start..index]);
+                start = index + 1;
+            }
+            ':' if !quoted => {
+                head.push(&line[start..index]);
+                let mut head = head.into_iter();
+                let name = head.next()?.trim().to_ascii_uppercase();
+                let params = head
+                    .filter_map(|param| {
+                        let (key, value) = param.split_once('=')?;
+                        Some((
+                            key.trim().to_ascii_uppercase(),
+                            value.trim_matches('"').to_string(),
+                        ))
+                    })
+                    .collect();
+                return Some(Property {
+                    name,
+                    params,
+                    value: line[index + 1..].to_string(),
+                });
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// TEXT values escape newlines, commas, semicolons and backslashes
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(escaped) => text.push(escaped),
+            None => {}
+        }
+    }
+    text
+}
+
+// A comma separated TEXT list, unescaped
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut item = String::new();
+    let mut escaped = false;
+    for c in value.chars() {
+        if c == ',' && !escaped {
+            items.push(unescape(item.trim()));
+            item.clear();
+        } else {
+            item.push(c);
+        }
+        escaped = c == '\\' && !escaped;
+    }
+    items.push(unescape(item.trim()));
+    items
+}
@@ -0,0 +1,281 @@
+use crate::services::{
+    calendar::ics::{resolve, timestamp, zone},
+    database::models::{CalendarEvent, Meeting},
+};
+use chrono::{
+    DateTime, Datelike, Days, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use regex::Regex;
+use std::{fs, path::Path, sync::LazyLock};
+
+// How long before an event starts or after it ends a recording still belongs to it
+const TOLERANCE_MINUTES: i64 = 15;
+// Periods of a recurring event looked at before giving up on it
+const MAX_PERIODS: u32 = 20_000;
+
+// 2025-01-31 10.02.11, 20250131_100211, REC_20250131-1002, ...
+static TIME_IN_NAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?:^|\D)(\d{4})-?(\d{2})-?(\d{2})[ T_-]?(\d{2})[-:.h]?(\d{2})(?:[-:.m]?(\d{2}))?(?:\D|$)",
+    )
+    .unwrap()
+});
+
+#[derive(Clone, Copy)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+// The supported part of an RRULE
+struct Rule {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<DateTime<Utc>>,
+    weekdays: Vec<Weekday>,
+}
+
+// When a recording was made: a date and time in its file name, as most
+// recorders name their files, else when the file was last written
+pub fn recorded_at(path: &Path) -> Option<DateTime<Utc>> {
+    let named = path
+        .file_stem()
+        .and_then(|stem| time_in_name(&stem.to_string_lossy()));
+    if named.is_some() {
+        return named;
+    }
+    let modified = fs::metadata(path).ok()?.modified().ok()?;
+    Some(modified.into())
+}
+
+fn time_in_name(name: &str) -> Option<DateTime<Utc>> {
+    let captures = TIME_IN_NAME.captures(name)?;
+    let number = |index: usize| {
+        captures
+            .get(index)
+            .map_or(Some(0), |value| value.as_str().parse::<u32>().ok())
+    };
+    let date = NaiveDate::from_ymd_opt(number(1)? as i32, number(2)?, number(3)?)?;
+    let time = NaiveTime::from_hms_opt(number(4)?, number(5)?, number(6)?)?;
+    resolve(date.and_time(time), None)
+}
+
+// The occurrence of an event a recording made at `at` belongs to: the one it
+// falls in, else the closest one within the tolerance. All-day events do not
+// count, they are rarely the meeting itself.
+pub fn find_meeting(events: &[CalendarEvent], at: DateTime<Utc>) -> Option<Meeting> {
+    let tolerance = Duration::minutes(TOLERANCE_MINUTES);
+    let mut best: Option<((Duration, Duration), Meeting)> = None;
+    for event in events.iter().filter(|event| !event.all_day) {
+        let (Some(start), Some(end)) = (parse_utc(&event.starts_at), parse_utc(&event.ends_at))
+        else {
+            continue;
+        };
+        let length = end - start;
+        for starts in occurrences(event, start, at + tolerance) {
+            let ends = starts + length;
+            if at < starts - tolerance || at > ends + tolerance {
+                continue;
+            }
+            let outside = if at < starts {
+                starts - at
+            } else if at > ends {
+                at - ends
+            } else {
+                Duration::zero()
+            };
+            let score = (outside, (at - starts).abs());
+            if best.as_ref().is_none_or(|(best, _)| score < *best) {
+                best = Some((score, meeting(event, starts, ends)));
+            }
+        }
+    }
+    best.map(|(_, meeting)| meeting)
+}
+
+fn meeting(event: &CalendarEvent, starts: DateTime<Utc>, ends: DateTime<Utc>) -> Meeting {
+    Meeting {
+        event_id: event.id.clone(),
+        title: event.summary.clone(),
+        starts_at: timestamp(starts),
+        ends_at: timestamp(ends),
+        attendees: event.attendees.clone(),
+        agenda: event.agenda.clone(),
+        location: event.location.clone(),
+        categories: event.categories.clone(),
+    }
+}
+
+fn parse_utc(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|at| at.with_timezone(&Utc))
+}
+
+// Starts of the event's occurrences up to `limit`. Recurring events repeat in
+// their own zone, so a weekly 10:00 stays at 10:00 across DST changes.
+fn occurrences(
+    event: &CalendarEvent,
+    start: DateTime<Utc>,
+    limit: DateTime<Utc>,
+) -> Vec<DateTime<Utc>> {
+    let zone = event.timezone.as_deref().and_then(zone);
+    let Some(rule) = event
+        .rrule
+        .as_deref()
+        .and_then(|rrule| parse_rule(rrule, zone))
+    else {
+        return vec![start];
+    };
+    let first = match zone {
+        Some(zone) => start.with_timezone(&zone).naive_local(),
+        None => start.with_timezone(&Local).naive_local(),
+    };
+
+    let mut starts = Vec::new();
+    let mut generated = 0;
+    for period in 0..MAX_PERIODS {
+        let Some(candidates) = period_starts(&rule, first, period) else {
+            return starts;
+        };
+        for candidate in candidates {
+            if candidate < first {
+                continue;
+            }
+            let Some(at) = resolve(candidate, zone) else {
+                continue;
+            };
+            let past_count = rule.count.is_some_and(|count| generated >= count);
+            if past_count || rule.until.is_some_and(|until| at > until) || at > limit {
+                return starts;
+            }
+            generated += 1;
+            if !event.exdates.contains(&timestamp(at)) {
+                starts.push(at);
+            }
+        }
+    }
+    starts
+}
+
+// Local starts in the `period`th day, week, month or year of the rule, in
+// order. None once the period lies beyond the dates chrono can represent.
+fn period_starts(rule: &Rule, first: NaiveDateTime, period: u32) -> Option<Vec<NaiveDateTime>> {
+    let time = first.time();
+    let step = period.checked_mul(rule.interval)?;
+    let dates = match rule.frequency {
+        Frequency::Daily => vec![first.date().checked_add_days(Days::new(step.into()))?],
+        Frequency::Weekly => {
+            let monday = first
+                .date()
+                .checked_sub_days(Days::new(first.weekday().num_days_from_monday().into()))?
+                .checked_add_days(Days::new(u64::from(step) * 7))?;
+            let mut days: Vec<u32> = if rule.weekdays.is_empty() {
+                vec![first.weekday().num_days_from_monday()]
+            } else {
+                rule.weekdays
+                    .iter()
+                    .map(Weekday::num_days_from_monday)
+                    .collect()
+            };
+            days.sort_unstable();
+            days.dedup();
+            days.into_iter()
+                .map(|day| monday.checked_add_days(Days::new(day.into())))
+                .collect::<Option<Vec<_>>>()?
+        }
+        Frequency::Monthly => shift_months(first.date(), step)?.into_iter().collect(),
+        Frequency::Yearly => shift_months(first.date(), step.checked_mul(12)?)?
+            .into_iter()
+            .collect(),
+    };
+    Some(dates.into_iter().map(|date| date.and_time(time)).collect())
+}
+
+// The same day `months` later, Some(None) for months without the day, like
+// February 30th, which are skipped
+fn shift_months(date: NaiveDate, months: u32) -> Option<Option<NaiveDate>> {
+    let index = (date.year() * 12 + date.month0() as i32).checked_add(months.try_into().ok()?)?;
+    let (year, month) = (index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+    NaiveDate::from_ymd_opt(year, month, 1)?;
+    Some(NaiveDate::from_ymd_opt(year, month, date.day()))
+}
+
+// FREQ, INTERVAL, COUNT, UNTIL and the days of BYDAY, None for frequencies
+// below a day, which are then matched as a single event
+fn parse_rule(rrule: &str, zone: Option<Tz>) -> Option<Rule> {
+    let mut rule = Rule {
+        frequency: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        weekdays: Vec::new(),
+    };
+    let mut frequency = None;
+    for part in rrule.split(';') {
+        let Some((key, value)) = part.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim().to_ascii_uppercase().as_str() {
+            "FREQ" => {
+                frequency = match value.to_ascii_uppercase().as_str() {
+                    "DAILY" => Some(Frequency::Daily),
+                    "WEEKLY" => Some(Frequency::Weekly),
+                    "MONTHLY" => Some(Frequency::Monthly),
+                    "YEARLY" => Some(Frequency::Yearly),
+                    _ => None,
+                }
+            }
+            "INTERVAL" => rule.interval = value.parse().ok().filter(|&n| n > 0)?,
+            "COUNT" => rule.count = value.parse().ok(),
+            "UNTIL" => rule.until = parse_until(value, zone),
+            // only weekly rules use the days, a monthly 2TU repeats on the
+            // day of the month of the first occurrence instead
+            "BYDAY" => rule.weekdays = value.split(',').filter_map(weekday).collect(),
+            _ => {}
+        }
+    }
+    rule.frequency = frequency?;
+    Some(rule)
+}
+
+// MO, TU, ... with an optional number in front, e.g. 2TU
+fn weekday(day: &str) -> Option<Weekday> {
+    let day = day.trim();
+    match day
+        .get(day.len().checked_sub(2)?..)?
+        .to_ascii_uppercase()
+        .as_str()
+    {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+// UNTIL is inclusive, a date means until the end of that day
+fn parse_until(value: &str, zone: Option<Tz>) -> Option<DateTime<Utc>> {
+    if let Some(utc) = value.strip_suffix(['Z', 'z']) {
+        return NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|naive| naive.and_utc());
+    }
+    if value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return resolve(date.and_hms_opt(23, 59, 59)?, zone);
+    }
+    resolve(
+        NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?,
+        zone,
+    )
+}
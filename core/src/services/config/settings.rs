@@ -0,0 +1,130 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::database::models::MediaFormat;
+use crate::services::llm::llama_server::LlamaServerConfig;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// User settings stored in {data dir}/config.json, unset fields keep the built-in defaults
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub server_path: Option<PathBuf>,
+    pub model_path: Option<PathBuf>,
+    pub ctx_size: Option<u32>,
+    pub n_gpu_layers: Option<u32>,
+    pub parallel: Option<u32>,
+    // group new recordings go to when none is given
+    pub default_group: Option<String>,
+    // how imported recordings are kept in the media store
+    pub media_format: Option<MediaFormat>,
+    // recordings of older projects are deleted by `taunote media prune`, None keeps them
+    pub media_retention_days: Option<u32>,
+}
+
+impl Settings {
+    pub const KEYS: [&'static str; 8] = [
+        "server_path",
+        "model_path",
+        "ctx_size",
+        "n_gpu_layers",
+        "parallel",
+        "default_group",
+        "media_format",
+        "media_retention_days",
+    ];
+
+    pub fn path(base_dir: &Path) -> PathBuf {
+        base_dir.join("config.json")
+    }
+
+    // Missing file means nothing was configured yet
+    pub fn load(base_dir: &Path) -> Result<Self> {
+        let path = Self::path(base_dir);
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| {
+                TaunoteError::Config(format!("{} is not valid: {e}", path.display()))
+            }),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, base_dir: &Path) -> Result<()> {
+        fs::create_dir_all(base_dir)?;
+        fs::write(Self::path(base_dir), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        let value = match key {
+            "server_path" => self.server_path.as_ref().map(|p| p.display().to_string()),
+            "model_path" => self.model_path.as_ref().map(|p| p.display().to_string()),
+            "ctx_size" => self.ctx_size.map(|v| v.to_string()),
+            "n_gpu_layers" => self.n_gpu_layers.map(|v| v.to_string()),
+            "parallel" => self.parallel.map(|v| v.to_string()),
+            "default_group" => self.default_group.clone(),
+            "media_format" => self.media_format.map(|f| f.as_str().to_string()),
+            "media_retention_days" => self.media_retention_days.map(|v| v.to_string()),
+            _ => return Err(unknown_key(key)),
+        };
+        Ok(value)
+    }
+
+    // Sets a key from its text form, None clears it
+    pub fn set(&mut self, key: &str, value: Option<&str>) -> Result<()> {
+        match key {
+            "server_path" => self.server_path = value.map(PathBuf::from),
+            "model_path" => self.model_path = value.map(PathBuf::from),
+            "ctx_size" => self.ctx_size = value.map(|v| parse_number(key, v)).transpose()?,
+            "n_gpu_layers" => {
+                self.n_gpu_layers = value.map(|v| parse_number(key, v)).transpose()?
+            }
+            "parallel" => self.parallel = value.map(|v| parse_number(key, v)).transpose()?,
+            "default_group" => self.default_group = value.map(str::to_string),
+            "media_format" => {
+                self.media_format = value
+                    .map(|v| {
+                        MediaFormat::parse(v).ok_or_else(|| {
+                            TaunoteError::InvalidInput(format!(
+                                "{key} must be original, flac or opus, got {v}"
+                            ))
+                        })
+                    })
+                    .transpose()?
+            }
+            "media_retention_days" => {
+                self.media_retention_days = value.map(|v| parse_number(key, v)).transpose()?
+            }
+            _ => return Err(unknown_key(key)),
+        }
+        Ok(())
+    }
+
+    // Config with every set field replacing the one in `config`
+    pub fn apply_to(&self, config: LlamaServerConfig) -> LlamaServerConfig {
+        LlamaServerConfig {
+            server_path: self.server_path.clone().unwrap_or(config.server_path),
+            model_path: self.model_path.clone().unwrap_or(config.model_path),
+            ctx_size: self.ctx_size.unwrap_or(config.ctx_size),
+            n_gpu_layers: self.n_gpu_layers.unwrap_or(config.n_gpu_layers),
+            parallel: self.parallel.unwrap_or(config.parallel),
+            ..config
+        }
+    }
+}
+
+fn parse_number(key: &str, value: &str) -> Result<u32> {
+    value
+        .parse()
+        .map_err(|_| TaunoteError::InvalidInput(format!("{key} must be a number, got {value}")))
+}
+
+fn unknown_key(key: &str) -> TaunoteError {
+    TaunoteError::InvalidInput(format!(
+        "Unknown setting {key}, expected one of {}",
+        Settings::KEYS.join(", ")
+    ))
+}
@@ -0,0 +1,194 @@
+use crate::error::{Result, TaunoteError};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{rand_core::RngCore, Aead, AeadCore, KeyInit, OsRng},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use zeroize::Zeroize;
+
+// Written next to the database once the data directory is encrypted
+pub const KEY_FILE: &str = "encryption.json";
+
+// Prefix of every encrypted file, followed by the nonce and the ciphertext
+const MAGIC: &[u8; 8] = b"TNENC001";
+const NONCE_LEN: usize = 24;
+// Encrypted with the file key to tell a wrong passphrase from a corrupt file
+const CHECK_PLAINTEXT: &[u8] = b"taunote";
+
+// Argon2id cost, the defaults follow the OWASP recommendation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: 19 * 1024,
+            t_cost: 2,
+            p_cost: 1,
+        }
+    }
+}
+
+// Everything needed to derive the keys again, holds no secret
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyFile {
+    pub version: u32,
+    pub salt: String,
+    pub kdf: KdfParams,
+    pub check: String,
+}
+
+impl KeyFile {
+    pub fn path(base_dir: &Path) -> PathBuf {
+        base_dir.join(KEY_FILE)
+    }
+
+    pub fn load(base_dir: &Path) -> Result<Option<Self>> {
+        let path = Self::path(base_dir);
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map(Some)
+                .map_err(|e| TaunoteError::Config(format!("{} is not valid: {e}", path.display()))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, base_dir: &Path) -> Result<()> {
+        fs::write(Self::path(base_dir), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
+
+// The derived key: the first half keys SQLCipher, the second half the note files
+pub struct Keys {
+    master: [u8; 64],
+}
+
+impl Drop for Keys {
+    fn drop(&mut self) {
+        self.master.zeroize();
+    }
+}
+
+impl Keys {
+    // New random salt and keys for `passphrase`
+    pub fn create(passphrase: &str, kdf: KdfParams) -> Result<(KeyFile, Keys)> {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        let keys = derive(passphrase, &salt, kdf)?;
+        let key_file = KeyFile {
+            version: 1,
+            salt: hex::encode(salt),
+            kdf,
+            check: hex::encode(keys.encrypt(CHECK_PLAINTEXT)?),
+        };
+        Ok((key_file, keys))
+    }
+
+    pub fn unlock(key_file: &KeyFile, passphrase: &str) -> Result<Keys> {
+        let salt = hex::decode(&key_file.salt)
+            .map_err(|e| TaunoteError::Config(format!("Invalid salt in {KEY_FILE}: {e}")))?;
+        let keys = derive(passphrase, &salt, key_file.kdf)?;
+        keys.verify(key_file)?;
+        Ok(keys)
+    }
+
+    // Keys stored in the OS keyring are checked against the key file as well
+    pub fn from_hex(key_file: &KeyFile, value: &str) -> Result<Keys> {
+        let mut bytes = hex::decode(value.trim())
+            .map_err(|_| TaunoteError::Locked("the stored key is not valid".to_string()))?;
+        let mut master = [0u8; 64];
+        if bytes.len() != master.len() {
+            bytes.zeroize();
+            return Err(TaunoteError::Locked(
+                "the stored key is not valid".to_string(),
+            ));
+        }
+        master.copy_from_slice(&bytes);
+        bytes.zeroize();
+        let keys = Keys { master };
+        keys.verify(key_file)?;
+        Ok(keys)
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.master)
+    }
+
+    // SQL literal of the raw key for `PRAGMA key` and `ATTACH ... KEY`, a raw
+    // key skips SQLCipher's own passphrase derivation
+    pub fn sqlcipher_key(&self) -> String {
+        format!("'x''{}'''", hex::encode(&self.master[..32]))
+    }
+
+    fn cipher(&self) -> XChaCha20Poly1305 {
+        XChaCha20Poly1305::new(Key::from_slice(&self.master[32..]))
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher()
+            .encrypt(&nonce, plaintext)
+            .map_err(|_| TaunoteError::Internal("Encryption failed".to_string()))?;
+        let mut data = Vec::with_capacity(MAGIC.len() + NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        if !is_encrypted(data) || data.len() < MAGIC.len() + NONCE_LEN {
+            return Err(TaunoteError::InvalidInput(
+                "not an encrypted taunote file".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = data[MAGIC.len()..].split_at(NONCE_LEN);
+        self.cipher()
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                TaunoteError::InvalidInput(
+                    "the file was modified or encrypted with another key".to_string(),
+                )
+            })
+    }
+
+    fn verify(&self, key_file: &KeyFile) -> Result<()> {
+        let check = hex::decode(&key_file.check)
+            .map_err(|e| TaunoteError::Config(format!("Invalid check in {KEY_FILE}: {e}")))?;
+        match self.decrypt(&check) {
+            Ok(plaintext) if plaintext == CHECK_PLAINTEXT => Ok(()),
+            _ => Err(TaunoteError::Locked("wrong passphrase".to_string())),
+        }
+    }
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+fn derive(passphrase: &str, salt: &[u8], kdf: KdfParams) -> Result<Keys> {
+    if passphrase.is_empty() {
+        return Err(TaunoteError::InvalidInput(
+            "the passphrase must not be empty".to_string(),
+        ));
+    }
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(64))
+        .map_err(|e| TaunoteError::Config(format!("Invalid key derivation parameters: {e}")))?;
+    let mut master = [0u8; 64];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut master)
+        .map_err(|e| TaunoteError::Internal(format!("Key derivation failed: {e}")))?;
+    Ok(Keys { master })
+}
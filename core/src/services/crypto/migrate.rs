@@ -0,0 +1,100 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::crypto::{keys::KdfParams, vault};
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// What `taunote encrypt` converted
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptReport {
+    pub database: Option<PathBuf>,
+    pub note_files: usize,
+}
+
+// Encrypts a plaintext data directory in place: the database with SQLCipher and
+// the markdown notes under groups/. Nothing else may have the database open.
+// Running it again on an encrypted directory finishes an interrupted run.
+pub fn encrypt_data_dir(
+    base_dir: &Path,
+    passphrase: &str,
+    kdf: KdfParams,
+) -> Result<EncryptReport> {
+    let database = if vault::is_encrypted_dir(base_dir) {
+        vault::unlock(base_dir, passphrase)?;
+        None
+    } else {
+        Some(encrypt_database(base_dir, passphrase, kdf)?)
+    };
+    Ok(EncryptReport {
+        database,
+        note_files: encrypt_note_files(&base_dir.join("groups"), base_dir)?,
+    })
+}
+
+#[cfg(not(feature = "encryption"))]
+fn encrypt_database(_base_dir: &Path, _passphrase: &str, _kdf: KdfParams) -> Result<PathBuf> {
+    Err(TaunoteError::Config(vault::NO_ENCRYPTION.to_string()))
+}
+
+#[cfg(feature = "encryption")]
+fn encrypt_database(base_dir: &Path, passphrase: &str, kdf: KdfParams) -> Result<PathBuf> {
+    use crate::services::{crypto::keys::Keys, database::schema::db_path};
+    use rusqlite::{params, Connection};
+
+    let database = db_path(base_dir);
+    if !database.is_file() {
+        return Err(TaunoteError::NotFound(format!(
+            "Database {}",
+            database.display()
+        )));
+    }
+    let (key_file, keys) = Keys::create(passphrase, kdf)?;
+    let encrypted = database.with_extension("db.encrypting");
+    if encrypted.exists() {
+        fs::remove_file(&encrypted)?;
+    }
+
+    let conn = Connection::open(&database)?;
+    let user_version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    conn.execute(
+        &format!(
+            "ATTACH DATABASE ?1 AS encrypted KEY {}",
+            keys.sqlcipher_key()
+        ),
+        params![encrypted.to_string_lossy()],
+    )?;
+    conn.query_row("SELECT sqlcipher_export('encrypted')", [], |_| Ok(()))?;
+    conn.execute_batch(&format!(
+        "PRAGMA encrypted.user_version = {user_version}; DETACH DATABASE encrypted;"
+    ))?;
+    drop(conn);
+
+    // the key file goes first: should we stop before the swap, deleting it
+    // leaves the plaintext data directory as it was
+    key_file.save(base_dir)?;
+    fs::rename(&encrypted, &database)?;
+    vault::insert(base_dir, keys);
+    Ok(database)
+}
+
+// Replaces every {kind}.md below `dir` with {kind}.md.enc
+fn encrypt_note_files(dir: &Path, base_dir: &Path) -> Result<usize> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Ok(0);
+    };
+    let mut converted = 0;
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_dir() {
+            converted += encrypt_note_files(&path, base_dir)?;
+        } else if path.extension().is_some_and(|ext| ext == "md") {
+            let content = fs::read_to_string(&path)?;
+            vault::write_note(base_dir, &path.with_extension("md.enc"), &content)?;
+            fs::remove_file(&path)?;
+            converted += 1;
+        }
+    }
+    Ok(converted)
+}
@@ -0,0 +1,3 @@
+pub mod keys;
+pub mod migrate;
+pub mod vault;
@@ -0,0 +1,176 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::crypto::keys::{is_encrypted, KeyFile, Keys};
+use rusqlite::Connection;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, RwLock},
+};
+
+#[cfg(not(feature = "encryption"))]
+pub(crate) const NO_ENCRYPTION: &str =
+    "this build of taunote has no encryption support, rebuild it with --features encryption";
+
+// Keys of the unlocked data directories. Note files are read and written by
+// free functions that only get the data directory, so the keys live here
+// rather than being passed down every call.
+static UNLOCKED: LazyLock<RwLock<HashMap<PathBuf, Arc<Keys>>>> = LazyLock::new(Default::default);
+
+pub fn is_encrypted_dir(base_dir: &Path) -> bool {
+    KeyFile::path(base_dir).is_file()
+}
+
+pub fn is_unlocked(base_dir: &Path) -> bool {
+    UNLOCKED
+        .read()
+        .is_ok_and(|unlocked| unlocked.contains_key(base_dir))
+}
+
+// Keys of an encrypted data directory, None when it is not encrypted
+pub fn keys(base_dir: &Path) -> Result<Option<Arc<Keys>>> {
+    if !is_encrypted_dir(base_dir) {
+        return Ok(None);
+    }
+    UNLOCKED
+        .read()
+        .ok()
+        .and_then(|unlocked| unlocked.get(base_dir).cloned())
+        .map(Some)
+        .ok_or_else(|| TaunoteError::Locked(format!("{} is locked", base_dir.display())))
+}
+
+pub fn insert(base_dir: &Path, keys: Keys) {
+    if let Ok(mut unlocked) = UNLOCKED.write() {
+        unlocked.insert(base_dir.to_path_buf(), Arc::new(keys));
+    }
+}
+
+pub fn remove(base_dir: &Path) {
+    if let Ok(mut unlocked) = UNLOCKED.write() {
+        unlocked.remove(base_dir);
+    }
+}
+
+// Checks the passphrase against the key file and keeps the keys for this process
+pub fn unlock(base_dir: &Path, passphrase: &str) -> Result<()> {
+    let key_file = KeyFile::load(base_dir)?.ok_or_else(|| {
+        TaunoteError::InvalidInput(format!("{} is not encrypted", base_dir.display()))
+    })?;
+    insert(base_dir, Keys::unlock(&key_file, passphrase)?);
+    Ok(())
+}
+
+// Extension of note files, encrypted ones cannot be edited outside of taunote
+pub fn note_extension(base_dir: &Path) -> &'static str {
+    if is_encrypted_dir(base_dir) {
+        "md.enc"
+    } else {
+        "md"
+    }
+}
+
+// Reads a note file, decrypting it when it is encrypted
+pub fn read_note(base_dir: &Path, path: &Path) -> Result<String> {
+    let data = fs::read(path)?;
+    let data = if is_encrypted(&data) {
+        let keys = keys(base_dir)?.ok_or_else(|| {
+            TaunoteError::Config(format!(
+                "{} is encrypted but {} has no key file",
+                path.display(),
+                base_dir.display()
+            ))
+        })?;
+        keys.decrypt(&data)
+            .map_err(|e| TaunoteError::InvalidInput(format!("{}: {e}", path.display())))?
+    } else {
+        data
+    };
+    String::from_utf8(data)
+        .map_err(|_| TaunoteError::InvalidInput(format!("{} is not UTF-8 text", path.display())))
+}
+
+// Writes a note file, encrypted when the data directory is
+pub fn write_note(base_dir: &Path, path: &Path, content: &str) -> Result<()> {
+    match keys(base_dir)? {
+        Some(keys) => fs::write(path, keys.encrypt(content.as_bytes())?)?,
+        None => fs::write(path, content)?,
+    }
+    Ok(())
+}
+
+// Must run before anything else on a connection to an encrypted database
+#[cfg(feature = "encryption")]
+pub fn apply_db_key(conn: &Connection, keys: &Keys) -> Result<()> {
+    conn.execute_batch(&format!("PRAGMA key = {};", keys.sqlcipher_key()))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn apply_db_key(_conn: &Connection, _keys: &Keys) -> Result<()> {
+    Err(TaunoteError::Config(NO_ENCRYPTION.to_string()))
+}
+
+// One keyring entry per data directory
+#[cfg(feature = "encryption")]
+fn keyring_entry(base_dir: &Path) -> Result<keyring::Entry> {
+    keyring::Entry::new("taunote", &base_dir.display().to_string())
+        .map_err(|e| TaunoteError::Config(format!("OS keyring unavailable: {e}")))
+}
+
+// Stores the unlocked keys in the OS keyring so later runs unlock without a passphrase
+#[cfg(feature = "encryption")]
+pub fn remember(base_dir: &Path) -> Result<()> {
+    let keys = keys(base_dir)?.ok_or_else(|| {
+        TaunoteError::InvalidInput(format!("{} is not encrypted", base_dir.display()))
+    })?;
+    keyring_entry(base_dir)?
+        .set_password(&keys.to_hex())
+        .map_err(|e| {
+            TaunoteError::Config(format!("Could not store the key in the OS keyring: {e}"))
+        })
+}
+
+#[cfg(feature = "encryption")]
+pub fn forget(base_dir: &Path) -> Result<()> {
+    match keyring_entry(base_dir)?.delete_credential() {
+        Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+        Err(e) => Err(TaunoteError::Config(format!(
+            "Could not remove the key from the OS keyring: {e}"
+        ))),
+    }
+}
+
+// Returns false when the keyring has no key for this data directory
+#[cfg(feature = "encryption")]
+pub fn unlock_from_keyring(base_dir: &Path) -> Result<bool> {
+    let Some(key_file) = KeyFile::load(base_dir)? else {
+        return Ok(false);
+    };
+    let stored = match keyring_entry(base_dir)?.get_password() {
+        Ok(stored) => stored,
+        Err(keyring::Error::NoEntry) => return Ok(false),
+        Err(e) => {
+            return Err(TaunoteError::Config(format!(
+                "Could not read the OS keyring: {e}"
+            )))
+        }
+    };
+    insert(base_dir, Keys::from_hex(&key_file, &stored)?);
+    Ok(true)
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn remember(_base_dir: &Path) -> Result<()> {
+    Err(TaunoteError::Config(NO_ENCRYPTION.to_string()))
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn forget(_base_dir: &Path) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(not(feature = "encryption"))]
+pub fn unlock_from_keyring(_base_dir: &Path) -> Result<bool> {
+    Ok(false)
+}
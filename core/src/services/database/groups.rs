@@ -0,0 +1,246 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::database::models::ProjectGroup;
+use crate::services::paths::slug::{slug, unique_slug};
+use rusqlite::{params, Connection, OptionalExtension};
+
+// Groups form a tree through parent_id. Folders only use ids: a project lives
+// in groups/{group_id}/{project_id} wherever its group is in the tree, so
+// renaming or moving a group never touches files. New groups get the slug of
+// their name as id, which keeps the folders readable and safe on any system.
+
+// Separates the group names of a path like Clients/Acme
+pub const PATH_SEPARATOR: char = '/';
+
+pub fn project_relative_path(group_id: &str, project_id: &str) -> String {
+    format!("groups/{group_id}/{project_id}")
+}
+
+pub fn get_project_group(conn: &Connection, id: &str) -> rusqlite::Result<Option<ProjectGroup>> {
+    conn.query_row(
+        "SELECT id, name, parent_id FROM project_groups WHERE id = ?1",
+        params![id],
+        |r| {
+            Ok(ProjectGroup {
+                id: r.get(0)?,
+                name: r.get(1)?,
+                parent_id: r.get(2)?,
+            })
+        },
+    )
+    .optional()
+}
+
+pub fn insert_project_group(conn: &Connection, group: &ProjectGroup) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO project_groups (id, name, parent_id) VALUES (?1, ?2, ?3)",
+        params![group.id, group.name, group.parent_id],
+    )?;
+    Ok(())
+}
+
+// Oldest group called `name` under `parent`, None for the top level
+fn child_group(
+    conn: &Connection,
+    parent: Option<&str>,
+    name: &str,
+) -> rusqlite::Result<Option<ProjectGroup>> {
+    conn.query_row(
+        "SELECT id, name, parent_id FROM project_groups
+         WHERE parent_id IS ?1 AND name = ?2 ORDER BY rowid LIMIT 1",
+        params![parent, name],
+        |r| {
+            Ok(ProjectGroup {
+                id: r.get(0)?,
+                name: r.get(1)?,
+                parent_id: r.get(2)?,
+            })
+        },
+    )
+    .optional()
+}
+
+// The names of a path, trimmed, e.g. "Clients / Acme" is ["Clients", "Acme"]
+fn path_names(path: &str) -> Result<Vec<&str>> {
+    let names: Vec<&str> = path.split(PATH_SEPARATOR).map(str::trim).collect();
+    for name in &names {
+        check_name(name)?;
+    }
+    Ok(names)
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(TaunoteError::InvalidInput(format!(
+            "\"{name}\" cannot be used as a group name"
+        )));
+    }
+    Ok(())
+}
+
+// The group `reference` stands for: a group id, else a path of names from the
+// top like Clients/Acme
+pub fn find_group(conn: &Connection, reference: &str) -> Result<Option<ProjectGroup>> {
+    if let Some(group) = get_project_group(conn, reference)? {
+        return Ok(Some(group));
+    }
+    let Ok(names) = path_names(reference) else {
+        return Ok(None);
+    };
+    let mut found: Option<ProjectGroup> = None;
+    for name in names {
+        match child_group(conn, found.as_ref().map(|group| group.id.as_str()), name)? {
+            Some(group) => found = Some(group),
+            None => return Ok(None),
+        }
+    }
+    Ok(found)
+}
+
+// Like find_group, creating the groups of the path that do not exist yet
+pub fn resolve_group(conn: &Connection, reference: &str) -> Result<ProjectGroup> {
+    if let Some(group) = get_project_group(conn, reference)? {
+        return Ok(group);
+    }
+    let mut parent: Option<ProjectGroup> = None;
+    for name in path_names(reference)? {
+        let parent_id = parent.as_ref().map(|group| group.id.clone());
+        let group = match child_group(conn, parent_id.as_deref(), name)? {
+            Some(group) => group,
+            None => {
+                let group = ProjectGroup {
+                    id: new_group_id(conn, name)?,
+                    name: name.to_string(),
+                    parent_id,
+                };
+                insert_project_group(conn, &group)?;
+                group
+            }
+        };
+        parent = Some(group);
+    }
+    Ok(parent.expect("a path has at least one name"))
+}
+
+// The slug of the name, numbered when another group has it already. Ids are
+// compared ignoring case as they name folders.
+pub fn new_group_id(conn: &Connection, name: &str) -> rusqlite::Result<String> {
+    let mut taken =
+        conn.prepare("SELECT EXISTS (SELECT 1 FROM project_groups WHERE id = ?1 COLLATE NOCASE)")?;
+    let mut failed = None;
+    let id = unique_slug(&slug(name, "group"), |candidate| {
+        taken
+            .query_row(params![candidate], |r| r.get(0))
+            .unwrap_or_else(|err| {
+                failed = Some(err);
+                false
+            })
+    });
+    match failed {
+        Some(err) => Err(err),
+        None => Ok(id),
+    }
+}
+
+// The group and its ancestors, the top level one first
+pub fn ancestors(conn: &Connection, id: &str) -> Result<Vec<ProjectGroup>> {
+    let mut chain = Vec::new();
+    let mut next = Some(id.to_string());
+    while let Some(id) = next {
+        let group = get_project_group(conn, &id)?
+            .ok_or_else(|| TaunoteError::NotFound(format!("Group {id}")))?;
+        if chain
+            .iter()
+            .any(|known: &ProjectGroup| known.id == group.id)
+        {
+            return Err(TaunoteError::Internal(format!(
+                "Group {} is its own ancestor",
+                group.id
+            )));
+        }
+        next = group.parent_id.clone();
+        chain.push(group);
+    }
+    chain.reverse();
+    Ok(chain)
+}
+
+// Path of names of the group with `id` among `groups`, e.g. Clients/Acme
+pub fn group_path(groups: &[ProjectGroup], id: &str) -> String {
+    let mut names = Vec::new();
+    let mut next = Some(id);
+    while let Some(id) = next {
+        let Some(group) = groups.iter().find(|group| group.id == id) else {
+            names.push(id);
+            break;
+        };
+        // stop on a loop in a database edited by hand
+        if names.len() > groups.len() {
+            break;
+        }
+        names.push(group.name.as_str());
+        next = group.parent_id.as_deref();
+    }
+    names.reverse();
+    names.join(&PATH_SEPARATOR.to_string())
+}
+
+fn check_free_name(conn: &Connection, parent: Option<&str>, name: &str, id: &str) -> Result<()> {
+    match child_group(conn, parent, name)? {
+        Some(sibling) if sibling.id != id => Err(TaunoteError::InvalidInput(format!(
+            "A group named \"{name}\" already exists there"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+pub fn rename_group(conn: &Connection, id: &str, name: &str) -> Result<()> {
+    let name = name.trim();
+    check_name(name)?;
+    let group = get_project_group(conn, id)?
+        .ok_or_else(|| TaunoteError::NotFound(format!("Group {id}")))?;
+    check_free_name(conn, group.parent_id.as_deref(), name, id)?;
+    conn.execute(
+        "UPDATE project_groups SET name = ?2 WHERE id = ?1",
+        params![id, name],
+    )?;
+    Ok(())
+}
+
+// Puts the group under `parent`, or at the top level for None
+pub fn move_group(conn: &Connection, id: &str, parent: Option<&str>) -> Result<()> {
+    let group = get_project_group(conn, id)?
+        .ok_or_else(|| TaunoteError::NotFound(format!("Group {id}")))?;
+    if let Some(parent) = parent {
+        if ancestors(conn, parent)?
+            .iter()
+            .any(|ancestor| ancestor.id == id)
+        {
+            return Err(TaunoteError::InvalidInput(format!(
+                "Group {id} cannot be moved into itself or one of its subgroups"
+            )));
+        }
+    }
+    check_free_name(conn, parent, &group.name, id)?;
+    conn.execute(
+        "UPDATE project_groups SET parent_id = ?2 WHERE id = ?1",
+        params![id, parent],
+    )?;
+    Ok(())
+}
+
+// Only empty groups can go, their projects and subgroups have to be moved first
+pub fn delete_group(conn: &Connection, id: &str) -> Result<()> {
+    let used: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM audio_projects WHERE group_id = ?1)
+             OR EXISTS (SELECT 1 FROM project_groups WHERE parent_id = ?1)",
+        params![id],
+        |r| r.get(0),
+    )?;
+    if used {
+        return Err(TaunoteError::InvalidInput(format!(
+            "Group {id} still has projects or subgroups"
+        )));
+    }
+    conn.execute("DELETE FROM project_groups WHERE id = ?1", params![id])?;
+    Ok(())
+}
@@ -1,3 +1,5 @@
+pub mod groups;
 pub mod models;
 pub mod queries;
 pub mod schema;
+pub mod versions;
@@ -1,4 +1,15 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectGroup {
+    pub id: String,
+    pub name: String,
+    // None for top level groups
+    #[serde(default)]
+    pub parent_id: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AudioProject {
     pub id: String,
@@ -6,6 +17,311 @@ pub struct AudioProject {
     pub name: String,
     pub relative_path: String,
     pub date: String,
-    pub project_type: String,
+    pub project_type: ProjectType,
     pub language: String,
+    // recording in the media store, None once retention removed it
+    #[serde(default)]
+    pub media_hash: Option<String>,
+}
+
+// What was recorded, decides which notes are generated by default
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectType {
+    #[default]
+    Meeting,
+    Lecture,
+    Interview,
+    VoiceMemo,
+    // also what types this version does not know are read as
+    #[serde(other)]
+    Other,
+}
+
+impl ProjectType {
+    pub const ALL: [ProjectType; 5] = [
+        ProjectType::Meeting,
+        ProjectType::Lecture,
+        ProjectType::Interview,
+        ProjectType::VoiceMemo,
+        ProjectType::Other,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectType::Meeting => "meeting",
+            ProjectType::Lecture => "lecture",
+            ProjectType::Interview => "interview",
+            ProjectType::VoiceMemo => "voice_memo",
+            ProjectType::Other => "other",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+}
+
+// Everything known about a project besides the columns of AudioProject
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectMetadata {
+    pub tags: Vec<String>,
+    pub description: Option<String>,
+    // people in the recording, in the order they were added
+    pub participants: Vec<String>,
+    pub duration_seconds: Option<f64>,
+    // microphone or app the recording came from
+    pub source_device: Option<String>,
+    // fields the user made up, e.g. "client" or "course"
+    pub fields: BTreeMap<String, String>,
+}
+
+// A tag and how many projects have it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagCount {
+    pub tag: String,
+    pub projects: u32,
+}
+
+// How a recording is kept in the media store
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MediaFormat {
+    // the imported file as is
+    #[default]
+    Original,
+    Flac,
+    Opus,
+}
+
+impl MediaFormat {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaFormat::Original => "original",
+            MediaFormat::Flac => "flac",
+            MediaFormat::Opus => "opus",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "original" => Some(MediaFormat::Original),
+            "flac" => Some(MediaFormat::Flac),
+            "opus" => Some(MediaFormat::Opus),
+            _ => None,
+        }
+    }
+}
+
+// A stored recording, `relative_path` is relative to the data directory
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Media {
+    pub hash: String,
+    pub relative_path: String,
+    pub format: MediaFormat,
+    pub original_name: String,
+    pub size_bytes: u64,
+    pub created_at: String,
+}
+
+// A stored recording and how many projects use it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaUsage {
+    #[serde(flatten)]
+    pub media: Media,
+    pub projects: u32,
+}
+
+// What a redaction placeholder stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityKind {
+    Email,
+    Phone,
+    Iban,
+    CreditCard,
+    // from a custom word list
+    Term,
+    Name,
+}
+
+impl EntityKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntityKind::Email => "email",
+            EntityKind::Phone => "phone",
+            EntityKind::Iban => "iban",
+            EntityKind::CreditCard => "credit_card",
+            EntityKind::Term => "term",
+            EntityKind::Name => "name",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "email" => Some(EntityKind::Email),
+            "phone" => Some(EntityKind::Phone),
+            "iban" => Some(EntityKind::Iban),
+            "credit_card" => Some(EntityKind::CreditCard),
+            "term" => Some(EntityKind::Term),
+            "name" => Some(EntityKind::Name),
+            _ => None,
+        }
+    }
+
+    // Placeholders read [EMAIL_1], [NAME_2], ...
+    pub fn label(&self) -> &'static str {
+        match self {
+            EntityKind::Email => "EMAIL",
+            EntityKind::Phone => "PHONE",
+            EntityKind::Iban => "IBAN",
+            EntityKind::CreditCard => "CARD",
+            EntityKind::Term => "TERM",
+            EntityKind::Name => "NAME",
+        }
+    }
+}
+
+// A placeholder in a redacted note and the value it replaced
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Redaction {
+    pub placeholder: String,
+    pub entity: EntityKind,
+    pub original: String,
+}
+
+// Current text of a project's notes as indexed for search, empty for kinds the
+// project does not have
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectNotes {
+    pub transcript: String,
+    pub summary: String,
+    pub email: String,
+    pub lecture_notes: String,
+}
+
+// An event from an imported .ics file, times are RFC 3339 in UTC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalendarEvent {
+    // the UID, plus the original start for a moved occurrence of a recurring event
+    pub id: String,
+    pub summary: String,
+    pub starts_at: String,
+    pub ends_at: String,
+    pub all_day: bool,
+    // IANA zone the event repeats in, None for floating times
+    pub timezone: Option<String>,
+    // RRULE as written in the file and the occurrences it skips
+    pub rrule: Option<String>,
+    pub exdates: Vec<String>,
+    pub attendees: Vec<String>,
+    pub agenda: Option<String>,
+    pub location: Option<String>,
+    pub categories: Vec<String>,
+}
+
+// The calendar event a project was recorded in, with the times of that
+// occurrence
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Meeting {
+    pub event_id: String,
+    pub title: String,
+    pub starts_at: String,
+    pub ends_at: String,
+    // people invited, candidates for the speakers in the transcript
+    pub attendees: Vec<String>,
+    pub agenda: Option<String>,
+    pub location: Option<String>,
+    pub categories: Vec<String>,
+}
+
+impl Meeting {
+    // The first category usable as a group name
+    pub fn group(&self) -> Option<&str> {
+        self.categories.iter().map(|c| c.trim()).find(|category| {
+            !category.is_empty()
+                && !category.contains(['/', '\\'])
+                && !category.starts_with('.')
+        })
+    }
+}
+
+// Narrows a search to projects matching all of the set fields
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchFilter {
+    pub group_id: Option<String>,
+    pub project_type: Option<ProjectType>,
+    // projects with every one of these tags
+    pub tags: Vec<String>,
+    // part of a participant's name, ignoring case
+    pub participant: Option<String>,
+    // dates like 2025-01 or 2025-01-31, both inclusive
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+// A project whose notes match a search, the snippet marks matches with [ ]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub project_id: String,
+    pub project_name: String,
+    pub snippet: String,
+}
+
+// Tokens spent on one generated note
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    // transcript tokens that made it into the prompt, out of the total
+    pub transcript_tokens: u32,
+    pub transcript_tokens_total: u32,
+    pub truncated: bool,
+}
+
+// Where a note version came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionSource {
+    Generated,
+    UserEdit,
+    Restored,
+}
+
+impl VersionSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VersionSource::Generated => "generated",
+            VersionSource::UserEdit => "user_edit",
+            VersionSource::Restored => "restored",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "generated" => Some(VersionSource::Generated),
+            "user_edit" => Some(VersionSource::UserEdit),
+            "restored" => Some(VersionSource::Restored),
+            _ => None,
+        }
+    }
+}
+
+// One stored revision of a project note (summary, email, ...)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NoteVersion {
+    pub id: String,
+    pub project_id: String,
+    pub note_kind: String,
+    pub content: String,
+    pub source: VersionSource,
+    // prompt template, model file and GenerationParams (JSON) for generated versions
+    pub template: Option<String>,
+    pub model: Option<String>,
+    pub params: Option<String>,
+    pub parent_id: Option<String>,
+    pub is_current: bool,
+    pub created_at: String,
 }
@@ -1,32 +1,344 @@
-use crate::services::database::models::AudioProject;
-use rusqlite::{params, Connection, Result};
+use crate::services::database::models::{
+    AudioProject, CalendarEvent, EntityKind, Media, MediaFormat, MediaUsage, Meeting, ProjectGroup,
+    ProjectMetadata, ProjectNotes, ProjectType, Redaction, SearchFilter, SearchHit, TagCount,
+    TokenUsage,
+};
+use rusqlite::{
+    params, params_from_iter, types::Value, Connection, OptionalExtension, Result, Row,
+};
+
+const PROJECT_COLUMNS: &str = "id, group_id, name, relative_path, date, type, language, media_hash";
+const MEDIA_COLUMNS: &str = "hash, relative_path, format, original_name, size_bytes, created_at";
+const EVENT_COLUMNS: &str = "id, summary, starts_at, ends_at, all_day, timezone, rrule, exdates, \
+    attendees, agenda, location, categories";
+const MEETING_COLUMNS: &str =
+    "event_id, title, starts_at, ends_at, attendees, agenda, location, categories";
+
+fn project_from_row(r: &Row) -> Result<AudioProject> {
+    let project_type: String = r.get(5)?;
+    Ok(AudioProject {
+        id: r.get(0)?,
+        group_id: r.get(1)?,
+        name: r.get(2)?,
+        relative_path: r.get(3)?,
+        date: r.get(4)?,
+        // the desktop app used to store any string here
+        project_type: ProjectType::parse(&project_type).unwrap_or(ProjectType::Other),
+        language: r.get(6)?,
+        media_hash: r.get(7)?,
+    })
+}
+
+fn media_from_row(r: &Row) -> Result<Media> {
+    let format: String = r.get(2)?;
+    Ok(Media {
+        hash: r.get(0)?,
+        relative_path: r.get(1)?,
+        format: MediaFormat::parse(&format).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                2,
+                rusqlite::types::Type::Text,
+                format!("unknown media format {format}").into(),
+            )
+        })?,
+        original_name: r.get(3)?,
+        size_bytes: r.get(4)?,
+        created_at: r.get(5)?,
+    })
+}
+
+// Groups referenced by a project must exist, the CLI uses the group name as its id.
+// See groups::resolve_group for groups given by path.
+pub fn ensure_project_group(conn: &Connection, id: &str, name: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO project_groups (id, name) VALUES (?1, ?2)",
+        params![id, name],
+    )?;
+    Ok(())
+}
+
+pub fn list_project_groups(conn: &Connection) -> Result<Vec<ProjectGroup>> {
+    let mut stmt = conn.prepare("SELECT id, name, parent_id FROM project_groups ORDER BY name")?;
+    let groups = stmt
+        .query_map([], |r| {
+            Ok(ProjectGroup {
+                id: r.get(0)?,
+                name: r.get(1)?,
+                parent_id: r.get(2)?,
+            })
+        })?
+        .collect();
+    groups
+}
 
 pub fn insert_audio_project(conn: &Connection, project: &AudioProject) -> Result<()> {
     conn.execute(
         "INSERT INTO audio_projects (
-            id, group_id, name, relative_path, date, type, language
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            id, group_id, name, relative_path, date, type, language, media_hash
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
         params![
             project.id,
             project.group_id,
             project.name,
             project.relative_path,
             project.date,
-            project.project_type,
-            project.language
+            project.project_type.as_str(),
+            project.language,
+            project.media_hash
         ],
     )?;
     Ok(())
 }
 
+pub fn get_audio_project(conn: &Connection, id: &str) -> Result<Option<AudioProject>> {
+    conn.query_row(
+        &format!("SELECT {PROJECT_COLUMNS} FROM audio_projects WHERE id = ?1"),
+        params![id],
+        project_from_row,
+    )
+    .optional()
+}
+
+pub fn list_audio_projects(conn: &Connection) -> Result<Vec<AudioProject>> {
+    let mut stmt = conn.prepare(&format!("SELECT {PROJECT_COLUMNS} FROM audio_projects"))?;
+    let projects = stmt.query_map([], project_from_row)?.collect();
+    projects
+}
+
+pub fn list_group_projects(conn: &Connection, group_id: &str) -> Result<Vec<AudioProject>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {PROJECT_COLUMNS} FROM audio_projects WHERE group_id = ?1 ORDER BY date"
+    ))?;
+    let projects = stmt.query_map(params![group_id], project_from_row)?.collect();
+    projects
+}
+
+pub fn rename_audio_project(conn: &Connection, id: &str, name: &str) -> Result<()> {
+    conn.execute(
+        "UPDATE audio_projects SET name = ?2 WHERE id = ?1",
+        params![id, name],
+    )?;
+    Ok(())
+}
+
+pub fn set_project_type(conn: &Connection, id: &str, project_type: ProjectType) -> Result<()> {
+    conn.execute(
+        "UPDATE audio_projects SET type = ?2 WHERE id = ?1",
+        params![id, project_type.as_str()],
+    )?;
+    Ok(())
+}
+
+pub fn update_project_location(
+    conn: &Connection,
+    id: &str,
+    group_id: &str,
+    relative_path: &str,
+) -> Result<()> {
+    conn.execute(
+        "UPDATE audio_projects SET group_id = ?2, relative_path = ?3 WHERE id = ?1",
+        params![id, group_id, relative_path],
+    )?;
+    Ok(())
+}
+
+// Removes the project and everything stored for it, callers delete its folder
+pub fn delete_audio_project(conn: &Connection, id: &str) -> Result<()> {
+    for table in [
+        "note_token_usage",
+        "note_files",
+        "note_versions",
+        "project_notes",
+        "redactions",
+        "project_meetings",
+        "project_metadata",
+        "project_tags",
+    ] {
+        conn.execute(
+            &format!("DELETE FROM {table} WHERE project_id = ?1"),
+            params![id],
+        )?;
+    }
+    conn.execute("DELETE FROM audio_projects WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+// Replaces the row of a recording whose file had gone missing
+pub fn upsert_media(conn: &Connection, media: &Media) -> Result<()> {
+    conn.execute(
+        &format!("INSERT OR REPLACE INTO media ({MEDIA_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6)"),
+        params![
+            media.hash,
+            media.relative_path,
+            media.format.as_str(),
+            media.original_name,
+            media.size_bytes,
+            media.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get_media(conn: &Connection, hash: &str) -> Result<Option<Media>> {
+    conn.query_row(
+        &format!("SELECT {MEDIA_COLUMNS} FROM media WHERE hash = ?1"),
+        params![hash],
+        media_from_row,
+    )
+    .optional()
+}
+
+pub fn list_media(conn: &Connection) -> Result<Vec<MediaUsage>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {MEDIA_COLUMNS}, (SELECT COUNT(*) FROM audio_projects p WHERE p.media_hash = m.hash)
+         FROM media m ORDER BY created_at"
+    ))?;
+    let media = stmt
+        .query_map([], |r| {
+            Ok(MediaUsage {
+                media: media_from_row(r)?,
+                projects: r.get(6)?,
+            })
+        })?
+        .collect();
+    media
+}
+
+// Recordings no project links to anymore
+pub fn unreferenced_media(conn: &Connection) -> Result<Vec<Media>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {MEDIA_COLUMNS} FROM media m
+         WHERE NOT EXISTS (SELECT 1 FROM audio_projects p WHERE p.media_hash = m.hash)"
+    ))?;
+    let media = stmt.query_map([], media_from_row)?.collect();
+    media
+}
+
+pub fn delete_media(conn: &Connection, hash: &str) -> Result<()> {
+    conn.execute("DELETE FROM media WHERE hash = ?1", params![hash])?;
+    Ok(())
+}
+
+pub fn set_project_media(conn: &Connection, id: &str, media_hash: Option<&str>) -> Result<()> {
+    conn.execute(
+        "UPDATE audio_projects SET media_hash = ?2 WHERE id = ?1",
+        params![id, media_hash],
+    )?;
+    Ok(())
+}
+
+// Full text search over the indexed notes, best matches first
+// Projects whose notes match `query` (FTS5 syntax) and the filter, best first.
+// Without a query every project matching the filter is listed, newest first.
+pub fn search_notes(
+    conn: &Connection,
+    query: &str,
+    filter: &SearchFilter,
+    limit: usize,
+) -> Result<Vec<SearchHit>> {
+    let mut values: Vec<Value> = Vec::new();
+    let mut conditions = Vec::new();
+    let mut condition = |sql: &str, value: Value| {
+        values.push(value);
+        conditions.push(sql.replace('?', &format!("?{}", values.len())));
+    };
+    let text = !query.trim().is_empty();
+    if text {
+        condition("project_notes MATCH ?", query.to_string().into());
+    }
+    if let Some(group_id) = &filter.group_id {
+        condition("p.group_id = ?", group_id.clone().into());
+    }
+    if let Some(project_type) = filter.project_type {
+        condition("p.type = ?", project_type.as_str().to_string().into());
+    }
+    for tag in &filter.tags {
+        condition(
+            "EXISTS (SELECT 1 FROM project_tags t WHERE t.project_id = p.id AND t.tag = ?)",
+            tag.clone().into(),
+        );
+    }
+    if let Some(participant) = &filter.participant {
+        condition(
+            "EXISTS (SELECT 1 FROM project_metadata m, json_each(m.participants) j
+                     WHERE m.project_id = p.id AND j.value LIKE ?)",
+            format!("%{participant}%").into(),
+        );
+    }
+    if let Some(from) = &filter.from {
+        condition("substr(p.date, 1, length(?)) >= ?", from.clone().into());
+    }
+    if let Some(to) = &filter.to {
+        condition("substr(p.date, 1, length(?)) <= ?", to.clone().into());
+    }
+    let conditions = if conditions.is_empty() {
+        "1".to_string()
+    } else {
+        conditions.join(" AND ")
+    };
+    values.push((limit as i64).into());
+
+    let sql = if text {
+        format!(
+            "SELECT n.project_id, p.name, snippet(project_notes, -1, '[', ']', '...', 16)
+             FROM project_notes n JOIN audio_projects p ON p.id = n.project_id
+             WHERE {conditions}
+             ORDER BY rank
+             LIMIT ?{}",
+            values.len()
+        )
+    } else {
+        format!(
+            "SELECT p.id, p.name, '' FROM audio_projects p
+             WHERE {conditions}
+             ORDER BY p.date DESC
+             LIMIT ?{}",
+            values.len()
+        )
+    };
+    let mut stmt = conn.prepare(&sql)?;
+    let hits = stmt
+        .query_map(params_from_iter(values), |r| {
+            Ok(SearchHit {
+                project_id: r.get(0)?,
+                project_name: r.get(1)?,
+                snippet: r.get(2)?,
+            })
+        })?
+        .collect();
+    hits
+}
+
+pub fn get_project_notes(conn: &Connection, project_id: &str) -> Result<Option<ProjectNotes>> {
+    conn.query_row(
+        "SELECT transcript, summary, email, lecture_notes
+         FROM project_notes WHERE project_id = ?1",
+        params![project_id],
+        |r| {
+            Ok(ProjectNotes {
+                transcript: r.get(0)?,
+                summary: r.get(1)?,
+                email: r.get(2)?,
+                lecture_notes: r.get(3)?,
+            })
+        },
+    )
+    .optional()
+}
+
 pub fn insert_project_notes(
     conn: &Connection,
     project_id: &str,
     transcript: &str,
     summary: &str,
     email: &str,
-    lecture_notes: &str
+    lecture_notes: &str,
 ) -> Result<()> {
+    // keep a single search row per project
+    conn.execute(
+        "DELETE FROM project_notes WHERE project_id = ?1",
+        params![project_id],
+    )?;
     conn.execute(
         "INSERT INTO project_notes (
             project_id, transcript, summary, email, lecture_notes
@@ -35,3 +347,258 @@ pub fn insert_project_notes(
     )?;
     Ok(())
 }
+
+pub fn insert_token_usage(
+    conn: &Connection,
+    project_id: &str,
+    note_kind: &str,
+    usage: &TokenUsage,
+) -> Result<()> {
+    conn.execute(
+        "INSERT INTO note_token_usage (
+            project_id, note_kind, prompt_tokens, completion_tokens,
+            transcript_tokens, transcript_tokens_total, truncated, created_at
+         ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            project_id,
+            note_kind,
+            usage.prompt_tokens,
+            usage.completion_tokens,
+            usage.transcript_tokens,
+            usage.transcript_tokens_total,
+            usage.truncated,
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+// Placeholders already used for a project, oldest first
+pub fn list_redactions(conn: &Connection, project_id: &str) -> Result<Vec<Redaction>> {
+    let mut stmt = conn.prepare(
+        "SELECT placeholder, entity, original FROM redactions
+         WHERE project_id = ?1 ORDER BY created_at, rowid",
+    )?;
+    let redactions = stmt
+        .query_map(params![project_id], |r| {
+            let entity: String = r.get(1)?;
+            Ok(Redaction {
+                placeholder: r.get(0)?,
+                entity: EntityKind::parse(&entity).ok_or_else(|| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        1,
+                        rusqlite::types::Type::Text,
+                        format!("unknown redaction entity {entity}").into(),
+                    )
+                })?,
+                original: r.get(2)?,
+            })
+        })?
+        .collect();
+    redactions
+}
+
+pub fn insert_redaction(conn: &Connection, project_id: &str, redaction: &Redaction) -> Result<()> {
+    conn.execute(
+        "INSERT INTO redactions (project_id, placeholder, entity, original, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            project_id,
+            redaction.placeholder,
+            redaction.entity.as_str(),
+            redaction.original,
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+// Lists are stored as JSON arrays
+fn to_json(list: &[String]) -> String {
+    serde_json::to_string(list).unwrap_or_else(|_| "[]".to_string())
+}
+
+fn list_from_row(r: &Row, index: usize) -> Result<Vec<String>> {
+    let json: String = r.get(index)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+// Replaces an event imported before with the same id
+pub fn upsert_calendar_event(conn: &Connection, event: &CalendarEvent) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT OR REPLACE INTO calendar_events ({EVENT_COLUMNS}, imported_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)"
+        ),
+        params![
+            event.id,
+            event.summary,
+            event.starts_at,
+            event.ends_at,
+            event.all_day,
+            event.timezone,
+            event.rrule,
+            to_json(&event.exdates),
+            to_json(&event.attendees),
+            event.agenda,
+            event.location,
+            to_json(&event.categories),
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn list_calendar_events(conn: &Connection) -> Result<Vec<CalendarEvent>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {EVENT_COLUMNS} FROM calendar_events ORDER BY starts_at, id"
+    ))?;
+    let events = stmt
+        .query_map([], |r| {
+            Ok(CalendarEvent {
+                id: r.get(0)?,
+                summary: r.get(1)?,
+                starts_at: r.get(2)?,
+                ends_at: r.get(3)?,
+                all_day: r.get(4)?,
+                timezone: r.get(5)?,
+                rrule: r.get(6)?,
+                exdates: list_from_row(r, 7)?,
+                attendees: list_from_row(r, 8)?,
+                agenda: r.get(9)?,
+                location: r.get(10)?,
+                categories: list_from_row(r, 11)?,
+            })
+        })?
+        .collect();
+    events
+}
+
+pub fn set_project_meeting(conn: &Connection, project_id: &str, meeting: &Meeting) -> Result<()> {
+    conn.execute(
+        &format!(
+            "INSERT OR REPLACE INTO project_meetings (project_id, {MEETING_COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+        ),
+        params![
+            project_id,
+            meeting.event_id,
+            meeting.title,
+            meeting.starts_at,
+            meeting.ends_at,
+            to_json(&meeting.attendees),
+            meeting.agenda,
+            meeting.location,
+            to_json(&meeting.categories)
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn get_project_meeting(conn: &Connection, project_id: &str) -> Result<Option<Meeting>> {
+    conn.query_row(
+        &format!("SELECT {MEETING_COLUMNS} FROM project_meetings WHERE project_id = ?1"),
+        params![project_id],
+        |r| {
+            Ok(Meeting {
+                event_id: r.get(0)?,
+                title: r.get(1)?,
+                starts_at: r.get(2)?,
+                ends_at: r.get(3)?,
+                attendees: list_from_row(r, 4)?,
+                agenda: r.get(5)?,
+                location: r.get(6)?,
+                categories: list_from_row(r, 7)?,
+            })
+        },
+    )
+    .optional()
+}
+
+// Metadata of a project, empty when none was stored
+pub fn get_project_metadata(conn: &Connection, project_id: &str) -> Result<ProjectMetadata> {
+    let mut metadata = conn
+        .query_row(
+            "SELECT description, participants, duration_seconds, source_device, fields
+             FROM project_metadata WHERE project_id = ?1",
+            params![project_id],
+            |r| {
+                let fields: String = r.get(4)?;
+                Ok(ProjectMetadata {
+                    description: r.get(0)?,
+                    participants: list_from_row(r, 1)?,
+                    duration_seconds: r.get(2)?,
+                    source_device: r.get(3)?,
+                    fields: serde_json::from_str(&fields).map_err(|e| {
+                        rusqlite::Error::FromSqlConversionFailure(
+                            4,
+                            rusqlite::types::Type::Text,
+                            e.into(),
+                        )
+                    })?,
+                    ..Default::default()
+                })
+            },
+        )
+        .optional()?
+        .unwrap_or_default();
+    let mut stmt =
+        conn.prepare("SELECT tag FROM project_tags WHERE project_id = ?1 ORDER BY rowid")?;
+    metadata.tags = stmt
+        .query_map(params![project_id], |r| r.get(0))?
+        .collect::<Result<_>>()?;
+    Ok(metadata)
+}
+
+// Replaces the project's metadata and tags, run it in a transaction
+pub fn set_project_metadata(
+    conn: &Connection,
+    project_id: &str,
+    metadata: &ProjectMetadata,
+) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO project_metadata
+            (project_id, description, participants, duration_seconds, source_device, fields)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            project_id,
+            metadata.description,
+            to_json(&metadata.participants),
+            metadata.duration_seconds,
+            metadata.source_device,
+            serde_json::to_string(&metadata.fields).unwrap_or_else(|_| "{}".to_string())
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM project_tags WHERE project_id = ?1",
+        params![project_id],
+    )?;
+    for tag in &metadata.tags {
+        conn.execute(
+            "INSERT OR IGNORE INTO project_tags (project_id, tag) VALUES (?1, ?2)",
+            params![project_id, tag],
+        )?;
+    }
+    Ok(())
+}
+
+// Every tag in use, the most used first
+pub fn list_tags(conn: &Connection) -> Result<Vec<TagCount>> {
+    let mut stmt = conn.prepare(
+        // spelled the way it was first used, MIN makes SQLite take the tag
+        // from that row
+        "SELECT tag, COUNT(*), MIN(rowid) FROM project_tags
+         GROUP BY tag COLLATE NOCASE ORDER BY COUNT(*) DESC, tag COLLATE NOCASE",
+    )?;
+    let tags = stmt
+        .query_map([], |r| {
+            Ok(TagCount {
+                tag: r.get(0)?,
+                projects: r.get(1)?,
+            })
+        })?
+        .collect();
+    tags
+}
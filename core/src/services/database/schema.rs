@@ -2,17 +2,52 @@ use rusqlite::{Connection, Result};
 use std::fs;
 use std::path::{Path, PathBuf};
 
+// Run in order on top of schema.sql, the database user_version counts how many were applied
+const MIGRATIONS: &[&str] = &[
+    include_str!("../../assets/migrations/0001_project_notes.sql"),
+    include_str!("../../assets/migrations/0002_media.sql"),
+    include_str!("../../assets/migrations/0003_redactions.sql"),
+    include_str!("../../assets/migrations/0004_calendar.sql"),
+    include_str!("../../assets/migrations/0005_project_metadata.sql"),
+    include_str!("../../assets/migrations/0006_group_tree.sql"),
+];
+
+// user_version of a database this build created or migrated
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+pub fn db_path(base_dir: &Path) -> PathBuf {
+    base_dir.join("db").join("project.db")
+}
+
 // Returns the path to the local SQLite database
 fn get_db_path(base_dir: &Path) -> PathBuf {
-    let db_dir = base_dir.join("db");
-    fs::create_dir_all(&db_dir).expect("Failed to create database directory");
-    db_dir.join("project.db")
+    let db_path = db_path(base_dir);
+    if let Some(db_dir) = db_path.parent() {
+        fs::create_dir_all(db_dir).expect("Failed to create database directory");
+    }
+    db_path
 }
 
 pub fn init_db(base_dir: &Path) -> Result<()> {
-    let db_path = get_db_path(base_dir);
-    let conn = Connection::open(db_path)?;
+    let mut conn = Connection::open(get_db_path(base_dir))?;
+    apply_schema(&mut conn)
+}
+
+// Creates the tables and applies migrations, for callers that open the connection
+// themselves, e.g. with an encryption key
+pub fn apply_schema(conn: &mut Connection) -> Result<()> {
     let schema = include_str!("../../assets/schema.sql");
     conn.execute_batch(schema)?;
+    migrate(conn)
+}
+
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let applied: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
     Ok(())
 }
@@ -0,0 +1,257 @@
+use crate::services::database::{
+    models::{NoteVersion, VersionSource},
+    queries::insert_project_notes,
+};
+use rusqlite::{params, Connection, OptionalExtension, Result, Row};
+use similar::TextDiff;
+use uuid::Uuid;
+
+// Note kinds that have a column in the project_notes search index
+const INDEXED_KINDS: [&str; 4] = ["transcript", "summary", "email", "lecture_notes"];
+
+const VERSION_COLUMNS: &str = "id, project_id, note_kind, content, source, template, model,
+    params, parent_id, is_current, created_at";
+
+// A version about to be stored, it becomes the current one for its note kind
+#[derive(Debug, Clone)]
+pub struct NewNoteVersion {
+    pub project_id: String,
+    pub note_kind: String,
+    pub content: String,
+    pub source: VersionSource,
+    pub template: Option<String>,
+    pub model: Option<String>,
+    pub params: Option<String>,
+}
+
+fn version_from_row(row: &Row) -> Result<NoteVersion> {
+    let source: String = row.get(4)?;
+    Ok(NoteVersion {
+        id: row.get(0)?,
+        project_id: row.get(1)?,
+        note_kind: row.get(2)?,
+        content: row.get(3)?,
+        source: VersionSource::parse(&source).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(
+                4,
+                rusqlite::types::Type::Text,
+                format!("unknown version source {source}").into(),
+            )
+        })?,
+        template: row.get(5)?,
+        model: row.get(6)?,
+        params: row.get(7)?,
+        parent_id: row.get(8)?,
+        is_current: row.get(9)?,
+        created_at: row.get(10)?,
+    })
+}
+
+// Stores a new version and makes it current, the search index follows in the same transaction
+pub fn record_version(conn: &mut Connection, new: NewNoteVersion) -> Result<NoteVersion> {
+    let tx = conn.transaction()?;
+    let version = insert_version(&tx, new)?;
+    tx.commit()?;
+    Ok(version)
+}
+
+// Same as record_version for callers that already hold a transaction
+pub fn insert_version(conn: &Connection, new: NewNoteVersion) -> Result<NoteVersion> {
+    let parent_id = current_version(conn, &new.project_id, &new.note_kind)?.map(|v| v.id);
+    let version = NoteVersion {
+        id: Uuid::new_v4().to_string(),
+        project_id: new.project_id,
+        note_kind: new.note_kind,
+        content: new.content,
+        source: new.source,
+        template: new.template,
+        model: new.model,
+        params: new.params,
+        parent_id,
+        is_current: true,
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+
+    conn.execute(
+        "UPDATE note_versions SET is_current = 0 WHERE project_id = ?1 AND note_kind = ?2",
+        params![version.project_id, version.note_kind],
+    )?;
+    insert_row(conn, &version)?;
+    refresh_search_index(conn, &version.project_id)?;
+    Ok(version)
+}
+
+fn insert_row(conn: &Connection, version: &NoteVersion) -> Result<()> {
+    conn.execute(
+        &format!("INSERT INTO note_versions ({VERSION_COLUMNS})
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"),
+        params![
+            version.id,
+            version.project_id,
+            version.note_kind,
+            version.content,
+            version.source.as_str(),
+            version.template,
+            version.model,
+            version.params,
+            version.parent_id,
+            version.is_current,
+            version.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+// Stores versions copied from another library as they are, e.g. out of a project
+// bundle. Parents must come before their children and the ids must be new here.
+pub fn import_versions(conn: &Connection, versions: &[NoteVersion]) -> Result<()> {
+    let mut projects = Vec::new();
+    for version in versions {
+        insert_row(conn, version)?;
+        if !projects.contains(&version.project_id) {
+            projects.push(version.project_id.clone());
+        }
+    }
+    for project_id in projects {
+        refresh_search_index(conn, &project_id)?;
+    }
+    Ok(())
+}
+
+// A user edit is stored like any other version, without generation metadata
+pub fn record_user_edit(
+    conn: &mut Connection,
+    project_id: &str,
+    note_kind: &str,
+    content: &str,
+) -> Result<NoteVersion> {
+    record_version(
+        conn,
+        NewNoteVersion {
+            project_id: project_id.to_string(),
+            note_kind: note_kind.to_string(),
+            content: content.to_string(),
+            source: VersionSource::UserEdit,
+            template: None,
+            model: None,
+            params: None,
+        },
+    )
+}
+
+pub fn get_version(conn: &Connection, id: &str) -> Result<Option<NoteVersion>> {
+    conn.query_row(
+        &format!("SELECT {VERSION_COLUMNS} FROM note_versions WHERE id = ?1"),
+        params![id],
+        version_from_row,
+    )
+    .optional()
+}
+
+pub fn current_version(
+    conn: &Connection,
+    project_id: &str,
+    note_kind: &str,
+) -> Result<Option<NoteVersion>> {
+    conn.query_row(
+        &format!(
+            "SELECT {VERSION_COLUMNS} FROM note_versions
+             WHERE project_id = ?1 AND note_kind = ?2 AND is_current = 1"
+        ),
+        params![project_id, note_kind],
+        version_from_row,
+    )
+    .optional()
+}
+
+// All versions of one note, newest first
+pub fn list_versions(
+    conn: &Connection,
+    project_id: &str,
+    note_kind: &str,
+) -> Result<Vec<NoteVersion>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {VERSION_COLUMNS} FROM note_versions
+         WHERE project_id = ?1 AND note_kind = ?2
+         ORDER BY created_at DESC, rowid DESC"
+    ))?;
+    let versions = stmt
+        .query_map(params![project_id, note_kind], version_from_row)?
+        .collect();
+    versions
+}
+
+// Every version of every note of a project, oldest first
+pub fn project_versions(conn: &Connection, project_id: &str) -> Result<Vec<NoteVersion>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {VERSION_COLUMNS} FROM note_versions
+         WHERE project_id = ?1 ORDER BY created_at, rowid"
+    ))?;
+    let versions = stmt
+        .query_map(params![project_id], version_from_row)?
+        .collect();
+    versions
+}
+
+// Makes an existing version the current one without creating a new version
+pub fn set_current_version(conn: &mut Connection, id: &str) -> Result<NoteVersion> {
+    let tx = conn.transaction()?;
+    let mut version = get_version(&tx, id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    tx.execute(
+        "UPDATE note_versions SET is_current = (id = ?3) WHERE project_id = ?1 AND note_kind = ?2",
+        params![version.project_id, version.note_kind, version.id],
+    )?;
+    refresh_search_index(&tx, &version.project_id)?;
+    tx.commit()?;
+    version.is_current = true;
+    Ok(version)
+}
+
+// Copies an old version into a new current one, so history stays append-only
+pub fn restore_version(conn: &mut Connection, id: &str) -> Result<NoteVersion> {
+    let old = get_version(conn, id)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    record_version(
+        conn,
+        NewNoteVersion {
+            project_id: old.project_id,
+            note_kind: old.note_kind,
+            content: old.content,
+            source: VersionSource::Restored,
+            template: old.template,
+            model: old.model,
+            params: old.params,
+        },
+    )
+}
+
+// Unified diff from version `from` to version `to`
+pub fn diff_versions(conn: &Connection, from: &str, to: &str) -> Result<String> {
+    let old = get_version(conn, from)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    let new = get_version(conn, to)?.ok_or(rusqlite::Error::QueryReturnedNoRows)?;
+    Ok(TextDiff::from_lines(&old.content, &new.content)
+        .unified_diff()
+        .context_radius(3)
+        .header(&old.created_at, &new.created_at)
+        .to_string())
+}
+
+// Rewrites the project's search row from the current versions, kinds without
+// a version keep what was indexed before
+fn refresh_search_index(conn: &Connection, project_id: &str) -> Result<()> {
+    let indexed: Option<[String; 4]> = conn
+        .query_row(
+            "SELECT transcript, summary, email, lecture_notes
+             FROM project_notes WHERE project_id = ?1",
+            params![project_id],
+            |row| Ok([row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?]),
+        )
+        .optional()?;
+    let mut columns = indexed.unwrap_or_default();
+    for (column, kind) in columns.iter_mut().zip(INDEXED_KINDS) {
+        if let Some(version) = current_version(conn, project_id, kind)? {
+            *column = version.content;
+        }
+    }
+    let [transcript, summary, email, lecture_notes] = columns;
+    insert_project_notes(conn, project_id, &transcript, &summary, &email, &lecture_notes)
+}
@@ -0,0 +1,368 @@
+use crate::services::audio::ffmpeg::INSTALL_HINT;
+use crate::services::llm::llama_server::LlamaServerConfig;
+use crate::services::transcribe::whisperx::path_to_model;
+use serde::Serialize;
+use std::{
+    fs::File,
+    io::{ErrorKind, Read},
+    path::{Path, PathBuf},
+    process::Command,
+};
+use sysinfo::{Disks, System};
+
+const GIB: u64 = 1024 * 1024 * 1024;
+// Below this new recordings and notes may not fit at all
+const MIN_FREE_DISK: u64 = GIB;
+// Below this we only warn, preprocessed audio and whisperx models add up quickly
+const LOW_FREE_DISK: u64 = 5 * GIB;
+// llama.cpp needs the weights plus KV cache and compute buffers
+const MODEL_MEMORY_FACTOR: f64 = 1.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+}
+
+// Outcome of probing one dependency, `fix` tells the user what to do when it is not Ok
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &str, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warning(name: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Warning,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn error(name: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status: CheckStatus::Error,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnvironmentReport {
+    pub checks: Vec<Check>,
+}
+
+impl EnvironmentReport {
+    // Worst status over all checks
+    pub fn status(&self) -> CheckStatus {
+        self.checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(CheckStatus::Ok)
+    }
+
+    // True when nothing would stop a recording from being processed
+    pub fn is_ready(&self) -> bool {
+        self.status() != CheckStatus::Error
+    }
+}
+
+// Probes everything the pipeline shells out to or loads, in the order the pipeline needs them
+pub fn check_environment(llama: &LlamaServerConfig, data_dir: &Path) -> EnvironmentReport {
+    let python = check_python();
+    let whisperx = if python.status == CheckStatus::Ok {
+        check_whisperx_import()
+    } else {
+        Check::error(
+            "whisperx",
+            "skipped, python3 is not available",
+            "install python3 first",
+        )
+    };
+    let runner = path_to_model().ok();
+
+    EnvironmentReport {
+        checks: vec![
+            check_ffmpeg(),
+            python,
+            whisperx,
+            check_runner(runner.as_deref()),
+            check_huggingface_token(runner.as_deref()),
+            check_llama_server(&llama.server_path),
+            check_model(&llama.model_path),
+            check_disk_space(data_dir),
+            check_memory(&llama.model_path, llama.n_gpu_layers),
+        ],
+    }
+}
+
+// Runs `program arg` and returns the first non-empty line of its output
+fn probe(program: impl AsRef<std::ffi::OsStr>, args: &[&str]) -> std::io::Result<(bool, String)> {
+    let output = Command::new(program).args(args).output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let line = stdout
+        .lines()
+        .chain(stderr.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("")
+        .to_string();
+    Ok((output.status.success(), line))
+}
+
+fn check_ffmpeg() -> Check {
+    let fix = INSTALL_HINT;
+    match probe("ffmpeg", &["-version"]) {
+        Ok((true, version)) => Check::ok("ffmpeg", version),
+        Ok((false, output)) => {
+            Check::error("ffmpeg", format!("ffmpeg -version failed: {output}"), fix)
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            Check::error("ffmpeg", "ffmpeg not found on PATH", fix)
+        }
+        Err(err) => Check::error("ffmpeg", err.to_string(), fix),
+    }
+}
+
+fn check_python() -> Check {
+    let fix = "install Python 3, e.g. with conda env create -f python_backend/env.yaml";
+    match probe("python3", &["--version"]) {
+        Ok((true, version)) => Check::ok("python3", version),
+        Ok((false, output)) => Check::error("python3", output, fix),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            Check::error("python3", "python3 not found on PATH", fix)
+        }
+        Err(err) => Check::error("python3", err.to_string(), fix),
+    }
+}
+
+fn check_whisperx_import() -> Check {
+    let script =
+        "import importlib.metadata as m, whisperx; print('whisperx', m.version('whisperx'))";
+    let fix = "activate the taunote conda env or run pip install whisperx python-dotenv";
+    let output = match Command::new("python3").args(["-c", script]).output() {
+        Ok(output) => output,
+        Err(err) => return Check::error("whisperx", err.to_string(), fix),
+    };
+    if output.status.success() {
+        let version = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Check::ok("whisperx", version)
+    } else {
+        // the last line of a traceback is the actual error, e.g. ModuleNotFoundError
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().rev().find(|line| !line.trim().is_empty());
+        Check::error(
+            "whisperx",
+            format!("import failed: {}", reason.unwrap_or("unknown error")),
+            fix,
+        )
+    }
+}
+
+fn check_runner(runner: Option<&Path>) -> Check {
+    match runner {
+        Some(path) => Check::ok("whisperx_runner.py", path.display().to_string()),
+        None => Check::error(
+            "whisperx_runner.py",
+            "not found next to the crate or the taunote binary",
+            "keep the python_backend folder next to the taunote binary",
+        ),
+    }
+}
+
+// whisperx_runner.py calls load_dotenv(), which searches upwards from the script's folder
+fn check_huggingface_token(runner: Option<&Path>) -> Check {
+    let name = "HUGGINGFACE_TOKEN";
+    let fix = "add HUGGINGFACE_TOKEN=<token> to a .env file next to python_backend, the token needs access to pyannote/speaker-diarization";
+    if std::env::var(name).is_ok_and(|token| !token.trim().is_empty()) {
+        return Check::ok(name, "set in the environment");
+    }
+
+    let mut searched = Vec::new();
+    let start = runner
+        .and_then(Path::parent)
+        .map(Path::to_path_buf)
+        .or_else(|| std::env::current_dir().ok());
+    for dir in start.iter().flat_map(|dir| dir.ancestors()) {
+        let env_file = dir.join(".env");
+        if !env_file.is_file() {
+            continue;
+        }
+        let found = dotenvy::from_path_iter(&env_file).is_ok_and(|mut vars| {
+            vars.any(|var| var.is_ok_and(|(key, value)| key == name && !value.trim().is_empty()))
+        });
+        if found {
+            return Check::ok(name, format!("found in {}", env_file.display()));
+        }
+        searched.push(env_file);
+    }
+
+    let detail = if searched.is_empty() {
+        "no .env file found".to_string()
+    } else {
+        let files: Vec<String> = searched.iter().map(|p| p.display().to_string()).collect();
+        format!("not set in {}", files.join(", "))
+    };
+    Check::error(name, detail, fix)
+}
+
+fn check_llama_server(server_path: &Path) -> Check {
+    let name = "llama-server";
+    let fix = "build llama.cpp (cmake --build build --target llama-server) and set the server path to build/bin/llama-server";
+    if !server_path.is_file() {
+        return Check::error(name, format!("no binary at {}", server_path.display()), fix);
+    }
+    match Command::new(server_path).arg("--version").output() {
+        Ok(output) => {
+            // llama.cpp prints the build info to stderr
+            let text = String::from_utf8_lossy(&output.stderr).to_string()
+                + &String::from_utf8_lossy(&output.stdout);
+            let version = text
+                .lines()
+                .find(|line| line.starts_with("version"))
+                .unwrap_or("version unknown");
+            Check::ok(
+                name,
+                format!("{} ({})", server_path.display(), version.trim()),
+            )
+        }
+        Err(err) if err.kind() == ErrorKind::PermissionDenied => Check::error(
+            name,
+            format!("{} is not executable", server_path.display()),
+            format!("chmod +x {}", server_path.display()),
+        ),
+        Err(err) => Check::error(name, format!("{}: {err}", server_path.display()), fix),
+    }
+}
+
+fn check_model(model_path: &Path) -> Check {
+    let name = "model";
+    let fix = "download a GGUF model (e.g. from Hugging Face) and select it in the settings";
+    let mut magic = [0u8; 4];
+    let read = File::open(model_path).and_then(|mut file| {
+        file.read_exact(&mut magic)?;
+        file.metadata()
+    });
+    match read {
+        Ok(metadata) if &magic == b"GGUF" => Check::ok(
+            name,
+            format!(
+                "{} ({})",
+                model_path.display(),
+                format_bytes(metadata.len())
+            ),
+        ),
+        Ok(_) => Check::error(
+            name,
+            format!("{} is not a GGUF file", model_path.display()),
+            fix,
+        ),
+        Err(err) if err.kind() == ErrorKind::NotFound => {
+            Check::error(name, format!("no file at {}", model_path.display()), fix)
+        }
+        Err(err) => Check::error(
+            name,
+            format!("cannot read {}: {err}", model_path.display()),
+            fix,
+        ),
+    }
+}
+
+fn check_disk_space(data_dir: &Path) -> Check {
+    let name = "disk space";
+    // the data dir may not exist yet on first run
+    let existing: PathBuf = data_dir
+        .ancestors()
+        .find(|dir| dir.exists())
+        .and_then(|dir| dir.canonicalize().ok())
+        .unwrap_or_else(|| data_dir.to_path_buf());
+    let disks = Disks::new_with_refreshed_list();
+    let disk = disks
+        .list()
+        .iter()
+        .filter(|disk| existing.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len());
+    let Some(disk) = disk else {
+        return Check::warning(
+            name,
+            format!("could not find the disk holding {}", data_dir.display()),
+            "make sure the data directory is on a mounted disk",
+        );
+    };
+
+    let free = disk.available_space();
+    let detail = format!(
+        "{} free on {} ({})",
+        format_bytes(free),
+        disk.mount_point().display(),
+        data_dir.display()
+    );
+    let fix = "free up space on the disk holding the taunote data directory";
+    if free < MIN_FREE_DISK {
+        Check::error(name, detail, fix)
+    } else if free < LOW_FREE_DISK {
+        Check::warning(name, detail, fix)
+    } else {
+        Check::ok(name, detail)
+    }
+}
+
+fn check_memory(model_path: &Path, n_gpu_layers: u32) -> Check {
+    let name = "memory";
+    let mut system = System::new();
+    system.refresh_memory();
+    let available = system.available_memory();
+    let Ok(model_size) = model_path.metadata().map(|metadata| metadata.len()) else {
+        return Check::ok(name, format!("{} available", format_bytes(available)));
+    };
+
+    let needed = (model_size as f64 * MODEL_MEMORY_FACTOR) as u64;
+    let detail = format!(
+        "{} available, the model needs about {}",
+        format_bytes(available),
+        format_bytes(needed)
+    );
+    if available >= needed {
+        Check::ok(name, detail)
+    } else if n_gpu_layers > 0 {
+        // offloaded layers live in VRAM, so this may still work
+        Check::warning(
+            name,
+            format!("{detail}, {n_gpu_layers} layers are offloaded to the GPU"),
+            "if loading fails, pick a smaller quantization or close other applications",
+        )
+    } else {
+        Check::error(
+            name,
+            detail,
+            "pick a smaller quantization, offload layers to the GPU or close other applications",
+        )
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    if bytes >= GIB {
+        format!("{:.1} GiB", bytes as f64 / GIB as f64)
+    } else {
+        format!("{} MiB", bytes / (1024 * 1024))
+    }
+}
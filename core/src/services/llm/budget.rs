@@ -0,0 +1,152 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::llm::llama_queue::LlmService;
+use serde::Serialize;
+
+// Placeholder in prompt templates that is replaced by the (possibly truncated) transcript
+pub const TRANSCRIPT_PLACEHOLDER: &str = "{transcript}";
+
+// Tokens kept free on top of n_predict, chat templates and BOS/EOS are not exact
+const SAFETY_MARGIN: usize = 32;
+
+// How a transcript was fitted into the context window
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PromptBudget {
+    pub context_tokens: usize,
+    pub template_tokens: usize,
+    pub reserved_for_output: usize,
+    pub transcript_tokens: usize,
+    pub transcript_tokens_total: usize,
+    pub truncated: bool,
+}
+
+impl PromptBudget {
+    pub fn prompt_tokens(&self) -> usize {
+        self.template_tokens + self.transcript_tokens
+    }
+
+    // Share of the transcript that made it into the prompt, 1.0 when nothing was cut
+    pub fn coverage(&self) -> f32 {
+        if self.transcript_tokens_total == 0 {
+            1.0
+        } else {
+            self.transcript_tokens as f32 / self.transcript_tokens_total as f32
+        }
+    }
+}
+
+pub struct FittedPrompt {
+    pub prompt: String,
+    pub budget: PromptBudget,
+}
+
+// Tokens left for transcript text once the template and the output are accounted for
+async fn transcript_allowance(
+    llm: &LlmService,
+    template: &str,
+    n_predict: u32,
+) -> Result<(usize, usize, usize)> {
+    let context_tokens = llm
+        .context_per_slot()
+        .ok_or_else(|| TaunoteError::LlmUnavailable("LLM service is not running".to_string()))?
+        as usize;
+    let template_tokens = llm
+        .count_tokens(&template.replace(TRANSCRIPT_PLACEHOLDER, ""), true)
+        .await?;
+    let reserved = n_predict as usize + SAFETY_MARGIN;
+    let allowance = context_tokens.saturating_sub(template_tokens + reserved);
+    if allowance == 0 {
+        return Err(TaunoteError::Config(format!(
+            "Prompt template ({template_tokens} tokens) and output ({n_predict} tokens) do not fit in a {context_tokens} token context"
+        )));
+    }
+    Ok((context_tokens, template_tokens, allowance))
+}
+
+// Fills the template with as much of the transcript as fits in the model context
+pub async fn fit_transcript(
+    llm: &LlmService,
+    template: &str,
+    transcript: &str,
+    n_predict: u32,
+) -> Result<FittedPrompt> {
+    let (context_tokens, template_tokens, allowance) =
+        transcript_allowance(llm, template, n_predict).await?;
+    let total = llm.count_tokens(transcript, false).await?;
+    let (kept, kept_tokens) = if total <= allowance {
+        (transcript, total)
+    } else {
+        take_tokens(llm, transcript, total, allowance).await?
+    };
+
+    Ok(FittedPrompt {
+        prompt: template.replace(TRANSCRIPT_PLACEHOLDER, kept),
+        budget: PromptBudget {
+            context_tokens,
+            template_tokens,
+            reserved_for_output: n_predict as usize,
+            transcript_tokens: kept_tokens,
+            transcript_tokens_total: total,
+            truncated: kept.len() < transcript.len(),
+        },
+    })
+}
+
+// Splits a transcript into pieces that each fit the template, cutting at line breaks when possible
+pub async fn chunk_transcript(
+    llm: &LlmService,
+    template: &str,
+    transcript: &str,
+    n_predict: u32,
+) -> Result<Vec<String>> {
+    let (_, _, allowance) = transcript_allowance(llm, template, n_predict).await?;
+    let mut chunks = Vec::new();
+    let mut rest = transcript;
+    while !rest.is_empty() {
+        let total = llm.count_tokens(rest, false).await?;
+        if total <= allowance {
+            chunks.push(rest.to_string());
+            break;
+        }
+        let (head, _) = take_tokens(llm, rest, total, allowance).await?;
+        let cut = match head.rfind('\n') {
+            Some(pos) if pos > head.len() / 2 => pos + 1,
+            _ => head.len(),
+        };
+        chunks.push(rest[..cut].to_string());
+        rest = &rest[cut..];
+    }
+    Ok(chunks)
+}
+
+// Longest prefix of `text` that tokenizes to at most `limit` tokens, but never less
+// than one char. Starts from a proportional estimate and shrinks, so it only needs
+// a few /tokenize calls.
+async fn take_tokens<'a>(
+    llm: &LlmService,
+    text: &'a str,
+    total_tokens: usize,
+    limit: usize,
+) -> Result<(&'a str, usize)> {
+    let mut len = text.len() * limit / total_tokens.max(1);
+    loop {
+        let prefix = &text[..floor_char_boundary(text, len)];
+        if prefix.is_empty() {
+            // a first char worth more than `limit` still goes, or callers never get past it
+            let first = &text[..text.chars().next().map_or(0, char::len_utf8)];
+            return Ok((first, llm.count_tokens(first, false).await?));
+        }
+        let tokens = llm.count_tokens(prefix, false).await?;
+        if tokens <= limit {
+            return Ok((prefix, tokens));
+        }
+        len = prefix.len() * limit / tokens * 95 / 100;
+    }
+}
+
+fn floor_char_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
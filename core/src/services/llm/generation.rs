@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+// Sampling settings for one completion. Unset fields fall back to the
+// task defaults in prompt_tasks, then to llama-server's own defaults.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenerationParams {
+    pub temperature: Option<f32>,
+    pub top_p: Option<f32>,
+    pub top_k: Option<u32>,
+    pub repeat_penalty: Option<f32>,
+    pub seed: Option<u64>,
+    pub stop: Vec<String>,
+    pub max_tokens: Option<u32>,
+    // GBNF grammar constraining the output
+    pub grammar: Option<String>,
+}
+
+impl GenerationParams {
+    // Returns self with every field set in `overrides` replaced
+    pub fn with_overrides(mut self, overrides: &GenerationParams) -> Self {
+        if overrides.temperature.is_some() {
+            self.temperature = overrides.temperature;
+        }
+        if overrides.top_p.is_some() {
+            self.top_p = overrides.top_p;
+        }
+        if overrides.top_k.is_some() {
+            self.top_k = overrides.top_k;
+        }
+        if overrides.repeat_penalty.is_some() {
+            self.repeat_penalty = overrides.repeat_penalty;
+        }
+        if overrides.seed.is_some() {
+            self.seed = overrides.seed;
+        }
+        if !overrides.stop.is_empty() {
+            self.stop = overrides.stop.clone();
+        }
+        if overrides.max_tokens.is_some() {
+            self.max_tokens = overrides.max_tokens;
+        }
+        if overrides.grammar.is_some() {
+            self.grammar = overrides.grammar.clone();
+        }
+        self
+    }
+
+    // Writes the set fields into a llama-server /completions request body
+    pub fn apply_to(&self, body: &mut serde_json::Value) {
+        let fields = [
+            ("temperature", self.temperature.map(serde_json::Value::from)),
+            ("top_p", self.top_p.map(serde_json::Value::from)),
+            ("top_k", self.top_k.map(serde_json::Value::from)),
+            ("repeat_penalty", self.repeat_penalty.map(serde_json::Value::from)),
+            ("seed", self.seed.map(serde_json::Value::from)),
+            ("n_predict", self.max_tokens.map(serde_json::Value::from)),
+            ("grammar", self.grammar.clone().map(serde_json::Value::from)),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                body[key] = value;
+            }
+        }
+        if !self.stop.is_empty() {
+            body["stop"] = serde_json::Value::from(self.stop.clone());
+        }
+    }
+}
@@ -1,52 +1,40 @@
-use anyhow::{anyhow, Result};
+use crate::error::{Result, TaunoteError};
+use crate::services::llm::generation::GenerationParams;
 use reqwest::Client;
-use std::{
-    ffi::OsStr,
-    path::PathBuf,
-    process::{Child, Command},
-};
-use sysinfo::System;
-use tokio::{
-    net::TcpStream,
-    time::{sleep, Duration},
-};
+use serde::Serialize;
 
-#[derive(Debug)]
+// Output of one completion with the token counts reported by llama-server
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Completion {
+    pub text: String,
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+}
+
+// Thin HTTP client for a running llama-server, the process itself is owned by LlamaSupervisor
+#[derive(Debug, Clone)]
 pub struct LlamaClient {
-    server: Child,
     client: Client,
     host: String,
     port: u16,
 }
 
 impl LlamaClient {
-    pub async fn try_new(
-        server_path: PathBuf,
-        model_path: PathBuf,
-        host: String,
-        port: u16,
-    ) -> Result<Self> {
-        let server = spawn_llama_server(&server_path, &model_path, port)?;
-        wait_for_server(&host, port, None, None).await?;
-        let client = Client::new();
-        wait_for_model_ready(&host, port, &client, None, None).await?;
-
-        Ok(Self {
-            server,
-            client,
+    pub fn new(host: String, port: u16) -> Self {
+        Self {
+            client: Client::new(),
             host,
             port,
-        })
+        }
     }
 
-    pub async fn complete(&self, prompt: String, n_predict: u32) -> Result<String> {
+    pub async fn complete(&self, prompt: String, params: &GenerationParams) -> Result<Completion> {
         let url = format!("http://{}:{}/completions", self.host, self.port);
-        let body = serde_json::json!({
+        let mut body = serde_json::json!({
             "prompt": prompt,
             "stream": false,
-            "temperature": 0.7,
-            "n_predict": n_predict,
         });
+        params.apply_to(&mut body);
 
         let resp = self.client.post(&url).json(&body).send().await?;
         let raw = resp.text().await?;
@@ -54,96 +42,41 @@ impl LlamaClient {
         let json: serde_json::Value = serde_json::from_str(&raw)?;
 
         if let Some(err) = json.get("error") {
-            Err(anyhow!("LLM error: {}", err))
+            Err(TaunoteError::Llm(format!("llama-server error: {err}")))
         } else {
-            Ok(json["content"].as_str().unwrap_or("").to_string())
+            Ok(Completion {
+                text: json["content"].as_str().unwrap_or("").to_string(),
+                prompt_tokens: json["tokens_evaluated"].as_u64().unwrap_or(0) as u32,
+                completion_tokens: json["tokens_predicted"].as_u64().unwrap_or(0) as u32,
+            })
         }
     }
-}
-
-impl Drop for LlamaClient {
-    fn drop(&mut self) {
-        let _ = self.server.kill();
-    }
-}
-
-// Helper for default configuration for the LlamaClient
-pub fn default_llama(port: u16) -> (PathBuf, PathBuf, String, u16) {
-    (
-        PathBuf::from("/home/andrea/llama.cpp/build/bin/llama-server"),
-        PathBuf::from("/home/andrea/taunote/models/llama/Meta-Llama-3.1-8B-Instruct-Q6_K_L.gguf"),
-        "127.0.0.1".to_string(),
-        port,
-    )
-}
 
-// Kills all currently running llama server processes
-fn kill_all_llama_servers() {
-    let sys = System::new_all();
-    for proc in sys.processes_by_name(OsStr::new("llama-server")) {
-        let _ = proc.kill();
-    }
-}
+    // Number of tokens the loaded model produces for `text`
+    pub async fn count_tokens(&self, text: &str, add_special: bool) -> Result<usize> {
+        let url = format!("http://{}:{}/tokenize", self.host, self.port);
+        let body = serde_json::json!({
+            "content": text,
+            "add_special": add_special,
+        });
 
-// Spawns the llama server subprocess
-fn spawn_llama_server(server_path: &PathBuf, model_path: &PathBuf, port: u16) -> Result<Child> {
-    kill_all_llama_servers();
-    let child = Command::new(server_path)
-        .args([
-            "--model",
-            model_path.to_str().unwrap(),
-            "--port",
-            &port.to_string(),
-            "--n-gpu-layers",
-            "35",
-            "--ctx-size",
-            "4096",
-            "--no-warmup",
-        ])
-        .spawn()?;
-    Ok(child)
-}
+        let json: serde_json::Value = self
+            .client
+            .post(&url)
+            .json(&body)
+            .send()
+            .await?
+            .json()
+            .await?;
 
-// waits for TCP server to be available
-async fn wait_for_server(
-    host: &str,
-    port: u16,
-    retries: Option<u32>,
-    delay_ms: Option<u64>,
-) -> Result<()> {
-    let addr = format!("{host}:{port}");
-    for _ in 0..retries.unwrap_or(10) {
-        if TcpStream::connect(&addr).await.is_ok() {
-            return Ok(());
-        }
-        sleep(Duration::from_millis(delay_ms.unwrap_or(500))).await;
+        json["tokens"]
+            .as_array()
+            .map(|tokens| tokens.len())
+            .ok_or_else(|| TaunoteError::Llm(format!("Unexpected /tokenize response: {json}")))
     }
-    Err(anyhow!("TCP Timeout"))
 }
 
-// Waits for model to be ready by sending a dummy completion request
-async fn wait_for_model_ready(
-    host: &str,
-    port: u16,
-    client: &Client,
-    retries: Option<u32>,
-    delay_ms: Option<u64>,
-) -> Result<()> {
-    let url = format!("http://{host}:{port}/completions");
-    let dummy = serde_json::json!({
-        "prompt": "ping",
-        "stream": false,
-        "temperature": 0.0,
-        "n_predict": 1
-    });
-
-    for _ in 0..retries.unwrap_or(60) {
-        if let Ok(resp) = client.post(&url).json(&dummy).send().await {
-            if resp.status().is_success() {
-                return Ok(());
-            }
-        }
-        sleep(Duration::from_millis(delay_ms.unwrap_or(500))).await;
-    }
-    Err(anyhow!("HTTP Timeout"))
+// True when the request never got an answer, e.g. because the server crashed mid-job
+pub fn is_unreachable(err: &TaunoteError) -> bool {
+    matches!(err, TaunoteError::LlmUnavailable(_))
 }
@@ -1,86 +1,470 @@
-use crate::services::llm::llama_client::LlamaClient;
-use anyhow::{anyhow, Error as AnyhowError, Result};
-use once_cell::sync::OnceCell;
-use std::path::PathBuf;
-use tokio::{
+use crate::error::{Result, TaunoteError};
+use crate::services::llm::{
+    generation::GenerationParams,
+    llama_client::{is_unreachable, Completion, LlamaClient},
+    llama_server::{wait_until_ready, LlamaServerConfig, LlamaSupervisor, ServerStatus},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
     sync::{
-        mpsc::{self, Sender},
-        oneshot, Mutex,
+        atomic::{AtomicU64, Ordering as AtomicOrdering},
+        Arc, Mutex, RwLock,
     },
-    task,
 };
+use tokio::{
+    sync::{oneshot, watch, Notify},
+    task::{self, JoinHandle},
+    time::{sleep, timeout, Duration},
+};
+
+pub type JobId = u64;
+
+// Tries a second apart against a server that is reported ready but refuses
+// connections, e.g. a wrong port, before the job fails. A restart starts over.
+const MAX_UNREACHABLE_TRIES: u32 = 10;
+
+// Higher priorities are always served first, FIFO within a priority
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobPriority {
+    Background,
+    #[default]
+    Normal,
+    Interactive,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct JobOptions {
+    pub priority: JobPriority,
+    // covers waiting for the server and generating, None waits forever
+    pub timeout: Option<Duration>,
+    // shown when inspecting the queue, e.g. "summary"
+    pub label: String,
+}
+
+impl JobOptions {
+    pub fn interactive(label: &str) -> Self {
+        Self {
+            priority: JobPriority::Interactive,
+            timeout: Some(Duration::from_secs(300)),
+            label: label.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Pending,
+    Running,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub label: String,
+    pub priority: JobPriority,
+    pub state: JobState,
+    pub enqueued_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueSnapshot {
+    pub pending: Vec<JobInfo>,
+    pub running: Vec<JobInfo>,
+}
 
 pub struct CompletionJob {
+    pub id: JobId,
     pub prompt: String,
-    pub n_predict: u32,
-    pub responder: oneshot::Sender<Result<String, AnyhowError>>,
-}
-
-static LLAMA_QUEUE: OnceCell<Mutex<Sender<CompletionJob>>> = OnceCell::new();
-
-pub async fn init_llama_queue() {
-    // create new channel with buffer size 16.
-    let (tx, mut rx) = mpsc::channel::<CompletionJob>(16);
-
-    // spawn a background task to handle LLM completion requests
-    task::spawn(async move {
-        let client = match LlamaClient::try_new(
-            PathBuf::from("/home/andrea/llama.cpp/build/bin/llama-server"),
-            PathBuf::from(
-                "/home/andrea/taunote/models/llama/Meta-Llama-3.1-8B-Instruct-Q6_K_L.gguf",
-            ),
-            "127.0.0.1".to_string(),
-            8081,
-        )
-        .await
-        {
-            Ok(c) => c,
-            Err(err) => {
-                eprintln!("Failed to start LlamaClient: {err}");
-                return;
+    pub params: GenerationParams,
+    pub options: JobOptions,
+    pub enqueued_at: String,
+    pub responder: oneshot::Sender<Result<Completion>>,
+}
+
+impl CompletionJob {
+    fn info(&self, state: JobState) -> JobInfo {
+        JobInfo {
+            id: self.id,
+            label: self.options.label.clone(),
+            priority: self.options.priority,
+            state,
+            enqueued_at: self.enqueued_at.clone(),
+        }
+    }
+}
+
+// BinaryHeap is a max-heap: highest priority first, then lowest id
+impl Ord for CompletionJob {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.options
+            .priority
+            .cmp(&other.options.priority)
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for CompletionJob {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for CompletionJob {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for CompletionJob {}
+
+#[derive(Default)]
+struct QueueState {
+    pending: BinaryHeap<CompletionJob>,
+    running: HashMap<JobId, JobInfo>,
+}
+
+#[derive(Default)]
+struct SharedQueue {
+    state: Mutex<QueueState>,
+    available: Notify,
+    next_id: AtomicU64,
+}
+
+impl SharedQueue {
+    async fn next_job(&self) -> CompletionJob {
+        loop {
+            let notified = self.available.notified();
+            if let Some(job) = self.take_pending() {
+                return job;
             }
-        };
+            notified.await;
+        }
+    }
+
+    fn take_pending(&self) -> Option<CompletionJob> {
+        let mut state = self.state.lock().unwrap();
+        let job = state.pending.pop()?;
+        state.running.insert(job.id, job.info(JobState::Running));
+        Some(job)
+    }
+
+    fn finish(&self, id: JobId) {
+        self.state.lock().unwrap().running.remove(&id);
+    }
+
+    fn remove_pending(&self, pred: impl Fn(&CompletionJob) -> bool) -> Vec<CompletionJob> {
+        let mut state = self.state.lock().unwrap();
+        let (removed, kept): (Vec<_>, Vec<_>) = state.pending.drain().partition(|job| pred(job));
+        state.pending.extend(kept);
+        removed
+    }
+}
+
+// Handle to the llama-server and its job queue. Cheap to clone, every clone
+// talks to the same server. Starts out stopped until `start` is called.
+#[derive(Clone, Default)]
+pub struct LlmService {
+    running: Arc<RwLock<Option<Running>>>,
+}
 
-        // process jobs from the queue as they arrive
-        while let Some(CompletionJob {
+struct Running {
+    config: LlamaServerConfig,
+    shared: Arc<SharedQueue>,
+    supervisor: LlamaSupervisor,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl LlmService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Starts the server, errors if it is already running
+    pub fn start(&self, config: LlamaServerConfig) -> Result<()> {
+        let mut running = self.running.write().unwrap();
+        if running.is_some() {
+            return Err(TaunoteError::Config(
+                "LLM service is already running".to_string(),
+            ));
+        }
+        *running = Some(Running::spawn(config)?);
+        Ok(())
+    }
+
+    // Starts the server unless it is already running with the same config,
+    // so setup can safely run again e.g. after a frontend reload
+    pub async fn ensure_started(&self, config: LlamaServerConfig) -> Result<()> {
+        let same = self
+            .running
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|running| running.config == config);
+        let healthy = !matches!(
+            self.status(),
+            ServerStatus::Failed { .. } | ServerStatus::Stopped
+        );
+        match same {
+            Some(true) if healthy => Ok(()),
+            Some(_) => self.restart(config).await,
+            None => self.start(config),
+        }
+    }
+
+    // Shuts the current server down and starts a new one, e.g. with a different model
+    pub async fn restart(&self, config: LlamaServerConfig) -> Result<()> {
+        self.shutdown().await;
+        self.start(config)
+    }
+
+    // Stops the server, pending jobs fail and running jobs are dropped
+    pub async fn shutdown(&self) {
+        let running = self.running.write().unwrap().take();
+        if let Some(running) = running {
+            running.stop().await;
+        }
+    }
+
+    // Current state of the llama-server, Stopped if the service is not running
+    pub fn status(&self) -> ServerStatus {
+        self.running
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|running| running.supervisor.status())
+            .unwrap_or(ServerStatus::Stopped)
+    }
+
+    pub fn config(&self) -> Option<LlamaServerConfig> {
+        self.running
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|running| running.config.clone())
+    }
+
+    pub fn submit(
+        &self,
+        prompt: String,
+        params: GenerationParams,
+        options: JobOptions,
+    ) -> Result<JobHandle> {
+        let shared = self.shared()?;
+        // create oneshot channel to receive the result from the processing task.
+        let (tx, rx) = oneshot::channel();
+        let id = shared.next_id.fetch_add(1, AtomicOrdering::Relaxed);
+        let job = CompletionJob {
+            id,
             prompt,
-            n_predict,
-            responder,
-        }) = rx.recv().await
-        {
-            let raw = client.complete(prompt, n_predict).await;
-            let anyhow_result = raw.map_err(|e| anyhow!(e.to_string()));
-            let _ = responder.send(anyhow_result);
+            params,
+            options,
+            enqueued_at: chrono::Utc::now().to_rfc3339(),
+            responder: tx,
+        };
+
+        shared.state.lock().unwrap().pending.push(job);
+        shared.available.notify_one();
+
+        Ok(JobHandle { id, receiver: rx })
+    }
+
+    pub async fn complete(
+        &self,
+        prompt: String,
+        params: GenerationParams,
+        options: JobOptions,
+    ) -> Result<Completion> {
+        self.submit(prompt, params, options)?.wait().await
+    }
+
+    // Token count of `text` for the loaded model, waits for the server to be ready
+    pub async fn count_tokens(&self, text: &str, add_special: bool) -> Result<usize> {
+        let (host, mut status) = {
+            let running = self.running.read().unwrap();
+            let running = running.as_ref().ok_or_else(not_running)?;
+            (running.config.host.clone(), running.supervisor.subscribe())
+        };
+        let port = wait_until_ready(&mut status).await?;
+        LlamaClient::new(host, port)
+            .count_tokens(text, add_special)
+            .await
+    }
+
+    // File name of the loaded model, stored with generated notes
+    pub fn model_name(&self) -> Option<String> {
+        self.config().and_then(|config| {
+            config
+                .model_path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+    }
+
+    // Context tokens available to a single job, the server splits ctx-size between its slots
+    pub fn context_per_slot(&self) -> Option<u32> {
+        self.config()
+            .map(|config| config.ctx_size / config.parallel.max(1))
+    }
+
+    // Pending jobs in the order they will run, plus the jobs currently generating
+    pub fn snapshot(&self) -> Result<QueueSnapshot> {
+        let shared = self.shared()?;
+        let state = shared.state.lock().unwrap();
+        let mut pending: Vec<&CompletionJob> = state.pending.iter().collect();
+        pending.sort_by(|a, b| b.cmp(a));
+        Ok(QueueSnapshot {
+            pending: pending
+                .into_iter()
+                .map(|job| job.info(JobState::Pending))
+                .collect(),
+            running: state.running.values().cloned().collect(),
+        })
+    }
+
+    // Removes a job that has not started yet, returns false if it is running or unknown
+    pub fn cancel(&self, id: JobId) -> Result<bool> {
+        let cancelled = self.shared()?.remove_pending(|job| job.id == id);
+        let found = !cancelled.is_empty();
+        for job in cancelled {
+            let _ = job.responder.send(Err(TaunoteError::Llm(format!(
+                "LLM job {id} was cancelled"
+            ))));
         }
-    });
-
-    // store the sender in static OnceCell, will panic if called more than once.
-    LLAMA_QUEUE
-        .set(Mutex::new(tx))
-        .expect("LLAMA_QUEUE was already initialized");
-}
-
-pub async fn enqueue_completion(prompt: String, n_predict: u32) -> Result<String> {
-    // create oneshot channel to receive the result from the processing task.
-    let (tx, rx) = oneshot::channel();
-    let job = CompletionJob {
-        prompt,
-        n_predict,
-        responder: tx,
-    };
-
-    // get a lock on the global queue sender.
-    let sender = LLAMA_QUEUE
-        .get()
-        .ok_or_else(|| anyhow!("LLAMA_QUEUE not initialized"))?
-        .lock()
-        .await;
-
-    // send the job to queue and wait for response
-    println!("Sending job to llm");
-    sender.send(job).await?;
-    let response: Result<String, AnyhowError> = rx.await?;
-    let output = response?;
-
-    Ok(output)
+        Ok(found)
+    }
+
+    fn shared(&self) -> Result<Arc<SharedQueue>> {
+        self.running
+            .read()
+            .unwrap()
+            .as_ref()
+            .map(|running| running.shared.clone())
+            .ok_or_else(not_running)
+    }
+}
+
+impl Running {
+    fn spawn(config: LlamaServerConfig) -> Result<Self> {
+        if !config.server_path.is_file() {
+            return Err(TaunoteError::MissingDependency {
+                name: "llama-server".to_string(),
+                hint: format!(
+                    "no binary at {}, build llama.cpp or set the server path",
+                    config.server_path.display()
+                ),
+            });
+        }
+        if !config.model_path.is_file() {
+            return Err(TaunoteError::MissingDependency {
+                name: "GGUF model".to_string(),
+                hint: format!(
+                    "no file at {}, download a model or pick another one",
+                    config.model_path.display()
+                ),
+            });
+        }
+
+        let shared = Arc::new(SharedQueue::default());
+        let supervisor = LlamaSupervisor::spawn(config.clone());
+
+        // one worker per llama-server slot, each handles a job at a time
+        let workers = (0..config.parallel.max(1))
+            .map(|_| {
+                let shared = shared.clone();
+                let host = config.host.clone();
+                let mut status = supervisor.subscribe();
+                task::spawn(async move {
+                    loop {
+                        let job = shared.next_job().await;
+                        let result = match job.options.timeout {
+                            Some(limit) => timeout(limit, run_job(&host, &mut status, &job))
+                                .await
+                                .unwrap_or_else(|_| {
+                                    Err(TaunoteError::Llm(format!(
+                                        "LLM job {} timed out after {limit:?}",
+                                        job.id
+                                    )))
+                                }),
+                            None => run_job(&host, &mut status, &job).await,
+                        };
+                        shared.finish(job.id);
+                        let _ = job.responder.send(result);
+                    }
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            config,
+            shared,
+            supervisor,
+            workers,
+        })
+    }
+
+    async fn stop(self) {
+        for worker in &self.workers {
+            worker.abort();
+        }
+        for job in self.shared.remove_pending(|_| true) {
+            let _ = job.responder.send(Err(TaunoteError::LlmUnavailable(
+                "LLM service was shut down".to_string(),
+            )));
+        }
+        self.supervisor.shutdown().await;
+    }
+}
+
+fn not_running() -> TaunoteError {
+    TaunoteError::LlmUnavailable("LLM service is not running".to_string())
+}
+
+// Sends the job to the server, a job that hits a crashed server waits for the restart and is sent again
+async fn run_job(
+    host: &str,
+    status: &mut watch::Receiver<ServerStatus>,
+    job: &CompletionJob,
+) -> Result<Completion> {
+    let mut tries = 0;
+    loop {
+        let port = wait_until_ready(status).await?;
+        let client = LlamaClient::new(host.to_string(), port);
+        match client.complete(job.prompt.clone(), &job.params).await {
+            Err(err) if is_unreachable(&err) => {
+                tries += 1;
+                if tries >= MAX_UNREACHABLE_TRIES {
+                    return Err(TaunoteError::LlmUnavailable(format!(
+                        "llama-server on port {port} is unreachable: {err}"
+                    )));
+                }
+                eprintln!("llama-server unreachable, retrying job after restart: {err}");
+                tokio::select! {
+                    _ = status.changed() => tries = 0,
+                    _ = sleep(Duration::from_secs(1)) => {}
+                }
+            }
+            other => return other,
+        }
+    }
+}
+
+// A queued job, await `wait` for its output
+pub struct JobHandle {
+    pub id: JobId,
+    receiver: oneshot::Receiver<Result<Completion>>,
+}
+
+impl JobHandle {
+    pub async fn wait(self) -> Result<Completion> {
+        self.receiver.await.map_err(|_| {
+            TaunoteError::LlmUnavailable(format!(
+                "LLM job {} was dropped, the service shut down",
+                self.id
+            ))
+        })?
+    }
 }
@@ -0,0 +1,276 @@
+use crate::error::{Result, TaunoteError};
+use reqwest::Client;
+use serde::Serialize;
+use std::{
+    fs::{create_dir_all, OpenOptions},
+    net::TcpListener,
+    path::PathBuf,
+    process::Stdio,
+};
+use tokio::{
+    process::{Child, Command},
+    sync::watch,
+    task::JoinHandle,
+    time::{sleep, Duration, Instant},
+};
+
+// State of the managed llama-server, reported to the UI
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ServerStatus {
+    Starting,
+    Ready { port: u16 },
+    Restarting { attempt: u32, reason: String },
+    Failed { reason: String },
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LlamaServerConfig {
+    pub server_path: PathBuf,
+    pub model_path: PathBuf,
+    pub host: String,
+    // None picks a free port on every (re)start
+    pub port: Option<u16>,
+    pub ctx_size: u32,
+    pub n_gpu_layers: u32,
+    // number of requests the server decodes at once, the context is split between them
+    pub parallel: u32,
+    // stdout and stderr of the server are appended here, inherited when None
+    pub log_path: Option<PathBuf>,
+    pub startup_timeout: Duration,
+    pub health_interval: Duration,
+    pub max_restarts: u32,
+}
+
+impl Default for LlamaServerConfig {
+    fn default() -> Self {
+        Self {
+            server_path: PathBuf::from("/home/andrea/llama.cpp/build/bin/llama-server"),
+            model_path: PathBuf::from(
+                "/home/andrea/taunote/models/llama/Meta-Llama-3.1-8B-Instruct-Q6_K_L.gguf",
+            ),
+            host: "127.0.0.1".to_string(),
+            port: None,
+            ctx_size: 4096,
+            n_gpu_layers: 35,
+            parallel: 1,
+            log_path: None,
+            startup_timeout: Duration::from_secs(120),
+            health_interval: Duration::from_secs(5),
+            max_restarts: 5,
+        }
+    }
+}
+
+// Owns the llama-server child process: restarts it when it dies and
+// publishes its status through a watch channel.
+pub struct LlamaSupervisor {
+    status: watch::Receiver<ServerStatus>,
+    shutdown: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+impl LlamaSupervisor {
+    pub fn spawn(config: LlamaServerConfig) -> Self {
+        let (status_tx, status) = watch::channel(ServerStatus::Starting);
+        let (shutdown, shutdown_rx) = watch::channel(false);
+        let task = tokio::spawn(supervise(config, status_tx, shutdown_rx));
+        Self {
+            status,
+            shutdown,
+            task,
+        }
+    }
+
+    pub fn status(&self) -> ServerStatus {
+        self.status.borrow().clone()
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<ServerStatus> {
+        self.status.clone()
+    }
+
+    // Stops the server and waits for the supervising task to finish
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(true);
+        let _ = self.task.await;
+    }
+}
+
+// Waits until the server is ready and returns its port, or errors if it failed for good
+pub async fn wait_until_ready(status: &mut watch::Receiver<ServerStatus>) -> Result<u16> {
+    let current = status
+        .wait_for(|s| {
+            matches!(
+                s,
+                ServerStatus::Ready { .. } | ServerStatus::Failed { .. } | ServerStatus::Stopped
+            )
+        })
+        .await
+        .map_err(|_| unavailable("llama-server supervisor is gone"))?
+        .clone();
+    match current {
+        ServerStatus::Ready { port } => Ok(port),
+        ServerStatus::Failed { reason } => {
+            Err(unavailable(&format!("llama-server failed: {reason}")))
+        }
+        _ => Err(unavailable("llama-server was stopped")),
+    }
+}
+
+fn unavailable(reason: &str) -> TaunoteError {
+    TaunoteError::LlmUnavailable(reason.to_string())
+}
+
+async fn supervise(
+    config: LlamaServerConfig,
+    status: watch::Sender<ServerStatus>,
+    mut shutdown: watch::Receiver<bool>,
+) {
+    let client = Client::new();
+    let mut attempt = 0;
+
+    loop {
+        let reason = match run_once(&config, &client, &status, &mut shutdown).await {
+            Ok(()) => {
+                let _ = status.send(ServerStatus::Stopped);
+                return;
+            }
+            Err(err) => err.to_string(),
+        };
+        eprintln!("llama-server stopped unexpectedly: {reason}");
+
+        // a server that got ready ran fine for a while, only crashes in a row
+        // count towards max_restarts and the backoff
+        if matches!(*status.borrow(), ServerStatus::Ready { .. }) {
+            attempt = 0;
+        }
+        attempt += 1;
+        if attempt > config.max_restarts {
+            let _ = status.send(ServerStatus::Failed { reason });
+            return;
+        }
+        let _ = status.send(ServerStatus::Restarting { attempt, reason });
+
+        // exponential backoff, capped at 30s
+        let backoff = Duration::from_millis(500 * 2u64.pow(attempt.min(6)));
+        tokio::select! {
+            _ = sleep(backoff.min(Duration::from_secs(30))) => {}
+            _ = shutdown.changed() => {
+                let _ = status.send(ServerStatus::Stopped);
+                return;
+            }
+        }
+    }
+}
+
+// Runs one server process until shutdown (Ok) or until it crashes or stops responding (Err)
+async fn run_once(
+    config: &LlamaServerConfig,
+    client: &Client,
+    status: &watch::Sender<ServerStatus>,
+    shutdown: &mut watch::Receiver<bool>,
+) -> Result<()> {
+    let port = match config.port {
+        Some(port) => port,
+        None => pick_free_port(&config.host)?,
+    };
+    let mut child = spawn_llama_server(config, port)?;
+
+    let startup = tokio::select! {
+        res = wait_for_health(&config.host, port, client, config.startup_timeout) => Some(res),
+        exit = child.wait() => Some(Err(unavailable(&format!("llama-server exited during startup: {:?}", exit?.code())))),
+        _ = shutdown.changed() => None,
+    };
+    match startup {
+        Some(Ok(())) => {}
+        Some(Err(err)) => {
+            let _ = child.kill().await;
+            return Err(err);
+        }
+        None => {
+            let _ = child.kill().await;
+            return Ok(());
+        }
+    }
+    let _ = status.send(ServerStatus::Ready { port });
+
+    loop {
+        let healthy = tokio::select! {
+            exit = child.wait() => {
+                return Err(unavailable(&format!("llama-server exited with status {:?}", exit?.code())));
+            }
+            _ = sleep(config.health_interval) => is_healthy(&config.host, port, client).await,
+            _ = shutdown.changed() => {
+                let _ = child.kill().await;
+                return Ok(());
+            }
+        };
+        if !healthy {
+            let _ = child.kill().await;
+            return Err(unavailable("llama-server health check failed"));
+        }
+    }
+}
+
+// Asks the OS for an unused port on the given host
+fn pick_free_port(host: &str) -> Result<u16> {
+    let listener = TcpListener::bind((host, 0))?;
+    Ok(listener.local_addr()?.port())
+}
+
+// Spawns the llama server subprocess, only this child is ever killed
+fn spawn_llama_server(config: &LlamaServerConfig, port: u16) -> Result<Child> {
+    let mut cmd = Command::new(&config.server_path);
+    cmd.arg("--model")
+        .arg(&config.model_path)
+        .arg("--host")
+        .arg(&config.host)
+        .arg("--port")
+        .arg(port.to_string())
+        .arg("--n-gpu-layers")
+        .arg(config.n_gpu_layers.to_string())
+        .arg("--ctx-size")
+        .arg(config.ctx_size.to_string())
+        .arg("--parallel")
+        .arg(config.parallel.max(1).to_string())
+        .arg("--no-warmup")
+        .kill_on_drop(true);
+
+    if let Some(log_path) = &config.log_path {
+        if let Some(parent) = log_path.parent() {
+            create_dir_all(parent)?;
+        }
+        let log = OpenOptions::new().create(true).append(true).open(log_path)?;
+        cmd.stdout(Stdio::from(log.try_clone()?))
+            .stderr(Stdio::from(log));
+    }
+
+    Ok(cmd.spawn()?)
+}
+
+// llama-server answers /health with 200 once the model is loaded, 503 while loading
+async fn is_healthy(host: &str, port: u16, client: &Client) -> bool {
+    let url = format!("http://{host}:{port}/health");
+    match client
+        .get(&url)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await
+    {
+        Ok(resp) => resp.status().is_success(),
+        Err(_) => false,
+    }
+}
+
+async fn wait_for_health(host: &str, port: u16, client: &Client, timeout: Duration) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        if is_healthy(host, port, client).await {
+            return Ok(());
+        }
+        sleep(Duration::from_millis(500)).await;
+    }
+    Err(unavailable(&format!("llama-server did not become healthy within {timeout:?}")))
+}
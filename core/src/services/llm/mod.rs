@@ -1,3 +1,6 @@
+pub mod budget;
+pub mod generation;
 pub mod llama_client;
 pub mod llama_queue;
+pub mod llama_server;
 pub mod prompt_tasks;
@@ -1,36 +1,135 @@
-use crate::services::llm::llama_queue::enqueue_completion;
-use anyhow::Result;
-use std::path::PathBuf;
-use tokio::fs;
-
-fn truncate(text: &str, max_len: usize) -> &str {
-    &text[..std::cmp::min(max_len, text.len())]
-}
-
-pub async fn summarize(path: &PathBuf) -> Result<String> {
-    let text = fs::read_to_string(path).await?;
-    let prompt = format!(
-        "Summarize the following transcript:\n{}",
-        truncate(&text, 3000)
-    );
-    println!("Summary sent to queue");
-    enqueue_completion(prompt, 512).await
-}
-
-pub async fn generate_email(path: &PathBuf) -> Result<String> {
-    let text = fs::read_to_string(path).await?;
-    let prompt = format!(
-        "Write a professional follow-up email based on this meeting:\n{}",
-        truncate(&text, 3000)
-    );
-    enqueue_completion(prompt, 512).await
-}
-
-pub async fn generate_lecture_notes(path: &PathBuf) -> Result<String> {
-    let text = fs::read_to_string(path).await?;
-    let prompt = format!(
-        "Write clear and concise lecture notes with bullet points and sections from this transcript:\n{}",
-        truncate(&text, 3000)
-    );
-    enqueue_completion(prompt, 600).await
+use crate::error::Result;
+use crate::services::database::{
+    models::{Meeting, TokenUsage, VersionSource},
+    versions::NewNoteVersion,
+};
+use crate::services::llm::{
+    budget::{fit_transcript, TRANSCRIPT_PLACEHOLDER},
+    generation::GenerationParams,
+    llama_queue::{JobOptions, LlmService},
+};
+
+// Replaced with what the calendar says about the meeting, or nothing
+pub const MEETING_PLACEHOLDER: &str = "{meeting}";
+
+pub const SUMMARY_TEMPLATE: &str = "{meeting}Summarize the following transcript:\n{transcript}";
+pub const EMAIL_TEMPLATE: &str =
+    "{meeting}Write a professional follow-up email based on this meeting:\n{transcript}";
+pub const LECTURE_NOTES_TEMPLATE: &str =
+    "{meeting}Write clear and concise lecture notes with bullet points and sections from this transcript:\n{transcript}";
+pub const NAMES_TEMPLATE: &str =
+    "List the names of all people mentioned in this text, one per line and nothing else. Write NONE if there are none.\n{transcript}";
+
+// Output used when a task does not set max_tokens
+const DEFAULT_N_PREDICT: u32 = 512;
+
+// Generated text plus what produced it and how many tokens it took
+#[derive(Debug, Clone)]
+pub struct Generated {
+    pub text: String,
+    pub usage: TokenUsage,
+    pub template: String,
+    pub model: Option<String>,
+    pub params: GenerationParams,
+}
+
+impl Generated {
+    // Version record for this output, to be stored with record_version
+    pub fn to_version(&self, project_id: &str, note_kind: &str) -> NewNoteVersion {
+        NewNoteVersion {
+            project_id: project_id.to_string(),
+            note_kind: note_kind.to_string(),
+            content: self.text.clone(),
+            source: VersionSource::Generated,
+            template: Some(self.template.clone()),
+            model: self.model.clone(),
+            params: serde_json::to_string(&self.params).ok(),
+        }
+    }
+}
+
+// Fills in {meeting} with the meeting's title, attendees and agenda, so the
+// model can tell who is speaking and what was planned
+pub fn with_meeting(template: &str, meeting: Option<&Meeting>) -> String {
+    let context = match meeting {
+        Some(meeting) => {
+            let mut context = format!("Meeting: {}\n", meeting.title);
+            if !meeting.attendees.is_empty() {
+                context.push_str(&format!(
+                    "Attendees, who may be the speakers: {}\n",
+                    meeting.attendees.join(", ")
+                ));
+            }
+            if let Some(agenda) = &meeting.agenda {
+                // the transcript goes in later, the agenda must not take its place
+                let agenda = agenda.replace(TRANSCRIPT_PLACEHOLDER, "");
+                context.push_str(&format!("Agenda:\n{agenda}\n"));
+            }
+            context + "\n"
+        }
+        None => String::new(),
+    };
+    template.replace(MEETING_PLACEHOLDER, &context)
+}
+
+// Default sampling per task, callers can override any field
+pub fn summary_params() -> GenerationParams {
+    GenerationParams {
+        temperature: Some(0.7),
+        max_tokens: Some(512),
+        ..Default::default()
+    }
+}
+
+// Emails should come out the same on every run
+pub fn email_params() -> GenerationParams {
+    GenerationParams {
+        temperature: Some(0.2),
+        seed: Some(42),
+        max_tokens: Some(512),
+        ..Default::default()
+    }
+}
+
+// Extraction, not writing: no sampling
+pub fn names_params() -> GenerationParams {
+    GenerationParams {
+        temperature: Some(0.0),
+        max_tokens: Some(256),
+        ..Default::default()
+    }
+}
+
+pub fn lecture_notes_params() -> GenerationParams {
+    GenerationParams {
+        temperature: Some(0.5),
+        max_tokens: Some(600),
+        ..Default::default()
+    }
+}
+
+// Fits the transcript into the template by token count and runs the completion
+pub async fn run_task(
+    llm: &LlmService,
+    transcript: &str,
+    template: &str,
+    params: GenerationParams,
+    options: JobOptions,
+) -> Result<Generated> {
+    let n_predict = params.max_tokens.unwrap_or(DEFAULT_N_PREDICT);
+    let fitted = fit_transcript(llm, template, transcript, n_predict).await?;
+    let completion = llm.complete(fitted.prompt, params.clone(), options).await?;
+    Ok(Generated {
+        text: completion.text,
+        template: template.to_string(),
+        model: llm.model_name(),
+        params,
+        usage: TokenUsage {
+            prompt_tokens: completion.prompt_tokens,
+            completion_tokens: completion.completion_tokens,
+            transcript_tokens: fitted.budget.transcript_tokens as u32,
+            transcript_tokens_total: fitted.budget.transcript_tokens_total as u32,
+            truncated: fitted.budget.truncated,
+        },
+    })
 }
@@ -0,0 +1,195 @@
+use crate::error::Result;
+use crate::services::{
+    audio::ffmpeg::encode_audio,
+    database::{
+        models::{Media, MediaFormat},
+        queries::{
+            delete_media, get_media, list_audio_projects, set_project_media, unreferenced_media,
+            upsert_media,
+        },
+    },
+};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rusqlite::Connection;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+};
+
+const MEDIA_DIR: &str = "media";
+
+// Speech stays intelligible far below music bitrates
+const OPUS_CODEC: &[&str] = &["-c:a", "libopus", "-b:a", "32k", "-application", "voip"];
+pub(crate) const FLAC_CODEC: &[&str] = &["-c:a", "flac"];
+
+// What prune_media removed
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PruneReport {
+    // projects past the retention period that lost their recording
+    pub expired_projects: Vec<String>,
+    pub removed: Vec<Media>,
+    pub freed_bytes: u64,
+}
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+pub fn media_path(base_dir: &Path, media: &Media) -> PathBuf {
+    base_dir.join(&media.relative_path)
+}
+
+fn media_relative_path(hash: &str, extension: &str) -> String {
+    format!("{MEDIA_DIR}/{}/{hash}.{extension}", &hash[..2])
+}
+
+// Stores a recording under `media/{hash[..2]}/{hash}.{ext}`. A recording that is
+// already stored is reused whatever format it was stored in.
+pub fn import_media(
+    conn: &Connection,
+    base_dir: &Path,
+    source: &Path,
+    format: MediaFormat,
+) -> Result<Media> {
+    let hash = hash_file(source)?;
+    if let Some(existing) = get_media(conn, &hash)? {
+        if media_path(base_dir, &existing).is_file() {
+            return Ok(existing);
+        }
+    }
+
+    let extension = match format {
+        MediaFormat::Original => source
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_else(|| "audio".to_string()),
+        MediaFormat::Flac => "flac".to_string(),
+        MediaFormat::Opus => "opus".to_string(),
+    };
+    let relative_path = media_relative_path(&hash, &extension);
+    let dest = base_dir.join(&relative_path);
+    // written under another name first so a crash never leaves a partial file
+    // behind the hash, ffmpeg picks the container from the last extension
+    let partial = dest.with_file_name(format!("{hash}.partial.{extension}"));
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let stored = match format {
+        MediaFormat::Original => fs::copy(source, &partial).map(|_| ()).map_err(Into::into),
+        MediaFormat::Flac => encode_audio(source, &partial, FLAC_CODEC),
+        MediaFormat::Opus => encode_audio(source, &partial, OPUS_CODEC),
+    };
+    if let Err(err) = stored.and_then(|()| Ok(fs::rename(&partial, &dest)?)) {
+        let _ = fs::remove_file(&partial);
+        return Err(err);
+    }
+
+    let media = Media {
+        hash,
+        relative_path,
+        format,
+        original_name: source
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        size_bytes: fs::metadata(&dest)?.len(),
+        created_at: Utc::now().to_rfc3339(),
+    };
+    upsert_media(conn, &media)?;
+    Ok(media)
+}
+
+// Stores a recording copied from another library, e.g. out of a project bundle,
+// under the hash and format it had there. `media.hash` must be a SHA-256.
+pub fn adopt_media(
+    conn: &Connection,
+    base_dir: &Path,
+    media: &Media,
+    source: &Path,
+) -> Result<Media> {
+    if let Some(existing) = get_media(conn, &media.hash)? {
+        if media_path(base_dir, &existing).is_file() {
+            return Ok(existing);
+        }
+    }
+    let extension = Path::new(&media.relative_path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_else(|| "audio".to_string());
+    let relative_path = media_relative_path(&media.hash, &extension);
+    let dest = base_dir.join(&relative_path);
+    let partial = dest.with_file_name(format!("{}.partial.{extension}", media.hash));
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Err(err) = fs::copy(source, &partial).and_then(|_| fs::rename(&partial, &dest)) {
+        let _ = fs::remove_file(&partial);
+        return Err(err.into());
+    }
+
+    let adopted = Media {
+        relative_path,
+        size_bytes: fs::metadata(&dest)?.len(),
+        created_at: Utc::now().to_rfc3339(),
+        ..media.clone()
+    };
+    upsert_media(conn, &adopted)?;
+    Ok(adopted)
+}
+
+// Deletes the recordings no project links to and returns them
+pub fn remove_unreferenced(conn: &Connection, base_dir: &Path) -> Result<Vec<Media>> {
+    let unused = unreferenced_media(conn)?;
+    for media in &unused {
+        match fs::remove_file(media_path(base_dir, media)) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err.into()),
+        }
+        delete_media(conn, &media.hash)?;
+    }
+    Ok(unused)
+}
+
+// Unlinks the recordings of projects older than `retention_days`, keeping their
+// transcripts and notes, then deletes every recording no project uses
+pub fn prune_media(
+    conn: &Connection,
+    base_dir: &Path,
+    retention_days: Option<u32>,
+    now: DateTime<Utc>,
+) -> Result<PruneReport> {
+    let mut report = PruneReport::default();
+    if let Some(days) = retention_days {
+        let cutoff = now - Duration::days(days.into());
+        for project in list_audio_projects(conn)? {
+            let expired = project.media_hash.is_some()
+                && project_date(&project.date).is_some_and(|date| date < cutoff);
+            if expired {
+                set_project_media(conn, &project.id, None)?;
+                report.expired_projects.push(project.id);
+            }
+        }
+    }
+    report.removed = remove_unreferenced(conn, base_dir)?;
+    report.freed_bytes = report.removed.iter().map(|media| media.size_bytes).sum();
+    Ok(report)
+}
+
+// The library writes RFC 3339 timestamps, the desktop app plain dates
+fn project_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(date)
+        .map(|date| date.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|date| date.and_utc())
+        })
+}
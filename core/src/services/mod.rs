@@ -1,4 +1,16 @@
 pub mod audio;
+pub mod backup;
+pub mod bundle;
+pub mod calendar;
+pub mod config;
+pub mod crypto;
 pub mod database;
+pub mod doctor;
 pub mod llm;
+pub mod media;
+pub mod notes;
+pub mod paths;
+pub mod redact;
+pub mod server;
+pub mod sync;
 pub mod transcribe;
@@ -0,0 +1,133 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::{
+    crypto::vault::read_note,
+    database::{
+        models::{AudioProject, NoteVersion, TokenUsage, VersionSource},
+        queries::{get_audio_project, get_project_meeting, insert_token_usage},
+        versions::{current_version, insert_version, NewNoteVersion},
+    },
+    llm::{
+        generation::GenerationParams,
+        llama_queue::{JobOptions, LlmService},
+        prompt_tasks::{run_task, with_meeting, Generated},
+    },
+    notes::kinds::NoteKind,
+    sync::markdown::{export_note, note_file_path},
+};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::{path::Path, path::PathBuf};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GeneratedNote {
+    pub project_id: String,
+    pub kind: NoteKind,
+    pub file_path: PathBuf,
+    pub version: NoteVersion,
+    pub usage: TokenUsage,
+}
+
+// Current transcript of a project, from the database or else from its transcript file
+pub fn load_transcript(
+    conn: &Connection,
+    base_dir: &Path,
+    project: &AudioProject,
+) -> Result<String> {
+    let kind = NoteKind::Transcript.as_str();
+    if let Some(version) = current_version(conn, &project.id, kind)? {
+        return Ok(version.content);
+    }
+    let path = note_file_path(base_dir, project, kind)?;
+    read_note(base_dir, &path).map_err(|e| {
+        TaunoteError::NotFound(format!(
+            "Transcript for project {} at {} ({e})",
+            project.id,
+            path.display()
+        ))
+    })
+}
+
+// Stores a transcript as the current version and writes transcript.md, `params`
+// records the transcription settings
+pub fn save_transcript(
+    conn: &mut Connection,
+    base_dir: &Path,
+    project: &AudioProject,
+    transcript: &str,
+    model: &str,
+    params: Option<String>,
+) -> Result<NoteVersion> {
+    let tx = conn.transaction()?;
+    let version = insert_version(
+        &tx,
+        NewNoteVersion {
+            project_id: project.id.clone(),
+            note_kind: NoteKind::Transcript.as_str().to_string(),
+            content: transcript.to_string(),
+            source: VersionSource::Generated,
+            template: None,
+            model: Some(model.to_string()),
+            params,
+        },
+    )?;
+    tx.commit()?;
+    export_note(conn, base_dir, project, NoteKind::Transcript.as_str())?;
+    Ok(version)
+}
+
+// Stores LLM output for a generated kind: the version, its token usage and the
+// search index are committed together, then the kind's own file is written.
+pub fn save_generated_note(
+    conn: &mut Connection,
+    base_dir: &Path,
+    project: &AudioProject,
+    kind: NoteKind,
+    generated: &Generated,
+) -> Result<GeneratedNote> {
+    if kind.generator().is_none() {
+        return Err(TaunoteError::InvalidInput(format!(
+            "{kind} notes are not generated by the LLM"
+        )));
+    }
+
+    let tx = conn.transaction()?;
+    let version = insert_version(&tx, generated.to_version(&project.id, kind.as_str()))?;
+    insert_token_usage(&tx, &project.id, kind.as_str(), &generated.usage)?;
+    tx.commit()?;
+
+    export_note(conn, base_dir, project, kind.as_str())?;
+    Ok(GeneratedNote {
+        project_id: project.id.clone(),
+        kind,
+        file_path: note_file_path(base_dir, project, kind.as_str())?,
+        version,
+        usage: generated.usage.clone(),
+    })
+}
+
+// Generates one note kind from the project's transcript, and its meeting when
+// it has one, and stores it
+pub async fn generate_note(
+    llm: &LlmService,
+    conn: &mut Connection,
+    base_dir: &Path,
+    project_id: &str,
+    kind: NoteKind,
+    overrides: &GenerationParams,
+    options: JobOptions,
+) -> Result<GeneratedNote> {
+    let spec = kind.generator().ok_or_else(|| {
+        TaunoteError::InvalidInput(format!("{kind} notes are not generated by the LLM"))
+    })?;
+    let project = get_audio_project(conn, project_id)?
+        .ok_or_else(|| TaunoteError::NotFound(format!("Project {project_id}")))?;
+    let transcript = load_transcript(conn, base_dir, &project)?;
+    let template = with_meeting(
+        spec.template,
+        get_project_meeting(conn, project_id)?.as_ref(),
+    );
+
+    let params = (spec.default_params)().with_overrides(overrides);
+    let generated = run_task(llm, &transcript, &template, params, options).await?;
+    save_generated_note(conn, base_dir, &project, kind, &generated)
+}
@@ -0,0 +1,96 @@
+use crate::services::database::models::ProjectType;
+use crate::services::llm::{
+    generation::GenerationParams,
+    prompt_tasks::{
+        email_params, lecture_notes_params, summary_params, EMAIL_TEMPLATE, LECTURE_NOTES_TEMPLATE,
+        SUMMARY_TEMPLATE,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// Every note a project can have. The transcript comes from WhisperX, the
+// other kinds are generated from it by the LLM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteKind {
+    Transcript,
+    Summary,
+    Email,
+    LectureNotes,
+}
+
+// How a generated kind is produced
+pub struct GeneratorSpec {
+    pub template: &'static str,
+    pub default_params: fn() -> GenerationParams,
+}
+
+impl NoteKind {
+    pub const ALL: [NoteKind; 4] = [
+        NoteKind::Transcript,
+        NoteKind::Summary,
+        NoteKind::Email,
+        NoteKind::LectureNotes,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoteKind::Transcript => "transcript",
+            NoteKind::Summary => "summary",
+            NoteKind::Email => "email",
+            NoteKind::LectureNotes => "lecture_notes",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+
+    // Markdown file inside the project folder, unique per kind
+    pub fn file_name(&self) -> String {
+        format!("{}.md", self.as_str())
+    }
+
+    // None for kinds that are not produced by the LLM
+    pub fn generator(&self) -> Option<GeneratorSpec> {
+        match self {
+            NoteKind::Transcript => None,
+            NoteKind::Summary => Some(GeneratorSpec {
+                template: SUMMARY_TEMPLATE,
+                default_params: summary_params,
+            }),
+            NoteKind::Email => Some(GeneratorSpec {
+                template: EMAIL_TEMPLATE,
+                default_params: email_params,
+            }),
+            NoteKind::LectureNotes => Some(GeneratorSpec {
+                template: LECTURE_NOTES_TEMPLATE,
+                default_params: lecture_notes_params,
+            }),
+        }
+    }
+
+    // Kinds generated for a new recording when none are asked for
+    pub fn defaults(project_type: ProjectType) -> &'static [NoteKind] {
+        match project_type {
+            ProjectType::Meeting => &[NoteKind::Summary, NoteKind::Email],
+            ProjectType::Lecture => &[NoteKind::LectureNotes],
+            ProjectType::Interview | ProjectType::VoiceMemo | ProjectType::Other => {
+                &[NoteKind::Summary]
+            }
+        }
+    }
+
+    pub fn generated() -> impl Iterator<Item = NoteKind> {
+        Self::ALL
+            .into_iter()
+            .filter(|kind| kind.generator().is_some())
+    }
+}
+
+impl fmt::Display for NoteKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
@@ -0,0 +1,32 @@
+use crate::error::{Result, TaunoteError};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+// `relative` below `root`, for paths read from the database or a bundle.
+// Only plain names separated by / are accepted: no root, drive, .. or \, and a
+// path that exists must not lead out of `root` through a symlink either.
+pub fn inside(root: &Path, relative: &str) -> Result<PathBuf> {
+    let escapes = || {
+        TaunoteError::InvalidInput(format!(
+            "\"{relative}\" is not a path inside {}",
+            root.display()
+        ))
+    };
+    let plain = !relative.is_empty()
+        && !relative.contains(['\\', '\0'])
+        && Path::new(relative)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+    if !plain {
+        return Err(escapes());
+    }
+    let path = root.join(relative);
+    if let (Ok(resolved), Ok(root)) = (fs::canonicalize(&path), fs::canonicalize(root)) {
+        if !resolved.starts_with(root) {
+            return Err(escapes());
+        }
+    }
+    Ok(path)
+}
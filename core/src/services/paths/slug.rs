@@ -0,0 +1,101 @@
+use unicode_normalization::UnicodeNormalization;
+
+// Slugs are folder names made from user input. They are at most this many
+// bytes, far below the 255 of common filesystems so the paths below them fit.
+pub const MAX_SLUG_BYTES: usize = 48;
+// File names made from names, without their extension
+pub const MAX_FILE_NAME_BYTES: usize = 120;
+
+// Characters Windows does not allow in file names, / and \ being separators
+const FORBIDDEN: &str = r#"/\:*?"<>|"#;
+
+// Names Windows reserves for devices, whatever the extension
+const RESERVED: [&str; 22] = [
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
+// Lowercase letters and digits of any script with single dashes between words,
+// e.g. "Clients: Acme GmbH" is clients-acme-gmbh. Compatibility forms are
+// folded first, so ﬁ and fi or full width letters give the same slug, and
+// lowercase keeps names apart on case insensitive filesystems.
+pub fn slug(name: &str, fallback: &str) -> String {
+    let mut slug = String::new();
+    for c in name.nfkc().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() || c == '_' {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = truncate(&slug, MAX_SLUG_BYTES).trim_end_matches('-');
+    if slug.is_empty() {
+        return fallback.to_string();
+    }
+    unreserved(slug)
+}
+
+// `base`, else base-2, base-3, ... whichever `taken` says is free first.
+// Suffixes shorten the base so the result stays within MAX_SLUG_BYTES.
+pub fn unique_slug(base: &str, mut taken: impl FnMut(&str) -> bool) -> String {
+    if !taken(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n: u64| {
+            let suffix = format!("-{n}");
+            let base = truncate(base, MAX_SLUG_BYTES - suffix.len()).trim_end_matches('-');
+            format!("{base}{suffix}")
+        })
+        .find(|candidate| !taken(candidate))
+        .expect("an unused slug")
+}
+
+// A name as a file name that is valid everywhere: as written, composed into
+// NFC, without separators or characters Windows forbids, without leading or
+// trailing dots and spaces and at most MAX_FILE_NAME_BYTES long
+pub fn file_name(name: &str, fallback: &str) -> String {
+    let name: String = name
+        .nfc()
+        .map(|c| {
+            if c.is_control() || FORBIDDEN.contains(c) {
+                '-'
+            } else {
+                c
+            }
+        })
+        .collect();
+    let trim = |name: &str| {
+        name.trim_matches(|c: char| c == '.' || c.is_whitespace())
+            .to_string()
+    };
+    let name = trim(truncate(&trim(&name), MAX_FILE_NAME_BYTES));
+    if name.is_empty() {
+        return fallback.to_string();
+    }
+    unreserved(&name)
+}
+
+// Longest prefix of `value` of at most `max` bytes ending on a character
+fn truncate(value: &str, max: usize) -> &str {
+    let mut end = value.len().min(max);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+// CON, nul.txt and the like get an underscore after their stem
+fn unreserved(name: &str) -> String {
+    let (stem, extension) = match name.split_once('.') {
+        Some((stem, extension)) => (stem, Some(extension)),
+        None => (name, None),
+    };
+    if !RESERVED.contains(&stem.trim_end().to_lowercase().as_str()) {
+        return name.to_string();
+    }
+    match extension {
+        Some(extension) => format!("{stem}_.{extension}"),
+        None => format!("{stem}_"),
+    }
+}
@@ -0,0 +1,172 @@
+use crate::services::database::models::EntityKind;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b[a-z0-9._%+-]+@[a-z0-9-]+(?:\.[a-z0-9-]+)*\.[a-z]{2,}\b").unwrap()
+});
+static IBAN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]{4}){2,7}(?: ?[A-Z0-9]{1,3})?\b").unwrap()
+});
+static CREDIT_CARD: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap());
+static PHONE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{1,4}\)[ .-]?)?\d{2,4}(?:[ .-]?\d{2,4}){1,4}\b")
+        .unwrap()
+});
+
+// Which detectors run, the rule-based ones are all on by default
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactionRules {
+    pub emails: bool,
+    pub phones: bool,
+    pub ibans: bool,
+    pub credit_cards: bool,
+    // redacted wherever they appear as whole words, ignoring case
+    pub words: Vec<String>,
+}
+
+impl Default for RedactionRules {
+    fn default() -> Self {
+        Self {
+            emails: true,
+            phones: true,
+            ibans: true,
+            credit_cards: true,
+            words: Vec::new(),
+        }
+    }
+}
+
+// Byte range of `text` to replace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection {
+    pub entity: EntityKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+// Everything the rules and the `names` found in `text`, in order and without
+// overlaps. Where two detections overlap the longer one wins.
+pub fn detect(text: &str, rules: &RedactionRules, names: &[String]) -> Vec<Detection> {
+    let mut found = Vec::new();
+    let mut find = |entity: EntityKind, regex: &Regex, valid: fn(&str) -> bool| {
+        for m in regex.find_iter(text) {
+            // not the tail of a longer word or number, e.g. an order id
+            let inside_word = text[..m.start()]
+                .chars()
+                .next_back()
+                .is_some_and(char::is_alphanumeric);
+            if !inside_word && valid(m.as_str()) {
+                found.push(Detection {
+                    entity,
+                    start: m.start(),
+                    end: m.end(),
+                });
+            }
+        }
+    };
+    if rules.emails {
+        find(EntityKind::Email, &EMAIL, |_| true);
+    }
+    if rules.ibans {
+        find(EntityKind::Iban, &IBAN, valid_iban);
+    }
+    if rules.credit_cards {
+        find(EntityKind::CreditCard, &CREDIT_CARD, valid_card);
+    }
+    if rules.phones {
+        find(EntityKind::Phone, &PHONE, valid_phone);
+    }
+    if let Some(regex) = words_regex(names) {
+        find(EntityKind::Name, &regex, |_| true);
+    }
+    if let Some(regex) = words_regex(&rules.words) {
+        find(EntityKind::Term, &regex, |_| true);
+    }
+
+    // stable sort, so for the same span the detector that ran first wins
+    found.sort_by_key(|d| (d.start, std::cmp::Reverse(d.end)));
+    let mut kept: Vec<Detection> = Vec::with_capacity(found.len());
+    for detection in found {
+        match kept.last() {
+            Some(last) if detection.start < last.end => {}
+            _ => kept.push(detection),
+        }
+    }
+    kept
+}
+
+// One case-insensitive alternation of whole words, longest first so
+// "Anna Maria" is preferred over "Anna"
+fn words_regex(words: &[String]) -> Option<Regex> {
+    let mut words: Vec<&str> = words
+        .iter()
+        .map(|word| word.trim())
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.is_empty() {
+        return None;
+    }
+    words.sort_by_key(|word| std::cmp::Reverse(word.len()));
+    let alternation: Vec<String> = words.into_iter().map(regex::escape).collect();
+    RegexBuilder::new(&format!(r"\b(?:{})\b", alternation.join("|")))
+        .case_insensitive(true)
+        .build()
+        .ok()
+}
+
+fn digits(value: &str) -> Vec<u32> {
+    value.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+fn valid_card(value: &str) -> bool {
+    let digits = digits(value);
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    // Luhn checksum
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &digit)| match (i % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn valid_iban(value: &str) -> bool {
+    let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    if !(15..=34).contains(&compact.len()) {
+        return false;
+    }
+    // ISO 13616: the country code and check digits move to the end, letters
+    // count as 10..35 and the number must be 1 modulo 97
+    let rearranged = compact[4..].chars().chain(compact[..4].chars());
+    let mut remainder = 0u32;
+    for c in rearranged {
+        let Some(value) = c.to_digit(36) else {
+            return false;
+        };
+        remainder = if value >= 10 {
+            (remainder * 100 + value) % 97
+        } else {
+            (remainder * 10 + value) % 97
+        };
+    }
+    remainder == 1
+}
+
+// Dates such as 2025-01-31 have eight digits, so numbers written without a
+// country code or area code in brackets need nine
+fn valid_phone(value: &str) -> bool {
+    let count = digits(value).len();
+    let prefixed = value.starts_with('+') || value.contains('(');
+    count <= 15 && (count >= 9 || (prefixed && count >= 7))
+}
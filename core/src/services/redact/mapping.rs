@@ -0,0 +1,121 @@
+use crate::services::{
+    database::models::{EntityKind, Redaction},
+    redact::detect::Detection,
+};
+use regex::Regex;
+use std::{collections::HashMap, sync::LazyLock};
+
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[(?:EMAIL|PHONE|IBAN|CARD|TERM|NAME)_\d+\]").unwrap());
+
+// Placeholders of one project. The same value gets the same placeholder in every
+// note and on every run, so redacted notes can still be read side by side.
+#[derive(Debug, Clone, Default)]
+pub struct RedactionMap {
+    entries: Vec<Redaction>,
+    // entries before this index were loaded, the rest are new
+    loaded: usize,
+    by_value: HashMap<(EntityKind, String), usize>,
+    by_placeholder: HashMap<String, usize>,
+    counts: HashMap<EntityKind, u32>,
+}
+
+impl RedactionMap {
+    pub fn new(existing: Vec<Redaction>) -> Self {
+        let mut map = Self::default();
+        for redaction in existing {
+            map.push(redaction);
+        }
+        map.loaded = map.entries.len();
+        map
+    }
+
+    pub fn entries(&self) -> &[Redaction] {
+        &self.entries
+    }
+
+    // Placeholders handed out since the map was loaded, to be stored
+    pub fn added(&self) -> &[Redaction] {
+        &self.entries[self.loaded..]
+    }
+
+    pub fn placeholder(&mut self, entity: EntityKind, original: &str) -> String {
+        if let Some(&index) = self.by_value.get(&(entity, normalize(entity, original))) {
+            return self.entries[index].placeholder.clone();
+        }
+        let count = self.counts.get(&entity).copied().unwrap_or(0) + 1;
+        let placeholder = format!("[{}_{count}]", entity.label());
+        self.push(Redaction {
+            placeholder: placeholder.clone(),
+            entity,
+            original: original.to_string(),
+        });
+        placeholder
+    }
+
+    // Replaces every detection, which must not overlap, with its placeholder
+    pub fn redact(&mut self, text: &str, detections: &[Detection]) -> String {
+        let mut redacted = String::with_capacity(text.len());
+        let mut last = 0;
+        for detection in detections {
+            redacted.push_str(&text[last..detection.start]);
+            redacted.push_str(
+                &self.placeholder(detection.entity, &text[detection.start..detection.end]),
+            );
+            last = detection.end;
+        }
+        redacted.push_str(&text[last..]);
+        redacted
+    }
+
+    // Puts the original values back, placeholders the map does not know are kept
+    pub fn restore(&self, text: &str) -> String {
+        PLACEHOLDER
+            .replace_all(text, |caps: &regex::Captures| {
+                self.by_placeholder
+                    .get(&caps[0])
+                    .map(|&index| self.entries[index].original.clone())
+                    .unwrap_or_else(|| caps[0].to_string())
+            })
+            .into_owned()
+    }
+
+    fn push(&mut self, redaction: Redaction) {
+        let index = self.entries.len();
+        let number = redaction
+            .placeholder
+            .trim_end_matches(']')
+            .rsplit('_')
+            .next()
+            .and_then(|number| number.parse().ok())
+            .unwrap_or(0);
+        let count = self.counts.entry(redaction.entity).or_insert(0);
+        *count = (*count).max(number);
+        self.by_value
+            .entry((
+                redaction.entity,
+                normalize(redaction.entity, &redaction.original),
+            ))
+            .or_insert(index);
+        self.by_placeholder
+            .insert(redaction.placeholder.clone(), index);
+        self.entries.push(redaction);
+    }
+}
+
+// Spelling differences that still mean the same value, e.g. "+1 555-0100" and
+// "+15550100" or "Alice" and "alice"
+fn normalize(entity: EntityKind, value: &str) -> String {
+    match entity {
+        EntityKind::Phone | EntityKind::Iban | EntityKind::CreditCard => value
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_uppercase())
+            .collect(),
+        _ => value
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase(),
+    }
+}
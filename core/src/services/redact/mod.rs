@@ -0,0 +1,3 @@
+pub mod detect;
+pub mod mapping;
+pub mod names;
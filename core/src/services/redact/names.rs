@@ -0,0 +1,54 @@
+use crate::error::Result;
+use crate::services::llm::{
+    budget::{chunk_transcript, TRANSCRIPT_PLACEHOLDER},
+    llama_queue::{JobOptions, LlmService},
+    prompt_tasks::{names_params, NAMES_TEMPLATE},
+};
+
+// People named in `text` according to the LLM. The text is split so that all of
+// it is read, and only names that really occur in it are kept.
+pub async fn detect_names(
+    llm: &LlmService,
+    text: &str,
+    options: JobOptions,
+) -> Result<Vec<String>> {
+    let params = names_params();
+    let n_predict = params.max_tokens.unwrap_or_default();
+    let mut names = Vec::new();
+    for chunk in chunk_transcript(llm, NAMES_TEMPLATE, text, n_predict).await? {
+        let prompt = NAMES_TEMPLATE.replace(TRANSCRIPT_PLACEHOLDER, &chunk);
+        let completion = llm
+            .complete(prompt, params.clone(), options.clone())
+            .await?;
+        for name in parse_names(&completion.text, &chunk) {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    Ok(names)
+}
+
+// Names from the model's answer: list markers and quotes are dropped, anything
+// that does not occur in `text` is taken as made up
+pub fn parse_names(output: &str, text: &str) -> Vec<String> {
+    let haystack = text.to_lowercase();
+    let mut names: Vec<String> = Vec::new();
+    for line in output.lines() {
+        let name = line
+            .trim()
+            .trim_start_matches(|c: char| c.is_ascii_digit() || "-*•.) ".contains(c))
+            .trim_matches(|c: char| "\"'`,;.".contains(c))
+            .trim();
+        let plausible = name.chars().count() >= 2
+            && name.split_whitespace().count() <= 4
+            && !name.eq_ignore_ascii_case("none")
+            // whisperx speaker labels are not names
+            && !name.to_uppercase().starts_with("SPEAKER")
+            && haystack.contains(&name.to_lowercase());
+        if plausible && !names.iter().any(|known| known.eq_ignore_ascii_case(name)) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
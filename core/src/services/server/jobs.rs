@@ -0,0 +1,132 @@
+use crate::error::Result;
+use crate::taunote::Progress;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::watch;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+// A long running API request, the client polls it or follows its events
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: String,
+    pub label: String,
+    pub state: JobState,
+    pub progress: Option<Progress>,
+    // the handler's output once it succeeded, the serialized TaunoteError once it failed
+    pub result: Option<serde_json::Value>,
+    pub error: Option<serde_json::Value>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        self.state != JobState::Running
+    }
+}
+
+// Lets a job publish the step it is on
+#[derive(Clone)]
+pub struct ProgressSink(watch::Sender<JobStatus>);
+
+impl ProgressSink {
+    pub fn report(&self, progress: Progress) {
+        self.0.send_modify(|status| {
+            status.progress = Some(progress);
+            status.updated_at = chrono::Utc::now().to_rfc3339();
+        });
+    }
+}
+
+// Jobs started by the API server, kept in memory until the server stops
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<HashMap<String, watch::Sender<JobStatus>>>>,
+}
+
+impl JobRegistry {
+    // Spawns `run` on the runtime and returns its initial status
+    pub fn start<F, Fut, T>(&self, label: &str, run: F) -> JobStatus
+    where
+        F: FnOnce(ProgressSink) -> Fut,
+        Fut: Future<Output = Result<T>> + Send + 'static,
+        T: Serialize,
+    {
+        let now = chrono::Utc::now().to_rfc3339();
+        let status = JobStatus {
+            id: Uuid::new_v4().to_string(),
+            label: label.to_string(),
+            state: JobState::Running,
+            progress: None,
+            result: None,
+            error: None,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+        let (sender, _) = watch::channel(status.clone());
+        self.jobs
+            .lock()
+            .unwrap()
+            .insert(status.id.clone(), sender.clone());
+
+        let job = run(ProgressSink(sender.clone()));
+        tokio::spawn(async move {
+            let outcome = job.await.and_then(|value| Ok(serde_json::to_value(value)?));
+            sender.send_modify(|status| {
+                match outcome {
+                    Ok(value) => {
+                        status.state = JobState::Succeeded;
+                        status.result = Some(value);
+                    }
+                    Err(err) => {
+                        status.state = JobState::Failed;
+                        status.error = serde_json::to_value(&err).ok();
+                    }
+                }
+                status.updated_at = chrono::Utc::now().to_rfc3339();
+            });
+        });
+        status
+    }
+
+    pub fn get(&self, id: &str) -> Option<JobStatus> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|sender| sender.borrow().clone())
+    }
+
+    // Newest first
+    pub fn list(&self) -> Vec<JobStatus> {
+        let mut jobs: Vec<JobStatus> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(|sender| sender.borrow().clone())
+            .collect();
+        jobs.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        jobs
+    }
+
+    pub fn subscribe(&self, id: &str) -> Option<watch::Receiver<JobStatus>> {
+        self.jobs
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|sender| sender.subscribe())
+    }
+}
@@ -0,0 +1,399 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::{
+    database::models::{
+        AudioProject, NoteVersion, ProjectGroup, ProjectMetadata, ProjectType, SearchFilter,
+        SearchHit,
+    },
+    llm::{generation::GenerationParams, llama_queue::JobOptions, llama_server::ServerStatus},
+    notes::kinds::NoteKind,
+    server::jobs::{JobRegistry, JobStatus},
+};
+use crate::taunote::{
+    NewProject, ProcessOptions, Progress, RedactOptions, ReprocessOptions, Taunote,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use futures_util::stream::{self, Stream};
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, net::SocketAddr, path::PathBuf};
+
+const OPENAPI: &str = include_str!("../../assets/openapi.json");
+
+#[derive(Clone)]
+pub struct AppState {
+    pub taunote: Taunote,
+    pub jobs: JobRegistry,
+}
+
+impl IntoResponse for TaunoteError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            TaunoteError::NotFound(_) => StatusCode::NOT_FOUND,
+            TaunoteError::Locked(_) => StatusCode::LOCKED,
+            TaunoteError::InvalidInput(_) | TaunoteError::Config(_) => StatusCode::BAD_REQUEST,
+            TaunoteError::MissingDependency { .. } | TaunoteError::LlmUnavailable(_) => {
+                StatusCode::SERVICE_UNAVAILABLE
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(self)).into_response()
+    }
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/health", get(health))
+        .route("/openapi.json", get(openapi))
+        .route("/llm/status", get(llm_status))
+        .route("/groups", get(list_groups))
+        .route("/projects", get(list_projects).post(create_project))
+        .route(
+            "/projects/{id}",
+            get(show_project)
+                .patch(update_project)
+                .delete(delete_project),
+        )
+        .route(
+            "/projects/{id}/metadata",
+            get(get_metadata).put(set_metadata),
+        )
+        .route("/projects/{id}/transcribe", post(transcribe))
+        .route("/projects/{id}/notes/{kind}", post(generate_note))
+        .route("/projects/{id}/reprocess", post(reprocess))
+        .route("/projects/{id}/redact", post(redact))
+        .route("/projects/{id}/unredact", post(unredact))
+        .route("/recordings", post(process_recording))
+        .route("/search", get(search))
+        .route("/jobs", get(list_jobs))
+        .route("/jobs/{id}", get(get_job))
+        .route("/jobs/{id}/events", get(job_events))
+        .with_state(state)
+}
+
+// Serves until ctrl-c, then stops llama-server
+pub async fn serve(taunote: Taunote, addr: SocketAddr) -> Result<()> {
+    let state = AppState {
+        taunote: taunote.clone(),
+        jobs: JobRegistry::default(),
+    };
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    println!("Listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await?;
+    taunote.shutdown().await;
+    Ok(())
+}
+
+async fn health() -> &'static str {
+    "ok"
+}
+
+async fn openapi() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI)
+}
+
+async fn llm_status(State(state): State<AppState>) -> Json<ServerStatus> {
+    Json(state.taunote.llm().status())
+}
+
+async fn list_groups(State(state): State<AppState>) -> Result<Json<Vec<ProjectGroup>>> {
+    Ok(Json(state.taunote.list_groups()?))
+}
+
+#[derive(Deserialize)]
+struct ProjectFilter {
+    group: Option<String>,
+}
+
+async fn list_projects(
+    State(state): State<AppState>,
+    Query(filter): Query<ProjectFilter>,
+) -> Result<Json<Vec<AudioProject>>> {
+    Ok(Json(state.taunote.list_projects(filter.group.as_deref())?))
+}
+
+async fn create_project(
+    State(state): State<AppState>,
+    Json(new): Json<NewProject>,
+) -> Result<(StatusCode, Json<AudioProject>)> {
+    Ok((
+        StatusCode::CREATED,
+        Json(state.taunote.create_project(new)?),
+    ))
+}
+
+#[derive(Serialize)]
+struct ProjectDetails {
+    project: AudioProject,
+    notes: Vec<NoteVersion>,
+}
+
+async fn show_project(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ProjectDetails>> {
+    Ok(Json(ProjectDetails {
+        project: state.taunote.project(&id)?,
+        notes: state.taunote.notes(&id)?,
+    }))
+}
+
+#[derive(Deserialize)]
+struct ProjectUpdate {
+    name: Option<String>,
+    group_id: Option<String>,
+    project_type: Option<ProjectType>,
+}
+
+async fn update_project(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(update): Json<ProjectUpdate>,
+) -> Result<Json<AudioProject>> {
+    if let Some(name) = &update.name {
+        state.taunote.rename_project(&id, name)?;
+    }
+    if let Some(group_id) = &update.group_id {
+        state.taunote.move_project(&id, group_id)?;
+    }
+    if let Some(project_type) = update.project_type {
+        state.taunote.set_project_type(&id, project_type)?;
+    }
+    Ok(Json(state.taunote.project(&id)?))
+}
+
+async fn get_metadata(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<ProjectMetadata>> {
+    Ok(Json(state.taunote.project_metadata(&id)?))
+}
+
+async fn set_metadata(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(metadata): Json<ProjectMetadata>,
+) -> Result<Json<ProjectMetadata>> {
+    Ok(Json(state.taunote.set_project_metadata(&id, metadata)?))
+}
+
+async fn delete_project(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode> {
+    state.taunote.delete_project(&id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct TranscribeRequest {
+    // path on the machine running the server
+    audio_path: PathBuf,
+    language: Option<String>,
+}
+
+async fn transcribe(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<TranscribeRequest>,
+) -> Result<(StatusCode, Json<JobStatus>)> {
+    state.taunote.project(&id)?;
+    let taunote = state.taunote.clone();
+    let job = state.jobs.start("transcribe", |progress| async move {
+        progress.report(Progress::Transcribing);
+        taunote
+            .transcribe_blocking(&id, &request.audio_path, request.language.as_deref())
+            .await
+    });
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct GenerateRequest {
+    params: GenerationParams,
+}
+
+async fn generate_note(
+    State(state): State<AppState>,
+    Path((id, kind)): Path<(String, String)>,
+    request: Option<Json<GenerateRequest>>,
+) -> Result<(StatusCode, Json<JobStatus>)> {
+    let kind = NoteKind::parse(&kind)
+        .ok_or_else(|| TaunoteError::InvalidInput(format!("Unknown note kind {kind}")))?;
+    state.taunote.project(&id)?;
+    let params = request
+        .map(|Json(request)| request.params)
+        .unwrap_or_default();
+    let taunote = state.taunote.clone();
+    let job = state.jobs.start(kind.as_str(), |progress| async move {
+        progress.report(Progress::Generating { kind });
+        taunote
+            .generate_note(&id, kind, &params, JobOptions::interactive(kind.as_str()))
+            .await
+    });
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+async fn reprocess(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    options: Option<Json<ReprocessOptions>>,
+) -> Result<(StatusCode, Json<JobStatus>)> {
+    state.taunote.project(&id)?;
+    let options = options.map(|Json(options)| options).unwrap_or_default();
+    let taunote = state.taunote.clone();
+    let job = state.jobs.start("reprocess", |progress| async move {
+        taunote
+            .reprocess_with_progress(&id, &options, |step| progress.report(step))
+            .await
+    });
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[derive(Deserialize)]
+struct ProcessRequest {
+    audio_path: PathBuf,
+    project: NewProject,
+    #[serde(default)]
+    options: ProcessOptions,
+}
+
+async fn process_recording(
+    State(state): State<AppState>,
+    Json(request): Json<ProcessRequest>,
+) -> (StatusCode, Json<JobStatus>) {
+    let taunote = state.taunote.clone();
+    let job = state.jobs.start("process", |progress| async move {
+        taunote
+            .process_recording_with_progress(
+                &request.audio_path,
+                request.project,
+                &request.options,
+                |step| progress.report(step),
+            )
+            .await
+    });
+    (StatusCode::ACCEPTED, Json(job))
+}
+
+// A job because the LLM name detector can take a while
+async fn redact(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    options: Option<Json<RedactOptions>>,
+) -> Result<(StatusCode, Json<JobStatus>)> {
+    state.taunote.project(&id)?;
+    let options = options.map(|Json(options)| options).unwrap_or_default();
+    let taunote = state.taunote.clone();
+    let job = state.jobs.start(
+        "redact",
+        |_| async move { taunote.redact(&id, &options).await },
+    );
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+#[derive(Deserialize, Serialize)]
+struct Text {
+    text: String,
+}
+
+async fn unredact(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<Text>,
+) -> Result<Json<Text>> {
+    Ok(Json(Text {
+        text: state.taunote.unredact(&id, &request.text)?,
+    }))
+}
+
+#[derive(Deserialize)]
+struct SearchQuery {
+    #[serde(default)]
+    q: String,
+    limit: Option<usize>,
+    group: Option<String>,
+    #[serde(rename = "type")]
+    project_type: Option<ProjectType>,
+    // comma separated, query strings have no lists
+    tags: Option<String>,
+    participant: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+async fn search(
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchHit>>> {
+    let filter = SearchFilter {
+        group_id: query.group,
+        project_type: query.project_type,
+        tags: query
+            .tags
+            .iter()
+            .flat_map(|tags| tags.split(','))
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+        participant: query.participant,
+        from: query.from,
+        to: query.to,
+    };
+    Ok(Json(state.taunote.search_filtered(
+        &query.q,
+        &filter,
+        query.limit.unwrap_or(20),
+    )?))
+}
+
+async fn list_jobs(State(state): State<AppState>) -> Json<Vec<JobStatus>> {
+    Json(state.jobs.list())
+}
+
+async fn get_job(State(state): State<AppState>, Path(id): Path<String>) -> Result<Json<JobStatus>> {
+    state
+        .jobs
+        .get(&id)
+        .map(Json)
+        .ok_or_else(|| TaunoteError::NotFound(format!("Job {id}")))
+}
+
+// Sends the job status now and on every change, the stream ends once the job finished
+async fn job_events(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Sse<impl Stream<Item = std::result::Result<Event, Infallible>>>> {
+    let receiver = state
+        .jobs
+        .subscribe(&id)
+        .ok_or_else(|| TaunoteError::NotFound(format!("Job {id}")))?;
+
+    let events = stream::unfold(Some((receiver, true)), |next| async move {
+        let (mut receiver, first) = next?;
+        if !first && receiver.changed().await.is_err() {
+            return None;
+        }
+        let status = receiver.borrow_and_update().clone();
+        let event = Event::default()
+            .event("status")
+            .json_data(&status)
+            .unwrap_or_else(|_| Event::default().event("status"));
+        let next = (!status.is_finished()).then_some((receiver, false));
+        Some((Ok(event), next))
+    });
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,304 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::{
+    crypto::vault::{note_extension, read_note, write_note},
+    database::{
+        models::{AudioProject, NoteVersion},
+        queries::{get_audio_project, list_audio_projects},
+        versions::{current_version, record_user_edit},
+    },
+    notes::kinds::NoteKind,
+    paths::root::inside,
+};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NoteRef {
+    pub project_id: String,
+    pub note_kind: String,
+}
+
+// Both the markdown file and the database changed since the last sync
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncConflict {
+    pub project_id: String,
+    pub note_kind: String,
+    pub file_path: PathBuf,
+    pub db_version_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SyncReport {
+    // file edits stored as new versions
+    pub imported: Vec<NoteRef>,
+    // database versions written to their files
+    pub exported: Vec<NoteRef>,
+    pub conflicts: Vec<SyncConflict>,
+    pub unchanged: usize,
+}
+
+impl SyncReport {
+    fn merge(&mut self, other: SyncReport) {
+        self.imported.extend(other.imported);
+        self.exported.extend(other.exported);
+        self.conflicts.extend(other.conflicts);
+        self.unchanged += other.unchanged;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    KeepFile,
+    KeepDatabase,
+}
+
+// What note_files remembers about the last reconciled state
+struct SyncRecord {
+    synced_version_id: Option<String>,
+    content_hash: String,
+    mtime_ms: i64,
+}
+
+struct FileState {
+    content: String,
+    hash: String,
+    mtime_ms: i64,
+}
+
+// Fails for a relative_path that leads out of the data directory
+pub fn project_dir(base_dir: &Path, project: &AudioProject) -> Result<PathBuf> {
+    inside(base_dir, &project.relative_path)
+}
+
+// {kind}.md, or {kind}.md.enc in an encrypted data directory
+pub fn note_file_path(base_dir: &Path, project: &AudioProject, note_kind: &str) -> Result<PathBuf> {
+    Ok(project_dir(base_dir, project)?.join(format!("{note_kind}.{}", note_extension(base_dir))))
+}
+
+fn hash_content(content: &str) -> String {
+    hex::encode(Sha256::digest(content.as_bytes()))
+}
+
+fn mtime_ms(path: &Path) -> Result<i64> {
+    let modified = fs::metadata(path)?.modified()?;
+    let since_epoch = modified
+        .duration_since(UNIX_EPOCH)
+        .map_err(|e| TaunoteError::Internal(format!("File time before 1970: {e}")))?;
+    Ok(since_epoch.as_millis() as i64)
+}
+
+fn read_file_state(base_dir: &Path, path: &Path) -> Result<Option<FileState>> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let content = read_note(base_dir, path)?;
+    Ok(Some(FileState {
+        hash: hash_content(&content),
+        mtime_ms: mtime_ms(path)?,
+        content,
+    }))
+}
+
+fn get_record(conn: &Connection, project_id: &str, note_kind: &str) -> Result<Option<SyncRecord>> {
+    Ok(conn
+        .query_row(
+            "SELECT synced_version_id, content_hash, mtime_ms FROM note_files
+             WHERE project_id = ?1 AND note_kind = ?2",
+            params![project_id, note_kind],
+            |row| {
+                Ok(SyncRecord {
+                    synced_version_id: row.get(0)?,
+                    content_hash: row.get(1)?,
+                    mtime_ms: row.get(2)?,
+                })
+            },
+        )
+        .optional()?)
+}
+
+fn save_record(
+    conn: &Connection,
+    project_id: &str,
+    note_kind: &str,
+    version: Option<&NoteVersion>,
+    path: &Path,
+    content: &str,
+) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO note_files (
+            project_id, note_kind, synced_version_id, content_hash, mtime_ms, synced_at
+         ) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            project_id,
+            note_kind,
+            version.map(|v| v.id.as_str()),
+            hash_content(content),
+            mtime_ms(path)?,
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    Ok(())
+}
+
+// Writes the current database version of a note to its markdown file
+pub fn export_note(
+    conn: &Connection,
+    base_dir: &Path,
+    project: &AudioProject,
+    note_kind: &str,
+) -> Result<()> {
+    let version = current_version(conn, &project.id, note_kind)?
+        .ok_or_else(|| TaunoteError::NotFound(format!("{note_kind} for project {}", project.id)))?;
+    let path = note_file_path(base_dir, project, note_kind)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_note(base_dir, &path, &version.content)?;
+    save_record(
+        conn,
+        &project.id,
+        note_kind,
+        Some(&version),
+        &path,
+        &version.content,
+    )
+}
+
+// Stores the markdown file as a user edit of the note
+fn import_note(
+    conn: &mut Connection,
+    project: &AudioProject,
+    note_kind: &str,
+    path: &Path,
+    content: &str,
+) -> Result<()> {
+    let version = record_user_edit(conn, &project.id, note_kind, content)?;
+    save_record(conn, &project.id, note_kind, Some(&version), path, content)
+}
+
+// Note kinds known for a project: those with versions or a synced file, plus
+// files of registered kinds. Any other *.md in the folder is not a note.
+fn note_kinds(conn: &Connection, base_dir: &Path, project: &AudioProject) -> Result<BTreeSet<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT note_kind FROM note_versions WHERE project_id = ?1
+         UNION SELECT note_kind FROM note_files WHERE project_id = ?1",
+    )?;
+    let mut kinds = stmt
+        .query_map(params![project.id], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<BTreeSet<_>>>()?;
+
+    for kind in NoteKind::ALL {
+        if note_file_path(base_dir, project, kind.as_str())?.is_file() {
+            kinds.insert(kind.as_str().to_string());
+        }
+    }
+    Ok(kinds)
+}
+
+fn sync_note(
+    conn: &mut Connection,
+    base_dir: &Path,
+    project: &AudioProject,
+    note_kind: &str,
+    report: &mut SyncReport,
+) -> Result<()> {
+    let path = note_file_path(base_dir, project, note_kind)?;
+    let note = NoteRef {
+        project_id: project.id.clone(),
+        note_kind: note_kind.to_string(),
+    };
+    let file = read_file_state(base_dir, &path)?;
+    let db = current_version(conn, &project.id, note_kind)?;
+    let record = get_record(conn, &project.id, note_kind)?;
+
+    // mtime is the cheap check, the hash confirms the content really changed
+    let file_changed = match (&file, &record) {
+        (Some(file), Some(record)) => {
+            file.mtime_ms != record.mtime_ms && file.hash != record.content_hash
+        }
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
+    let db_changed = match (&db, &record) {
+        (Some(db), Some(record)) => record.synced_version_id.as_deref() != Some(db.id.as_str()),
+        (Some(_), None) => true,
+        (None, _) => false,
+    };
+
+    match (file, db) {
+        (Some(file), Some(db)) if file.content == db.content => {
+            if file_changed || db_changed {
+                save_record(conn, &project.id, note_kind, Some(&db), &path, &file.content)?;
+            }
+            report.unchanged += 1;
+        }
+        (Some(_), Some(db)) if file_changed && db_changed => {
+            report.conflicts.push(SyncConflict {
+                project_id: project.id.clone(),
+                note_kind: note_kind.to_string(),
+                file_path: path,
+                db_version_id: Some(db.id),
+            });
+        }
+        (Some(file), _) if file_changed => {
+            import_note(conn, project, note_kind, &path, &file.content)?;
+            report.imported.push(note);
+        }
+        // a deleted file is written again from the database
+        (None, Some(_)) => {
+            export_note(conn, base_dir, project, note_kind)?;
+            report.exported.push(note);
+        }
+        (Some(_), Some(_)) if db_changed => {
+            export_note(conn, base_dir, project, note_kind)?;
+            report.exported.push(note);
+        }
+        _ => report.unchanged += 1,
+    }
+    Ok(())
+}
+
+// Reconciles every note of a project between its markdown files and the database
+pub fn sync_project(conn: &mut Connection, base_dir: &Path, project: &AudioProject) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    for note_kind in note_kinds(conn, base_dir, project)? {
+        sync_note(conn, base_dir, project, &note_kind, &mut report)?;
+    }
+    Ok(report)
+}
+
+pub fn sync_all(conn: &mut Connection, base_dir: &Path) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    for project in list_audio_projects(conn)? {
+        report.merge(sync_project(conn, base_dir, &project)?);
+    }
+    Ok(report)
+}
+
+// Settles a conflict by keeping one side and overwriting the other
+pub fn resolve_conflict(
+    conn: &mut Connection,
+    base_dir: &Path,
+    project_id: &str,
+    note_kind: &str,
+    resolution: Resolution,
+) -> Result<()> {
+    let project = get_audio_project(conn, project_id)?
+        .ok_or_else(|| TaunoteError::NotFound(format!("Project {project_id}")))?;
+    match resolution {
+        Resolution::KeepDatabase => export_note(conn, base_dir, &project, note_kind),
+        Resolution::KeepFile => {
+            let path = note_file_path(base_dir, &project, note_kind)?;
+            let content = read_note(base_dir, &path)?;
+            import_note(conn, &project, note_kind, &path, &content)
+        }
+    }
+}
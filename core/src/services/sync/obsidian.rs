@@ -0,0 +1,535 @@
+use crate::error::Result;
+use crate::services::{
+    database::{
+        models::{AudioProject, ProjectGroup, ProjectMetadata, ProjectNotes},
+        queries::{
+            get_project_metadata, get_project_notes, list_audio_projects, list_project_groups,
+        },
+    },
+    paths::slug,
+    sync::markdown::project_dir,
+    transcribe::whisperx::segments_duration,
+};
+use regex::Regex;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+// Only the text between the markers is rewritten, what the user writes around
+// it in the vault is kept
+const BEGIN: &str = "<!-- taunote:begin -->";
+const END: &str = "<!-- taunote:end -->";
+// Front matter keys taunote owns, other keys added in the vault are kept
+const PROJECT_KEYS: &[&str] = &[
+    "taunote_id",
+    "date",
+    "group",
+    "type",
+    "language",
+    "speakers",
+    "duration",
+    "tags",
+];
+const GROUP_KEYS: &[&str] = &["taunote_group", "tags"];
+// Segments whisperx aligned, see Taunote::transcribe
+const SEGMENTS_FILE: &str = "audio/segments.json";
+
+static SPEAKER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\[([^\]\n]+)\]").unwrap());
+static LIST_ITEM: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*(?:[-*+]|\d+[.)])\s+(?:\[[ xX]\]\s+)?(.+)$").unwrap());
+static CHECKED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\s*- \[[xX]\]\s+(.+?)\s*$").unwrap());
+static ACTION_HEADING: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)action items?|next steps|to-?dos?|follow-?ups?|tasks").unwrap()
+});
+static ACTION_PREFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)^(?:todo|action(?: item)?)\s*:\s*(.+)$").unwrap());
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct VaultReport {
+    // notes created or changed, group pages included
+    pub written: Vec<PathBuf>,
+    pub unchanged: usize,
+}
+
+// Writes one note per project under `{vault}/{group}/` and one page per group
+// linking its projects. Notes already in the vault are found by their
+// taunote_id, wherever they were moved, and updated in place.
+pub fn export_vault(
+    conn: &Connection,
+    base_dir: &Path,
+    vault: &Path,
+    group_id: Option<&str>,
+) -> Result<VaultReport> {
+    fs::create_dir_all(vault)?;
+    let existing = index_vault(vault)?;
+    let groups: HashMap<String, ProjectGroup> = list_project_groups(conn)?
+        .into_iter()
+        .map(|group| (group.id.clone(), group))
+        .collect();
+    let mut projects = list_audio_projects(conn)?;
+    projects.retain(|project| group_id.is_none_or(|id| project.group_id == id));
+    projects.sort_by(|a, b| (&a.group_id, &a.date, &a.name).cmp(&(&b.group_id, &b.date, &b.name)));
+
+    let mut report = VaultReport::default();
+    let mut taken: BTreeSet<PathBuf> = existing.values().cloned().collect();
+    let mut pages: Vec<(&ProjectGroup, Vec<(String, &AudioProject)>)> = Vec::new();
+    let fallback: Vec<ProjectGroup> = projects
+        .iter()
+        .filter(|project| !groups.contains_key(&project.group_id))
+        .map(|project| ProjectGroup {
+            id: project.group_id.clone(),
+            name: project.group_id.clone(),
+            parent_id: None,
+        })
+        .collect();
+    for project in &projects {
+        let group = groups
+            .get(&project.group_id)
+            .or_else(|| fallback.iter().find(|group| group.id == project.group_id))
+            .expect("every project has a group");
+        let path = match existing.get(&format!("project:{}", project.id)) {
+            Some(path) => path.clone(),
+            None => {
+                let folder = group_folder(vault, &groups, group);
+                let path = free_path(&folder, &project.name, &project.id, &taken);
+                taken.insert(path.clone());
+                path
+            }
+        };
+        let link = wiki_target(vault, &path);
+        let notes = get_project_notes(conn, &project.id)?.unwrap_or_default();
+        let metadata = get_project_metadata(conn, &project.id)?;
+        let page = project_page(
+            base_dir,
+            project,
+            group,
+            &group_link(vault, &existing, &groups, group),
+            &notes,
+            &metadata,
+        );
+        write_page(&path, &page, PROJECT_KEYS, &mut report)?;
+        match pages.iter_mut().find(|(known, _)| known.id == group.id) {
+            Some((_, links)) => links.push((link, project)),
+            None => pages.push((group, vec![(link, project)])),
+        }
+    }
+
+    for (group, links) in pages {
+        let path = existing
+            .get(&format!("group:{}", group.id))
+            .cloned()
+            .unwrap_or_else(|| group_page_path(vault, &groups, group));
+        write_page(&path, &group_page(group, &links), GROUP_KEYS, &mut report)?;
+    }
+    Ok(report)
+}
+
+// Generated note of a project: front matter and the part between the markers
+struct Page {
+    front_matter: Vec<(&'static str, String)>,
+    body: String,
+}
+
+fn project_page(
+    base_dir: &Path,
+    project: &AudioProject,
+    group: &ProjectGroup,
+    group_link: &str,
+    notes: &ProjectNotes,
+    metadata: &ProjectMetadata,
+) -> Page {
+    let mut front_matter = vec![
+        ("taunote_id", quote(&project.id)),
+        ("date", date(&project.date)),
+        (
+            "group",
+            quote(&format!("[[{group_link}|{}]]", file_name(&group.name))),
+        ),
+        ("type", quote(project.project_type.as_str())),
+        ("language", quote(&project.language)),
+        ("speakers", list(&speakers(&notes.transcript))),
+    ];
+    let seconds = metadata
+        .duration_seconds
+        .or_else(|| {
+            let dir = project_dir(base_dir, project).ok()?;
+            segments_duration(&dir.join(SEGMENTS_FILE))
+        });
+    if let Some(duration) = seconds.and_then(duration) {
+        front_matter.push(("duration", quote(&duration)));
+    }
+    let mut tags = vec![
+        "taunote".to_string(),
+        tag(project.project_type.as_str()),
+        tag(&group.name),
+    ];
+    for project_tag in metadata.tags.iter().map(|value| tag(value)) {
+        if !project_tag.is_empty() && !tags.contains(&project_tag) {
+            tags.push(project_tag);
+        }
+    }
+    front_matter.push(("tags", list(&tags)));
+
+    let mut body = format!(
+        "# {}\n\nGroup: [[{group_link}|{}]]\n",
+        project.name,
+        file_name(&group.name)
+    );
+    let items = action_items(&[&notes.summary, &notes.lecture_notes, &notes.email]);
+    if !items.is_empty() {
+        body.push_str("\n## Action items\n\n");
+        for item in items {
+            body.push_str(&format!("- [ ] {item}\n"));
+        }
+    }
+    for (title, content) in [
+        ("Summary", &notes.summary),
+        ("Lecture notes", &notes.lecture_notes),
+        ("Email", &notes.email),
+        ("Transcript", &notes.transcript),
+    ] {
+        if !content.trim().is_empty() {
+            body.push_str(&format!("\n## {title}\n\n{}\n", content.trim()));
+        }
+    }
+    Page { front_matter, body }
+}
+
+fn group_page(group: &ProjectGroup, links: &[(String, &AudioProject)]) -> Page {
+    let mut body = format!("# {}\n\n", group.name);
+    for (link, project) in links {
+        body.push_str(&format!(
+            "- [[{link}|{}]] · {}\n",
+            file_name(&project.name),
+            date(&project.date)
+        ));
+    }
+    Page {
+        front_matter: vec![
+            ("taunote_group", quote(&group.id)),
+            ("tags", list(&["taunote".to_string(), tag(&group.name)])),
+        ],
+        body,
+    }
+}
+
+// Merges the page into the file at `path`: taunote's front matter keys and the
+// text between the markers are replaced, the rest and ticked checkboxes are
+// kept. Files whose content would not change are not touched.
+fn write_page(path: &Path, page: &Page, owned: &[&str], report: &mut VaultReport) -> Result<()> {
+    let current = if path.is_file() {
+        Some(fs::read_to_string(path)?)
+    } else {
+        None
+    };
+    let (front_matter, rest) = current
+        .as_deref()
+        .map(split_front_matter)
+        .unwrap_or((Vec::new(), ""));
+
+    let mut yaml: Vec<String> = page
+        .front_matter
+        .iter()
+        .map(|(key, value)| format!("{key}: {value}"))
+        .collect();
+    let mut keep = false;
+    for line in front_matter {
+        if !line.starts_with(char::is_whitespace) && !line.starts_with('-') {
+            let key = line.split(':').next().unwrap_or_default().trim();
+            keep = !owned.contains(&key);
+        }
+        if keep {
+            yaml.push(line.to_string());
+        }
+    }
+
+    let (before, managed, after) = match (rest.find(BEGIN), rest.find(END)) {
+        (Some(begin), Some(end)) if begin < end => (
+            &rest[..begin],
+            &rest[begin + BEGIN.len()..end],
+            rest[end + END.len()..].trim_start_matches('\n'),
+        ),
+        _ => ("", "", rest.trim_start_matches('\n')),
+    };
+    let ticked: BTreeSet<&str> = managed
+        .lines()
+        .filter_map(|line| CHECKED.captures(line))
+        .filter_map(|caps| caps.get(1).map(|m| m.as_str()))
+        .collect();
+    let body: String = page
+        .body
+        .lines()
+        .map(|line| match line.strip_prefix("- [ ] ") {
+            Some(item) if ticked.contains(item.trim()) => format!("- [x] {item}\n"),
+            _ => format!("{line}\n"),
+        })
+        .collect();
+
+    let mut content = format!(
+        "---\n{}\n---\n{before}{BEGIN}\n{body}{END}\n",
+        yaml.join("\n")
+    );
+    if !after.is_empty() {
+        content.push('\n');
+        content.push_str(after);
+    }
+    if current.as_deref() == Some(content.as_str()) {
+        report.unchanged += 1;
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+    report.written.push(path.to_path_buf());
+    Ok(())
+}
+
+// Front matter lines and the text after it
+fn split_front_matter(text: &str) -> (Vec<&str>, &str) {
+    let Some(rest) = text.strip_prefix("---\n") else {
+        return (Vec::new(), text);
+    };
+    match rest.find("\n---\n") {
+        Some(end) => (rest[..end].lines().collect(), &rest[end + 5..]),
+        None if rest.starts_with("---\n") => (Vec::new(), &rest[4..]),
+        None => (Vec::new(), text),
+    }
+}
+
+// Notes taunote wrote before, by "project:{id}" or "group:{id}"
+fn index_vault(vault: &Path) -> Result<HashMap<String, PathBuf>> {
+    fn walk(dir: &Path, found: &mut HashMap<String, PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            // .obsidian, .trash and the like
+            if name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                walk(&path, found)?;
+            } else if name.ends_with(".md") {
+                let text = fs::read_to_string(&path).unwrap_or_default();
+                for line in split_front_matter(&text).0 {
+                    let entry = [("taunote_id:", "project"), ("taunote_group:", "group")]
+                        .into_iter()
+                        .find_map(|(key, kind)| line.strip_prefix(key).map(|id| (kind, id)));
+                    if let Some((kind, id)) = entry {
+                        found
+                            .entry(format!("{kind}:{}", unquote(id.trim())))
+                            .or_insert_with(|| path.clone());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    let mut found = HashMap::new();
+    walk(vault, &mut found)?;
+    Ok(found)
+}
+
+// `{folder}/{name}.md`, with the start of the id added when a different note
+// already has that name, ignoring case as vaults are synced to systems that do
+fn free_path(folder: &Path, name: &str, id: &str, taken: &BTreeSet<PathBuf>) -> PathBuf {
+    let name = file_name(name);
+    let path = folder.join(format!("{name}.md"));
+    let lower = |path: &Path| path.to_string_lossy().to_lowercase();
+    if !taken.iter().any(|known| lower(known) == lower(&path)) && !path.exists() {
+        return path;
+    }
+    folder.join(format!("{name} ({}).md", &id[..id.len().min(8)]))
+}
+
+// Wiki link target of a note: its path in the vault without the extension
+fn wiki_target(vault: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(vault).unwrap_or(path).with_extension("");
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn group_link(
+    vault: &Path,
+    existing: &HashMap<String, PathBuf>,
+    groups: &HashMap<String, ProjectGroup>,
+    group: &ProjectGroup,
+) -> String {
+    match existing.get(&format!("group:{}", group.id)) {
+        Some(path) => wiki_target(vault, path),
+        None => wiki_target(vault, &group_page_path(vault, groups, group)),
+    }
+}
+
+// Folder of a group's project notes, subgroups are folders in their parent's
+fn group_folder(
+    vault: &Path,
+    groups: &HashMap<String, ProjectGroup>,
+    group: &ProjectGroup,
+) -> PathBuf {
+    let mut names = vec![file_name(&group.name)];
+    let mut parent = group.parent_id.as_ref();
+    while let Some(ancestor) = parent.and_then(|id| groups.get(id)) {
+        // a loop in a database edited by hand
+        if names.len() > groups.len() {
+            break;
+        }
+        names.push(file_name(&ancestor.name));
+        parent = ancestor.parent_id.as_ref();
+    }
+    names
+        .iter()
+        .rev()
+        .fold(vault.to_path_buf(), |path, name| path.join(name))
+}
+
+// The group's page sits next to its folder, Clients/Acme.md for Clients/Acme/
+fn group_page_path(
+    vault: &Path,
+    groups: &HashMap<String, ProjectGroup>,
+    group: &ProjectGroup,
+) -> PathBuf {
+    let folder = group_folder(vault, groups, group);
+    folder.with_file_name(format!("{}.md", file_name(&group.name)))
+}
+
+// A note name that is a valid file name everywhere, without the characters
+// that break Obsidian's links
+fn file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if "#^[]".contains(c) { '-' } else { c })
+        .collect();
+    slug::file_name(&name, "Untitled")
+}
+
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn unquote(value: &str) -> String {
+    value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .map(|value| value.replace("\\\"", "\"").replace("\\\\", "\\"))
+        .unwrap_or_else(|| value.to_string())
+}
+
+fn list(values: &[String]) -> String {
+    let mut seen = BTreeSet::new();
+    let items: Vec<String> = values
+        .iter()
+        .filter(|value| !value.is_empty() && seen.insert(value.as_str()))
+        .map(|value| quote(value))
+        .collect();
+    format!("[{}]", items.join(", "))
+}
+
+// Obsidian tags have no spaces and no leading digits only
+fn tag(value: &str) -> String {
+    let tag: String = value
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || "_-/".contains(c) {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    if tag.chars().all(|c| c.is_ascii_digit()) {
+        String::new()
+    } else {
+        tag
+    }
+}
+
+// YYYY-MM-DD of an RFC 3339 timestamp
+fn date(value: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|date| date.format("%Y-%m-%d").to_string())
+        .unwrap_or_else(|_| value.chars().take(10).collect())
+}
+
+// Speaker labels of a diarized transcript, "[SPEAKER_00] text" per line, in
+// order of appearance
+fn speakers(transcript: &str) -> Vec<String> {
+    let mut speakers: Vec<String> = Vec::new();
+    for caps in transcript
+        .lines()
+        .filter_map(|line| SPEAKER.captures(line.trim_start()))
+    {
+        let speaker = caps[1].trim().to_string();
+        if !speakers.contains(&speaker) {
+            speakers.push(speaker);
+        }
+    }
+    speakers
+}
+
+// Length of the recording as H:MM:SS
+fn duration(seconds: f64) -> Option<String> {
+    let seconds = seconds.round() as u64;
+    (seconds > 0).then(|| {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    })
+}
+
+// List items under an "Action items", "Next steps", "TODO", ... heading and
+// items written as "TODO: ...", in order and without duplicates
+fn action_items(notes: &[&str]) -> Vec<String> {
+    let mut items: Vec<String> = Vec::new();
+    let mut push = |item: &str| {
+        let item = item.trim().trim_matches('*').trim().to_string();
+        if !item.is_empty() && !items.contains(&item) {
+            items.push(item);
+        }
+    };
+    for note in notes {
+        let mut in_section = false;
+        for line in note.lines() {
+            let trimmed = line.trim();
+            if let Some(item) = LIST_ITEM.captures(line) {
+                let text = &item[1];
+                if let Some(prefixed) = ACTION_PREFIX.captures(text.trim()) {
+                    push(&prefixed[1]);
+                } else if in_section {
+                    push(text);
+                }
+                continue;
+            }
+            if trimmed.is_empty() {
+                continue;
+            }
+            // a heading, a bold line or a line ending in ':' starts a section
+            let heading = trimmed.starts_with('#')
+                || (trimmed.starts_with("**") && trimmed.ends_with("**"))
+                || trimmed.ends_with(':');
+            if heading {
+                in_section = ACTION_HEADING.is_match(trimmed);
+            } else if let Some(prefixed) = ACTION_PREFIX.captures(trimmed) {
+                push(&prefixed[1]);
+            } else {
+                in_section = false;
+            }
+        }
+    }
+    items
+}
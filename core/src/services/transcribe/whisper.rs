@@ -1,18 +1,19 @@
+use crate::error::{Result, TaunoteError};
 use shellexpand;
 use std::path::Path;
 
-pub fn run_whisper(
-    input_path: &Path,
-    model: &Path,
-    lang: &str,
-    output_path: &Path,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let output_path = shellexpand::tilde(output_path.to_str().unwrap()).to_string();
+pub fn run_whisper(input_path: &Path, model: &Path, lang: &str, output_path: &Path) -> Result<()> {
+    let output_path = output_path
+        .to_str()
+        .ok_or_else(|| TaunoteError::InvalidInput("Invalid output path".to_string()))?;
+    let output_path = shellexpand::tilde(output_path).to_string();
     let output_path = Path::new(&output_path);
     std::fs::create_dir_all(output_path)?;
 
     let output_filename_path = output_path.join("transcript");
-    let output_filename_string = output_filename_path.to_str().ok_or("Invalid output path")?;
+    let output_filename_string = output_filename_path
+        .to_str()
+        .ok_or_else(|| TaunoteError::InvalidInput("Invalid output path".to_string()))?;
 
     match std::process::Command::new("./whisper-main")
         .arg("-m")
@@ -24,15 +25,25 @@ pub fn run_whisper(
         .arg("-otxt")
         .arg("-of")
         .arg(output_filename_string)
+        // stdout is for results, e.g. `taunote --json`
+        .stdout(std::io::stderr())
         .status()
     {
         Ok(status) if status.success() => {}
-        Ok(_) => return Err("whisper-cli exited with error".into()),
-        Err(e) => return Err(format!("Failed running whisper-cli: {e}").into()),
+        Ok(_) => {
+            return Err(TaunoteError::Transcription(
+                "whisper-cli exited with error".to_string(),
+            ))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(TaunoteError::missing_dependency(
+                "whisper-cli",
+                "build whisper.cpp and place whisper-main in the working directory",
+            ))
+        }
+        Err(e) => return Err(TaunoteError::Io(e)),
     }
 
     std::fs::read_to_string(output_filename_path.with_extension("txt"))?;
-
-    println!("Transcript written to: {output_filename_string}.txt");
     Ok(())
 }
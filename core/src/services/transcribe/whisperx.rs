@@ -1,8 +1,10 @@
-use anyhow::{anyhow, Result};
+use crate::error::{Result, TaunoteError};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
 use std::process::Command;
 
-fn path_to_model() -> Result<PathBuf> {
+// Locates whisperx_runner.py next to the crate or the installed binary
+pub fn path_to_model() -> Result<PathBuf> {
     let p = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
         .join("..")
         .join("python_backend")
@@ -15,43 +17,124 @@ fn path_to_model() -> Result<PathBuf> {
     // fallback for when I have the binary
     let bin_dir = std::env::current_exe()?
         .parent()
-        .ok_or_else(|| anyhow!("no parent directory for current_exe"))?
+        .ok_or_else(|| TaunoteError::Internal("no parent directory for current_exe".to_string()))?
         .to_path_buf();
     for f in [
         bin_dir.join("../python_backend/whisperx_runner.py"),
-        bin_dir.join("../../python_backend/whisperx_runner.py")
+        bin_dir.join("../../python_backend/whisperx_runner.py"),
     ] {
         if f.exists() {
             return Ok(f.canonicalize()?);
         }
     }
 
-    Err(anyhow!("Unbale to find whisperx_runner.py"))
+    Err(TaunoteError::missing_dependency(
+        "whisperx_runner.py",
+        "keep the python_backend folder next to the taunote binary",
+    ))
+}
+
+pub const DEFAULT_MODEL: &str = "small";
+
+// Settings a transcript is made with, stored with each transcript version
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WhisperxOptions {
+    // None lets whisperx detect the language
+    pub language: Option<String>,
+    // whisper model name, e.g. small, medium or large-v3
+    pub model: String,
+    pub diarize: bool,
+    pub min_speakers: Option<u32>,
+    pub max_speakers: Option<u32>,
+}
+
+impl Default for WhisperxOptions {
+    fn default() -> Self {
+        Self {
+            language: None,
+            model: DEFAULT_MODEL.to_string(),
+            diarize: true,
+            min_speakers: None,
+            max_speakers: None,
+        }
+    }
+}
+
+// Aligned segments kept between runs so diarization can be redone on its own
+pub enum Segments<'a> {
+    Discard,
+    Save(&'a Path),
+    // skips transcription and alignment
+    Reuse(&'a Path),
+}
+
+// Length of the recording in seconds: where the last aligned segment ends
+pub fn segments_duration(path: &Path) -> Option<f64> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let segments: serde_json::Value = serde_json::from_str(&text).ok()?;
+    let seconds = segments["segments"]
+        .as_array()?
+        .iter()
+        .filter_map(|segment| segment["end"].as_f64())
+        .fold(0.0, f64::max);
+    (seconds > 0.0).then_some(seconds)
 }
 
 pub fn run_whisperx(
     input_path: &Path,
     output_path: &Option<PathBuf>,
-    language: &Option<String>,
+    options: &WhisperxOptions,
+    segments: Segments,
 ) -> Result<()> {
     let whisperx_model = path_to_model()?;
     let mut cmd = Command::new("python3");
-    cmd.arg(&whisperx_model)
-        .arg("--input")
-        .arg(input_path);
+    cmd.arg(&whisperx_model).arg("--input").arg(input_path);
 
     if let Some(path) = output_path {
         cmd.arg("--output").arg(path);
     }
 
-    if let Some(lang) = language {
+    if let Some(lang) = &options.language {
         cmd.arg("--lang").arg(lang);
     }
 
-    let status = cmd.status()?;
+    cmd.arg("--model").arg(&options.model);
+    if !options.diarize {
+        cmd.arg("--no-diarize");
+    }
+    if let Some(min) = options.min_speakers {
+        cmd.arg("--min-speakers").arg(min.to_string());
+    }
+    if let Some(max) = options.max_speakers {
+        cmd.arg("--max-speakers").arg(max.to_string());
+    }
+
+    match segments {
+        Segments::Discard => {}
+        Segments::Save(path) => {
+            cmd.arg("--save-segments").arg(path);
+        }
+        Segments::Reuse(path) => {
+            cmd.arg("--from-segments").arg(path);
+        }
+    }
+
+    // the runner logs to stdout, which is kept for results like `taunote --json`
+    cmd.stdout(std::io::stderr());
+    let status = cmd.status().map_err(|err| match err.kind() {
+        std::io::ErrorKind::NotFound => TaunoteError::missing_dependency(
+            "python3",
+            "install Python 3 with the whisperx package",
+        ),
+        _ => TaunoteError::Io(err),
+    })?;
 
     if !status.success() {
-        return Err(anyhow!("WhisperX subprocess failed {:?}", status.code()));
+        return Err(TaunoteError::Transcription(format!(
+            "WhisperX subprocess failed {:?}",
+            status.code()
+        )));
     }
 
     Ok(())
@@ -0,0 +1,1202 @@
+use crate::error::{Result, TaunoteError};
+use crate::services::{
+    audio::{
+        capture::{Input, Level, Recorder, RecordingFormat},
+        ffmpeg::preprocess_audio,
+    },
+    backup::{
+        archive::{
+            create_backup, read_backup, restore_backup, verify_chain, BackupReport, RestoreReport,
+        },
+        manifest::Manifest,
+    },
+    bundle::{
+        export::export_bundle,
+        import::{import_bundle, ImportOptions, ImportReport},
+    },
+    calendar::{
+        ics::read_ics,
+        matching::{find_meeting, recorded_at},
+    },
+    config::settings::Settings,
+    crypto::{
+        keys::KdfParams,
+        migrate::{encrypt_data_dir, EncryptReport},
+        vault,
+    },
+    database::{
+        groups::{
+            delete_group, find_group, move_group, project_relative_path, rename_group,
+            resolve_group,
+        },
+        models::{
+            AudioProject, CalendarEvent, Media, MediaFormat, MediaUsage, Meeting, NoteVersion,
+            ProjectGroup, ProjectMetadata, ProjectType, Redaction, SearchFilter, SearchHit,
+            TagCount,
+        },
+        queries::{
+            delete_audio_project, get_audio_project, get_media, get_project_meeting,
+            get_project_metadata, insert_audio_project, insert_redaction, list_audio_projects,
+            list_calendar_events, list_group_projects, list_media, list_project_groups,
+            list_redactions, list_tags, rename_audio_project, search_notes, set_project_media,
+            set_project_meeting, set_project_metadata, set_project_type, update_project_location,
+            upsert_calendar_event,
+        },
+        schema::{apply_schema, db_path},
+        versions::current_version,
+    },
+    llm::{
+        generation::GenerationParams,
+        llama_queue::{JobOptions, LlmService},
+        llama_server::LlamaServerConfig,
+    },
+    media::store::{import_media, media_path, prune_media, remove_unreferenced, PruneReport},
+    notes::{
+        generate::{generate_note, save_transcript, GeneratedNote},
+        kinds::NoteKind,
+    },
+    redact::{
+        detect::{detect, RedactionRules},
+        mapping::RedactionMap,
+        names::detect_names,
+    },
+    sync::{
+        markdown::project_dir,
+        obsidian::{export_vault, VaultReport},
+    },
+    transcribe::whisperx::{run_whisperx, segments_duration, Segments, WhisperxOptions},
+};
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+// Inside a project folder: whisperx's aligned segments of the project's recording
+// and the redacted copies of its notes
+const AUDIO_DIR: &str = "audio";
+const SEGMENTS_FILE: &str = "segments.json";
+const REDACTED_DIR: &str = "redacted";
+// Microphone recordings until a project has its copy
+const RECORDINGS_DIR: &str = "recordings";
+
+/// What is needed to create a project, everything else is derived.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewProject {
+    /// Id of the group, or a path of group names like `Clients/Acme`. Missing
+    /// groups of the path are created.
+    pub group_id: String,
+    pub name: String,
+    pub project_type: ProjectType,
+    /// `None` lets whisperx detect the language.
+    pub language: Option<String>,
+}
+
+/// How a recording is turned into notes.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProcessOptions {
+    /// Note kinds generated after transcription, in order. Empty for the
+    /// defaults of the project's type, see [`NoteKind::defaults`].
+    #[serde(default)]
+    pub kinds: Vec<NoteKind>,
+    /// Overrides applied on top of every kind's default sampling.
+    pub params: GenerationParams,
+    /// Calendar event the recording was made in, see
+    /// [`Taunote::recording_meeting`]. Its attendees and agenda go into the
+    /// prompts.
+    #[serde(default)]
+    pub meeting: Option<Meeting>,
+}
+
+/// What [`Taunote::reprocess`] re-runs.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReprocessOptions {
+    /// Settings for the new transcript, the language falls back to the
+    /// project's, `"auto"` detects it.
+    pub whisperx: WhisperxOptions,
+    /// Keep the last transcription and only redo speaker diarization, e.g.
+    /// with a known number of speakers.
+    pub diarize_only: bool,
+    /// Regenerate the notes the project already has from the new transcript.
+    pub regenerate: bool,
+    pub params: GenerationParams,
+}
+
+/// What [`Taunote::redact`] looks for.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RedactOptions {
+    pub rules: RedactionRules,
+    /// Also ask the LLM for the names of people, slower and it can miss some.
+    pub names: bool,
+}
+
+/// Redacted copy of one note.
+#[derive(Debug, Clone, Serialize)]
+pub struct RedactedNote {
+    pub kind: String,
+    pub content: String,
+    pub file_path: PathBuf,
+}
+
+/// Result of [`Taunote::redact`].
+#[derive(Debug, Clone, Serialize)]
+pub struct RedactedProject {
+    pub project_id: String,
+    pub notes: Vec<RedactedNote>,
+    /// Every placeholder the project uses and the value it stands for.
+    pub redactions: Vec<Redaction>,
+}
+
+/// Result of [`Taunote::import_calendar`].
+#[derive(Debug, Clone, Serialize)]
+pub struct CalendarImport {
+    /// Events added or updated.
+    pub events: usize,
+    /// Of those, the ones that repeat.
+    pub recurring: usize,
+}
+
+/// Step of [`Taunote::process_recording_with_progress`] or
+/// [`Taunote::reprocess_with_progress`] that just started.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "stage", rename_all = "snake_case")]
+pub enum Progress {
+    Created { project_id: String },
+    Transcribing,
+    Generating { kind: NoteKind },
+}
+
+/// Result of [`Taunote::process_recording`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessedRecording {
+    pub project: AudioProject,
+    pub transcript: NoteVersion,
+    pub notes: Vec<GeneratedNote>,
+}
+
+/// Entry point to taunote: owns the data directory and the LLM service and
+/// runs the pipeline from recording to notes. The CLI and the desktop app
+/// both go through this type.
+#[derive(Clone)]
+pub struct Taunote {
+    base_dir: PathBuf,
+    llm: LlmService,
+    llama_config: LlamaServerConfig,
+    media_format: MediaFormat,
+    media_retention_days: Option<u32>,
+}
+
+impl Taunote {
+    /// Uses `base_dir` as the data directory. Nothing is touched on disk
+    /// until [`Taunote::init`] is called.
+    pub fn new(base_dir: impl Into<PathBuf>) -> Self {
+        let base_dir = base_dir.into();
+        let llama_config = LlamaServerConfig {
+            log_path: Some(base_dir.join("logs").join("llama-server.log")),
+            ..Default::default()
+        };
+        Self {
+            base_dir,
+            llm: LlmService::new(),
+            llama_config,
+            media_format: MediaFormat::default(),
+            media_retention_days: None,
+        }
+    }
+
+    /// The platform data directory, shared by the CLI and the desktop app.
+    pub fn default_dir() -> Result<PathBuf> {
+        directories_next::ProjectDirs::from("com", "andrea", "taunote")
+            .map(|dirs| dirs.data_local_dir().to_path_buf())
+            .ok_or_else(|| TaunoteError::Config("Failed to find platform data directory".into()))
+    }
+
+    /// Config used when the LLM service is started, the log path is kept
+    /// under the data directory unless set.
+    pub fn with_llama_config(mut self, config: LlamaServerConfig) -> Self {
+        self.llama_config = LlamaServerConfig {
+            log_path: config.log_path.or(self.llama_config.log_path),
+            ..config
+        };
+        self
+    }
+
+    /// Applies the settings saved with `taunote config` on top of the
+    /// current llama-server config and media policy.
+    pub fn with_settings(mut self, settings: &Settings) -> Self {
+        self.llama_config = settings.apply_to(self.llama_config);
+        self.media_format = settings.media_format.unwrap_or(self.media_format);
+        self.media_retention_days = settings.media_retention_days.or(self.media_retention_days);
+        self
+    }
+
+    /// Settings stored in the data directory.
+    pub fn settings(&self) -> Result<Settings> {
+        Settings::load(&self.base_dir)
+    }
+
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// The project's folder, an error when its stored path leads out of the
+    /// data directory.
+    pub fn project_dir(&self, project: &AudioProject) -> Result<PathBuf> {
+        project_dir(&self.base_dir, project)
+    }
+
+    pub fn llm(&self) -> &LlmService {
+        &self.llm
+    }
+
+    /// Creates the database and applies migrations, safe to call again. An
+    /// encrypted data directory must be unlocked first.
+    pub fn init(&self) -> Result<()> {
+        let path = db_path(&self.base_dir);
+        if let Some(db_dir) = path.parent() {
+            fs::create_dir_all(db_dir)?;
+        }
+        Ok(apply_schema(&mut self.connect()?)?)
+    }
+
+    /// Opens the database, keyed when the data directory is encrypted.
+    pub fn connect(&self) -> Result<Connection> {
+        let keys = vault::keys(&self.base_dir)?;
+        let conn = Connection::open(db_path(&self.base_dir))?;
+        if let Some(keys) = keys {
+            vault::apply_db_key(&conn, &keys)?;
+        }
+        // SQLite leaves REFERENCES unchecked unless asked per connection
+        conn.pragma_update(None, "foreign_keys", true)?;
+        Ok(conn)
+    }
+
+    /// Whether `taunote encrypt` was run on the data directory.
+    pub fn is_encrypted(&self) -> bool {
+        vault::is_encrypted_dir(&self.base_dir)
+    }
+
+    /// Whether the data directory is encrypted and not unlocked in this process.
+    pub fn is_locked(&self) -> bool {
+        self.is_encrypted() && !vault::is_unlocked(&self.base_dir)
+    }
+
+    /// Derives the keys from `passphrase` and keeps them until [`Taunote::lock`],
+    /// a wrong passphrase is a [`TaunoteError::Locked`].
+    pub fn unlock(&self, passphrase: &str) -> Result<()> {
+        vault::unlock(&self.base_dir, passphrase)
+    }
+
+    /// Unlocks with a key saved by [`Taunote::remember_key`], false when the OS
+    /// keyring has none.
+    pub fn unlock_from_keyring(&self) -> Result<bool> {
+        vault::unlock_from_keyring(&self.base_dir)
+    }
+
+    /// Saves the unlocked key in the OS keyring so the next runs need no passphrase.
+    pub fn remember_key(&self) -> Result<()> {
+        vault::remember(&self.base_dir)
+    }
+
+    /// Removes the key from the OS keyring and from this process.
+    pub fn lock(&self) -> Result<()> {
+        vault::remove(&self.base_dir);
+        vault::forget(&self.base_dir)
+    }
+
+    /// Encrypts the database and the note files with a key derived from
+    /// `passphrase` and leaves the data directory unlocked. Nothing else may
+    /// use the data directory meanwhile. Needs the `encryption` feature.
+    pub fn encrypt(&self, passphrase: &str) -> Result<EncryptReport> {
+        encrypt_data_dir(&self.base_dir, passphrase, KdfParams::default())
+    }
+
+    /// Config of the running llama-server, or the one it will be started with.
+    pub fn llama_config(&self) -> LlamaServerConfig {
+        self.llm
+            .config()
+            .unwrap_or_else(|| self.llama_config.clone())
+    }
+
+    /// Starts llama-server unless it already runs, keeping a model picked
+    /// with [`Taunote::switch_model`].
+    pub async fn start_llm(&self) -> Result<()> {
+        self.llm.ensure_started(self.llama_config()).await
+    }
+
+    /// Restarts llama-server with another GGUF model, queued jobs fail.
+    pub async fn switch_model(&self, model_path: PathBuf) -> Result<()> {
+        self.llm
+            .restart(LlamaServerConfig {
+                model_path,
+                ..self.llama_config()
+            })
+            .await
+    }
+
+    pub async fn shutdown(&self) {
+        self.llm.shutdown().await;
+    }
+
+    /// Stores a new project, creating its group if needed. Files live under
+    /// `groups/{group_id}/{project_id}` in the data directory, however deep
+    /// the group is nested.
+    pub fn create_project(&self, new: NewProject) -> Result<AudioProject> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        let group = resolve_group(&tx, &new.group_id)?;
+        let id = Uuid::new_v4().to_string();
+        let project = AudioProject {
+            relative_path: project_relative_path(&group.id, &id),
+            id,
+            group_id: group.id,
+            name: new.name,
+            date: chrono::Utc::now().to_rfc3339(),
+            project_type: new.project_type,
+            language: new.language.unwrap_or_else(|| "Auto".to_string()),
+            media_hash: None,
+        };
+        let folder = self.project_dir(&project)?;
+        insert_audio_project(&tx, &project)?;
+        tx.commit()?;
+        fs::create_dir_all(folder)?;
+        Ok(project)
+    }
+
+    pub fn project(&self, project_id: &str) -> Result<AudioProject> {
+        get_audio_project(&self.connect()?, project_id)?
+            .ok_or_else(|| TaunoteError::NotFound(format!("Project {project_id}")))
+    }
+
+    /// Every group with its parent, see
+    /// [`group_path`](crate::services::database::groups::group_path) for
+    /// paths like `Clients/Acme`.
+    pub fn list_groups(&self) -> Result<Vec<ProjectGroup>> {
+        Ok(list_project_groups(&self.connect()?)?)
+    }
+
+    /// The group with this id, or at this path of group names.
+    pub fn group(&self, group: &str) -> Result<ProjectGroup> {
+        find_group(&self.connect()?, group)?
+            .ok_or_else(|| TaunoteError::NotFound(format!("Group {group}")))
+    }
+
+    /// Creates the missing groups of a path like `Clients/Acme` and returns
+    /// the last one.
+    pub fn create_group(&self, path: &str) -> Result<ProjectGroup> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        let group = resolve_group(&tx, path)?;
+        tx.commit()?;
+        Ok(group)
+    }
+
+    /// Renames a group, its projects keep their folders.
+    pub fn rename_group(&self, group: &str, name: &str) -> Result<ProjectGroup> {
+        let group = self.group(group)?;
+        rename_group(&self.connect()?, &group.id, name)?;
+        self.group(&group.id)
+    }
+
+    /// Puts a group under `parent`, which is created if needed, or at the top
+    /// level for `None`. Its projects keep their folders.
+    pub fn move_group(&self, group: &str, parent: Option<&str>) -> Result<ProjectGroup> {
+        let group = self.group(group)?;
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        let parent = parent
+            .map(|parent| resolve_group(&tx, parent))
+            .transpose()?;
+        move_group(
+            &tx,
+            &group.id,
+            parent.as_ref().map(|parent| parent.id.as_str()),
+        )?;
+        tx.commit()?;
+        self.group(&group.id)
+    }
+
+    /// Deletes a group without projects or subgroups.
+    pub fn delete_group(&self, group: &str) -> Result<()> {
+        let group = self.group(group)?;
+        delete_group(&self.connect()?, &group.id)
+    }
+
+    /// All projects, or only those directly in `group`, given by id or path.
+    pub fn list_projects(&self, group: Option<&str>) -> Result<Vec<AudioProject>> {
+        let conn = self.connect()?;
+        Ok(match group {
+            Some(group) => list_group_projects(&conn, &self.group(group)?.id)?,
+            None => list_audio_projects(&conn)?,
+        })
+    }
+
+    /// Current version of every note the project has, in [`NoteKind::ALL`] order.
+    pub fn notes(&self, project_id: &str) -> Result<Vec<NoteVersion>> {
+        let project = self.project(project_id)?;
+        let conn = self.connect()?;
+        let mut notes = Vec::new();
+        for kind in NoteKind::ALL {
+            if let Some(version) = current_version(&conn, &project.id, kind.as_str())? {
+                notes.push(version);
+            }
+        }
+        Ok(notes)
+    }
+
+    pub fn set_project_type(
+        &self,
+        project_id: &str,
+        project_type: ProjectType,
+    ) -> Result<AudioProject> {
+        self.project(project_id)?;
+        set_project_type(&self.connect()?, project_id, project_type)?;
+        self.project(project_id)
+    }
+
+    /// Tags, description, participants and the other metadata of a project.
+    pub fn project_metadata(&self, project_id: &str) -> Result<ProjectMetadata> {
+        self.project(project_id)?;
+        Ok(get_project_metadata(&self.connect()?, project_id)?)
+    }
+
+    /// Replaces the project's metadata and returns it as stored: tags and
+    /// participants are trimmed and kept once ignoring case, tags lose a
+    /// leading `#`.
+    pub fn set_project_metadata(
+        &self,
+        project_id: &str,
+        metadata: ProjectMetadata,
+    ) -> Result<ProjectMetadata> {
+        self.update_metadata(project_id, |current| *current = metadata)
+    }
+
+    /// Changes the project's metadata with `edit`, e.g. to add a tag.
+    pub fn update_metadata(
+        &self,
+        project_id: &str,
+        edit: impl FnOnce(&mut ProjectMetadata),
+    ) -> Result<ProjectMetadata> {
+        self.project(project_id)?;
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        let mut metadata = get_project_metadata(&tx, project_id)?;
+        edit(&mut metadata);
+        let metadata = normalize_metadata(metadata)?;
+        set_project_metadata(&tx, project_id, &metadata)?;
+        tx.commit()?;
+        Ok(metadata)
+    }
+
+    /// Every tag in use and how many projects have it.
+    pub fn tags(&self) -> Result<Vec<TagCount>> {
+        Ok(list_tags(&self.connect()?)?)
+    }
+
+    pub fn rename_project(&self, project_id: &str, name: &str) -> Result<AudioProject> {
+        self.project(project_id)?;
+        rename_audio_project(&self.connect()?, project_id, name)?;
+        self.project(project_id)
+    }
+
+    /// Moves a project and its folder to another group, given by id or by a
+    /// path whose missing groups are created. The folder and the database
+    /// change together or not at all.
+    pub fn move_project(&self, project_id: &str, group: &str) -> Result<AudioProject> {
+        let project = self.project(project_id)?;
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        let group = resolve_group(&tx, group)?;
+        // folders of projects made before they were named by id get renamed too
+        let relative_path = project_relative_path(&group.id, &project.id);
+        if relative_path == project.relative_path {
+            return Ok(project);
+        }
+        let from = self.project_dir(&project)?;
+        let to = self.base_dir.join(&relative_path);
+        if to.exists() {
+            return Err(TaunoteError::InvalidInput(format!(
+                "{} already exists",
+                to.display()
+            )));
+        }
+
+        update_project_location(&tx, project_id, &group.id, &relative_path)?;
+        if from.exists() {
+            if let Some(parent) = to.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::rename(&from, &to)?;
+        }
+        if let Err(err) = tx.commit() {
+            let _ = fs::rename(&to, &from);
+            return Err(err.into());
+        }
+        self.project(project_id)
+    }
+
+    /// Deletes a project with all its notes, versions and files, and its
+    /// recording unless another project uses it.
+    pub fn delete_project(&self, project_id: &str) -> Result<()> {
+        let project = self.project(project_id)?;
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        delete_audio_project(&tx, project_id)?;
+        tx.commit()?;
+        remove_unreferenced(&conn, &self.base_dir)?;
+        // a folder outside the library is never removed, the project still goes
+        if let Ok(folder) = self.project_dir(&project) {
+            if folder.exists() {
+                fs::remove_dir_all(folder)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds the events of a local .ics file, replacing those imported before
+    /// with the same UID. Nothing is fetched, recurring events are expanded
+    /// from their rules when matching.
+    pub fn import_calendar(&self, ics: &Path) -> Result<CalendarImport> {
+        let events = read_ics(ics)?;
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        for event in &events {
+            upsert_calendar_event(&tx, event)?;
+        }
+        tx.commit()?;
+        Ok(CalendarImport {
+            events: events.len(),
+            recurring: events.iter().filter(|event| event.rrule.is_some()).count(),
+        })
+    }
+
+    /// Imported calendar events, by start.
+    pub fn calendar_events(&self) -> Result<Vec<CalendarEvent>> {
+        Ok(list_calendar_events(&self.connect()?)?)
+    }
+
+    /// The occurrence of an imported event going on at `at`, or starting or
+    /// ending within a few minutes of it.
+    pub fn find_meeting(&self, at: DateTime<Utc>) -> Result<Option<Meeting>> {
+        Ok(find_meeting(&self.calendar_events()?, at))
+    }
+
+    /// [`Taunote::find_meeting`] for when a recording was made: the date and
+    /// time in its file name, else when the file was last written.
+    pub fn recording_meeting(&self, audio_path: &Path) -> Result<Option<Meeting>> {
+        match recorded_at(audio_path) {
+            Some(at) => self.find_meeting(at),
+            None => Ok(None),
+        }
+    }
+
+    /// The meeting the project was recorded in, if it was matched to one.
+    pub fn project_meeting(&self, project_id: &str) -> Result<Option<Meeting>> {
+        self.project(project_id)?;
+        Ok(get_project_meeting(&self.connect()?, project_id)?)
+    }
+
+    /// Links the project to a meeting, notes generated from then on see its
+    /// attendees and agenda.
+    pub fn set_project_meeting(&self, project_id: &str, meeting: &Meeting) -> Result<()> {
+        self.project(project_id)?;
+        Ok(set_project_meeting(&self.connect()?, project_id, meeting)?)
+    }
+
+    /// Normalizes and transcribes an audio file without storing anything.
+    pub fn transcribe_file(&self, audio_path: &Path, language: Option<&str>) -> Result<String> {
+        let options = WhisperxOptions {
+            language: language.map(str::to_string),
+            ..Default::default()
+        };
+        self.run_transcription(audio_path, &options, Segments::Discard)
+    }
+
+    fn run_transcription(
+        &self,
+        audio_path: &Path,
+        options: &WhisperxOptions,
+        segments: Segments,
+    ) -> Result<String> {
+        // per-call scratch folder so parallel runs do not overwrite each other
+        let tmp_dir = self.base_dir.join("tmp").join(Uuid::new_v4().to_string());
+        let preprocessed = tmp_dir.join("preprocessed.wav");
+        let transcript_path = tmp_dir.join("transcript.txt");
+
+        let result = preprocess_audio(audio_path, &preprocessed)
+            .and_then(|()| {
+                run_whisperx(
+                    &preprocessed,
+                    &Some(transcript_path.clone()),
+                    options,
+                    segments,
+                )
+            })
+            .and_then(|()| Ok(fs::read_to_string(&transcript_path)?));
+        let _ = fs::remove_dir_all(&tmp_dir);
+        result
+    }
+
+    /// Transcribes a recording and stores it as the project's current
+    /// transcript. `language` falls back to the project's language. The
+    /// recording is copied into the project so it can be reprocessed.
+    pub fn transcribe(
+        &self,
+        project_id: &str,
+        audio_path: &Path,
+        language: Option<&str>,
+    ) -> Result<NoteVersion> {
+        let project = self.project(project_id)?;
+        let media = self.import_audio(&project, audio_path)?;
+        let original = media_path(&self.base_dir, &media);
+        let options = WhisperxOptions {
+            language: language.map(str::to_string),
+            ..Default::default()
+        };
+        self.transcribe_project(&project, &original, options, false)
+    }
+
+    /// Stores a recording in the media store and links it to the project,
+    /// replacing the recording it had. Recordings no project uses anymore
+    /// are deleted.
+    pub fn import_audio(&self, project: &AudioProject, audio_path: &Path) -> Result<Media> {
+        if !audio_path.is_file() {
+            return Err(TaunoteError::NotFound(format!(
+                "Recording {}",
+                audio_path.display()
+            )));
+        }
+        let conn = self.connect()?;
+        let media = import_media(&conn, &self.base_dir, audio_path, self.media_format)?;
+        let current = self.project(&project.id)?.media_hash;
+        if current.as_deref() != Some(media.hash.as_str()) {
+            set_project_media(&conn, &project.id, Some(&media.hash))?;
+            // segments of the old recording must not be diarized with the new one
+            let _ = fs::remove_file(
+                self.project_dir(project)?
+                    .join(AUDIO_DIR)
+                    .join(SEGMENTS_FILE),
+            );
+            remove_unreferenced(&conn, &self.base_dir)?;
+        }
+        Ok(media)
+    }
+
+    /// Path of the project's recording in the media store, if it has one.
+    pub fn original_audio(&self, project: &AudioProject) -> Result<Option<PathBuf>> {
+        let Some(hash) = self.project(&project.id)?.media_hash else {
+            return Ok(None);
+        };
+        Ok(get_media(&self.connect()?, &hash)?
+            .map(|media| media_path(&self.base_dir, &media))
+            .filter(|path| path.is_file()))
+    }
+
+    /// Every stored recording and how many projects use it.
+    pub fn media(&self) -> Result<Vec<MediaUsage>> {
+        Ok(list_media(&self.connect()?)?)
+    }
+
+    /// Applies the retention setting: recordings of projects older than
+    /// `media_retention_days` are deleted, their notes are kept. Recordings
+    /// no project uses are deleted in any case.
+    pub fn prune_media(&self) -> Result<PruneReport> {
+        let mut conn = self.connect()?;
+        let tx = conn.transaction()?;
+        let report = prune_media(
+            &tx,
+            &self.base_dir,
+            self.media_retention_days,
+            chrono::Utc::now(),
+        )?;
+        tx.commit()?;
+        Ok(report)
+    }
+
+    // Transcribes the stored recording with `options`, keeping the aligned
+    // segments for a later diarization-only run
+    fn transcribe_project(
+        &self,
+        project: &AudioProject,
+        audio_path: &Path,
+        mut options: WhisperxOptions,
+        diarize_only: bool,
+    ) -> Result<NoteVersion> {
+        if diarize_only {
+            // the words stay the same, so the stored settings keep the previous model
+            let previous =
+                current_version(&self.connect()?, &project.id, NoteKind::Transcript.as_str())?
+                    .and_then(|version| version.params)
+                    .and_then(|params| serde_json::from_str::<WhisperxOptions>(&params).ok());
+            options = WhisperxOptions {
+                diarize: true,
+                min_speakers: options.min_speakers,
+                max_speakers: options.max_speakers,
+                ..previous.unwrap_or(options)
+            };
+        }
+        options.language = options
+            .language
+            .or_else(|| Some(project.language.clone()))
+            .filter(|lang| !lang.eq_ignore_ascii_case("auto"));
+        let segments_path = self
+            .project_dir(project)?
+            .join(AUDIO_DIR)
+            .join(SEGMENTS_FILE);
+        let segments = if diarize_only {
+            if !segments_path.is_file() {
+                return Err(TaunoteError::InvalidInput(format!(
+                    "Project {} has no stored segments, reprocess the transcription first",
+                    project.id
+                )));
+            }
+            Segments::Reuse(&segments_path)
+        } else {
+            Segments::Save(&segments_path)
+        };
+
+        let transcript = self.run_transcription(audio_path, &options, segments)?;
+        if let Some(seconds) = segments_duration(&segments_path) {
+            self.update_metadata(&project.id, |metadata| {
+                metadata.duration_seconds.get_or_insert(seconds);
+            })?;
+        }
+        save_transcript(
+            &mut self.connect()?,
+            &self.base_dir,
+            project,
+            &transcript,
+            &format!("whisperx/{}", options.model),
+            Some(serde_json::to_string(&options)?),
+        )
+    }
+
+    /// Re-runs transcription, or only diarization, on the project's stored
+    /// recording and saves the result as a new transcript version.
+    pub async fn reprocess(
+        &self,
+        project_id: &str,
+        options: &ReprocessOptions,
+    ) -> Result<ProcessedRecording> {
+        self.reprocess_with_progress(project_id, options, |_| {})
+            .await
+    }
+
+    /// [`Taunote::reprocess`], calling `on_progress` as each step starts.
+    pub async fn reprocess_with_progress(
+        &self,
+        project_id: &str,
+        options: &ReprocessOptions,
+        on_progress: impl Fn(Progress) + Send + Sync,
+    ) -> Result<ProcessedRecording> {
+        let project = self.project(project_id)?;
+        let audio = self.original_audio(&project)?.ok_or_else(|| {
+            TaunoteError::InvalidInput(format!(
+                "Project {project_id} has no stored recording to reprocess"
+            ))
+        })?;
+        // decided before the new transcript exists, only notes the project has are redone
+        let mut kinds = Vec::new();
+        if options.regenerate {
+            let conn = self.connect()?;
+            for kind in NoteKind::generated() {
+                if current_version(&conn, &project.id, kind.as_str())?.is_some() {
+                    kinds.push(kind);
+                }
+            }
+        }
+
+        on_progress(Progress::Transcribing);
+        let whisperx = options.whisperx.clone();
+        let diarize_only = options.diarize_only;
+        let transcript = self
+            .blocking(move |taunote| {
+                taunote.transcribe_project(&project, &audio, whisperx, diarize_only)
+            })
+            .await?;
+
+        let mut notes = Vec::new();
+        for kind in kinds {
+            on_progress(Progress::Generating { kind });
+            let job = JobOptions {
+                label: kind.to_string(),
+                ..Default::default()
+            };
+            notes.push(
+                self.generate_note(project_id, kind, &options.params, job)
+                    .await?,
+            );
+        }
+        Ok(ProcessedRecording {
+            project: self.project(project_id)?,
+            transcript,
+            notes,
+        })
+    }
+
+    /// Generates one note kind from the project's transcript and stores it as
+    /// a new version. Starts the LLM service if it is not running.
+    pub async fn generate_note(
+        &self,
+        project_id: &str,
+        kind: NoteKind,
+        params: &GenerationParams,
+        options: JobOptions,
+    ) -> Result<GeneratedNote> {
+        self.start_llm().await?;
+        generate_note(
+            &self.llm,
+            &mut self.connect()?,
+            &self.base_dir,
+            project_id,
+            kind,
+            params,
+            options,
+        )
+        .await
+    }
+
+    /// Runs the whole pipeline: creates the project, transcribes the
+    /// recording, then generates each requested note kind. The project is
+    /// removed again when the recording cannot be transcribed.
+    pub async fn process_recording(
+        &self,
+        audio_path: &Path,
+        new: NewProject,
+        options: &ProcessOptions,
+    ) -> Result<ProcessedRecording> {
+        self.process_recording_with_progress(audio_path, new, options, |_| {})
+            .await
+    }
+
+    /// [`Taunote::process_recording`], calling `on_progress` as each step starts.
+    pub async fn process_recording_with_progress(
+        &self,
+        audio_path: &Path,
+        new: NewProject,
+        options: &ProcessOptions,
+        on_progress: impl Fn(Progress) + Send + Sync,
+    ) -> Result<ProcessedRecording> {
+        let project = self.create_project(new)?;
+        let transcribed = async {
+            if let Some(meeting) = &options.meeting {
+                self.set_project_meeting(&project.id, meeting)?;
+                self.update_metadata(&project.id, |metadata| {
+                    if metadata.participants.is_empty() {
+                        metadata.participants = meeting.attendees.clone();
+                    }
+                })?;
+            }
+            on_progress(Progress::Created {
+                project_id: project.id.clone(),
+            });
+            on_progress(Progress::Transcribing);
+            self.transcribe_blocking(&project.id, audio_path, None)
+                .await
+        }
+        .await;
+        // without a transcript there is nothing to keep, notes that fail
+        // later leave the transcribed project in place
+        let transcript = match transcribed {
+            Ok(transcript) => transcript,
+            Err(err) => {
+                let _ = self.delete_project(&project.id);
+                return Err(err);
+            }
+        };
+
+        let kinds = match options.kinds.as_slice() {
+            [] => NoteKind::defaults(project.project_type),
+            kinds => kinds,
+        };
+        let mut notes = Vec::new();
+        for kind in kinds {
+            on_progress(Progress::Generating { kind: *kind });
+            let job = JobOptions {
+                label: kind.to_string(),
+                ..Default::default()
+            };
+            notes.push(
+                self.generate_note(&project.id, *kind, &options.params, job)
+                    .await?,
+            );
+        }
+        Ok(ProcessedRecording {
+            project,
+            transcript,
+            notes,
+        })
+    }
+
+    /// Starts recording `input` into the data directory, `on_level` is
+    /// called about 20 times a second with the loudness. Pass the recorder
+    /// to [`Taunote::finish_recording`] when done, or cancel it.
+    pub fn start_recording(
+        &self,
+        input: Box<dyn Input>,
+        format: RecordingFormat,
+        on_level: impl Fn(Level) + Send + 'static,
+    ) -> Result<Recorder> {
+        let name = format!(
+            "{}-{}.{}",
+            Utc::now().format("%Y-%m-%d_%H-%M-%S"),
+            &Uuid::new_v4().simple().to_string()[..8],
+            format.as_str()
+        );
+        let path = self.base_dir.join(RECORDINGS_DIR).join(name);
+        Recorder::start(input, &path, format, on_level)
+    }
+
+    /// Stops the recording and runs it through
+    /// [`Taunote::process_recording_with_progress`]. The recording is removed
+    /// once the project has its copy, and kept in the data directory's
+    /// recordings folder when processing fails.
+    pub async fn finish_recording(
+        &self,
+        recorder: Recorder,
+        new: NewProject,
+        options: &ProcessOptions,
+        on_progress: impl Fn(Progress) + Send + Sync,
+    ) -> Result<ProcessedRecording> {
+        let recording = tokio::task::spawn_blocking(move || recorder.stop())
+            .await
+            .map_err(|e| TaunoteError::Internal(format!("Recording task failed: {e}")))??;
+        let processed = self
+            .process_recording_with_progress(&recording.path, new, options, on_progress)
+            .await?;
+        let _ = fs::remove_file(&recording.path);
+        Ok(processed)
+    }
+
+    /// [`Taunote::transcribe`] on the blocking thread pool, ffmpeg and
+    /// whisperx run for minutes.
+    pub async fn transcribe_blocking(
+        &self,
+        project_id: &str,
+        audio_path: &Path,
+        language: Option<&str>,
+    ) -> Result<NoteVersion> {
+        let project_id = project_id.to_string();
+        let audio_path = audio_path.to_path_buf();
+        let language = language.map(str::to_string);
+        self.blocking(move |taunote| {
+            taunote.transcribe(&project_id, &audio_path, language.as_deref())
+        })
+        .await
+    }
+
+    async fn blocking<T: Send + 'static>(
+        &self,
+        run: impl FnOnce(Taunote) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let taunote = self.clone();
+        tokio::task::spawn_blocking(move || run(taunote))
+            .await
+            .map_err(|e| TaunoteError::Internal(format!("Transcription task failed: {e}")))?
+    }
+
+    /// Full text search over transcripts and notes (SQLite FTS5 syntax).
+    pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchHit>> {
+        self.search_filtered(query, &SearchFilter::default(), limit)
+    }
+
+    /// [`Taunote::search`] among the projects matching `filter`. An empty
+    /// query lists those projects, newest first.
+    pub fn search_filtered(
+        &self,
+        query: &str,
+        filter: &SearchFilter,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>> {
+        Ok(search_notes(&self.connect()?, query, filter, limit)?)
+    }
+
+    /// Writes the current version of each of the project's notes to
+    /// `dest/{kind}.md` and returns the written files.
+    pub fn export(&self, project_id: &str, dest: &Path) -> Result<Vec<PathBuf>> {
+        let notes = self.notes(project_id)?;
+        fs::create_dir_all(dest)?;
+
+        let mut written = Vec::new();
+        for version in notes {
+            let file_name = NoteKind::parse(&version.note_kind)
+                .map(|kind| kind.file_name())
+                .unwrap_or_else(|| format!("{}.md", version.note_kind));
+            let path = dest.join(file_name);
+            fs::write(&path, version.content)?;
+            written.push(path);
+        }
+        Ok(written)
+    }
+
+    /// Writes every project, or those of one group, as a note with YAML front
+    /// matter into an Obsidian vault, plus a page per group linking its
+    /// projects. Running it again updates the notes in place and keeps what
+    /// was added to them in the vault.
+    pub fn export_obsidian(&self, vault: &Path, group_id: Option<&str>) -> Result<VaultReport> {
+        export_vault(&self.connect()?, &self.base_dir, vault, group_id)
+    }
+
+    /// Writes the project to a `.taunote` bundle for another library: its
+    /// recording, transcript segments and every version of its notes. `dest`
+    /// is a file or an existing folder, the written file is returned.
+    pub fn export_bundle(&self, project_id: &str, dest: &Path) -> Result<PathBuf> {
+        let project = self.project(project_id)?;
+        export_bundle(&self.connect()?, &self.base_dir, &project, dest)
+    }
+
+    /// Adds the project in a `.taunote` bundle under new ids, after checking
+    /// the bundle against its manifest. See [`ImportOptions`] for which group
+    /// it goes to.
+    pub fn import_bundle(&self, bundle: &Path, options: &ImportOptions) -> Result<ImportReport> {
+        import_bundle(&mut self.connect()?, &self.base_dir, bundle, options)
+    }
+
+    /// Writes a backup archive of the library: a consistent copy of the
+    /// database, the project folders, the media store and the settings, with a
+    /// manifest of checksums. `dest` is a file or an existing folder. With
+    /// `since`, an earlier backup, only what changed since is stored.
+    pub fn backup(&self, dest: &Path, since: Option<&Path>) -> Result<BackupReport> {
+        let since = since.map(read_backup).transpose()?;
+        let keys = vault::keys(&self.base_dir)?;
+        create_backup(
+            &self.connect()?,
+            keys.as_deref(),
+            &self.base_dir,
+            dest,
+            since.as_ref(),
+        )
+    }
+
+    /// Checks a full backup and the incremental backups that follow it, in
+    /// order, and returns the manifest of the last one.
+    pub fn verify_backup(&self, archives: &[PathBuf]) -> Result<Manifest> {
+        verify_chain(archives)?
+            .pop()
+            .ok_or_else(|| TaunoteError::InvalidInput("No backup given".to_string()))
+    }
+
+    /// Replaces the library with the one in the backups, see
+    /// [`Taunote::verify_backup`]. The current library is moved to a
+    /// `pre-restore-*` folder in the data directory. An encrypted library has
+    /// to be unlocked again afterwards.
+    pub fn restore(&self, archives: &[PathBuf]) -> Result<RestoreReport> {
+        restore_backup(&self.base_dir, archives)
+    }
+
+    /// Writes a redacted copy of each of the project's current notes to
+    /// `redacted/` in its folder. Placeholders such as `[EMAIL_1]` are kept
+    /// per project in the database, so the same value is replaced the same way
+    /// every time and [`Taunote::unredact`] can undo it.
+    pub async fn redact(
+        &self,
+        project_id: &str,
+        options: &RedactOptions,
+    ) -> Result<RedactedProject> {
+        let project = self.project(project_id)?;
+        let notes = self.notes(project_id)?;
+        if notes.is_empty() {
+            return Err(TaunoteError::NotFound(format!(
+                "Notes of project {project_id}"
+            )));
+        }
+        let names = if options.names {
+            self.start_llm().await?;
+            let text: Vec<&str> = notes.iter().map(|note| note.content.as_str()).collect();
+            detect_names(
+                &self.llm,
+                &text.join("\n\n"),
+                JobOptions::interactive("names"),
+            )
+            .await?
+        } else {
+            Vec::new()
+        };
+
+        let conn = self.connect()?;
+        let mut map = RedactionMap::new(list_redactions(&conn, project_id)?);
+        let dir = self.project_dir(&project)?.join(REDACTED_DIR);
+        fs::create_dir_all(&dir)?;
+        let mut redacted = Vec::new();
+        for note in notes {
+            let detections = detect(&note.content, &options.rules, &names);
+            let content = map.redact(&note.content, &detections);
+            let file_path = dir.join(format!(
+                "{}.{}",
+                note.note_kind,
+                vault::note_extension(&self.base_dir)
+            ));
+            vault::write_note(&self.base_dir, &file_path, &content)?;
+            redacted.push(RedactedNote {
+                kind: note.note_kind,
+                content,
+                file_path,
+            });
+        }
+        for redaction in map.added() {
+            insert_redaction(&conn, project_id, redaction)?;
+        }
+        Ok(RedactedProject {
+            project_id: project.id,
+            notes: redacted,
+            redactions: map.entries().to_vec(),
+        })
+    }
+
+    /// Puts the values a redaction of the project replaced back into `text`,
+    /// e.g. into a reply to a redacted summary.
+    pub fn unredact(&self, project_id: &str, text: &str) -> Result<String> {
+        self.project(project_id)?;
+        let map = RedactionMap::new(list_redactions(&self.connect()?, project_id)?);
+        Ok(map.restore(text))
+    }
+}
+
+// Trimmed, without empty entries and each tag and participant once
+fn normalize_metadata(metadata: ProjectMetadata) -> Result<ProjectMetadata> {
+    fn unique(values: Vec<String>, trim: impl Fn(&str) -> &str) -> Vec<String> {
+        let mut kept: Vec<String> = Vec::new();
+        for value in values {
+            let value = trim(&value).trim();
+            if !value.is_empty() && !kept.iter().any(|known| known.eq_ignore_ascii_case(value)) {
+                kept.push(value.to_string());
+            }
+        }
+        kept
+    }
+    let text = |value: Option<String>| {
+        value
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    if metadata.fields.keys().any(|key| key.trim().is_empty()) {
+        return Err(TaunoteError::InvalidInput(
+            "Metadata fields need a name".to_string(),
+        ));
+    }
+    if metadata
+        .duration_seconds
+        .is_some_and(|seconds| !seconds.is_finite() || seconds < 0.0)
+    {
+        return Err(TaunoteError::InvalidInput(
+            "The duration must be a positive number of seconds".to_string(),
+        ));
+    }
+    Ok(ProjectMetadata {
+        tags: unique(metadata.tags, |tag| tag.trim().trim_start_matches('#')),
+        description: text(metadata.description),
+        participants: unique(metadata.participants, str::trim),
+        duration_seconds: metadata.duration_seconds,
+        source_device: text(metadata.source_device),
+        fields: metadata
+            .fields
+            .into_iter()
+            .map(|(key, value)| (key.trim().to_string(), value))
+            .collect(),
+    })
+}
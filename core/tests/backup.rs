@@ -0,0 +1,256 @@
+mod support;
+
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::{
+    fs::{self, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+use support::library;
+use taunote_core::{
+    services::{
+        backup::manifest::{BackupKind, MANIFEST_FILE},
+        database::models::{AudioProject, ProjectType},
+        notes::generate::save_transcript,
+    },
+    NewProject, Taunote,
+};
+
+fn add_project(taunote: &Taunote, name: &str, transcript: &str) -> AudioProject {
+    let project = taunote
+        .create_project(NewProject {
+            group_id: "team".to_string(),
+            name: name.to_string(),
+            project_type: ProjectType::Meeting,
+            language: None,
+        })
+        .unwrap();
+    save_transcript(
+        &mut taunote.connect().unwrap(),
+        taunote.base_dir(),
+        &project,
+        transcript,
+        "whisperx",
+        None,
+    )
+    .unwrap();
+    project
+}
+
+// Same archive with `change` applied to the entry `name`
+fn rewrite(archive: &Path, dest: &Path, entry_name: &str, change: fn(&mut Vec<u8>)) {
+    let mut source = tar::Archive::new(GzDecoder::new(File::open(archive).unwrap()));
+    let mut out = tar::Builder::new(GzEncoder::new(
+        File::create(dest).unwrap(),
+        Compression::default(),
+    ));
+    for entry in source.entries().unwrap() {
+        let mut entry = entry.unwrap();
+        let name = entry.path().unwrap().to_string_lossy().into_owned();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        if name == entry_name {
+            change(&mut data);
+        }
+        let mut header = entry.header().clone();
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        out.append_data(&mut header, &name, data.as_slice())
+            .unwrap();
+    }
+    out.into_inner().unwrap().finish().unwrap();
+}
+
+#[test]
+fn full_backup_restores_into_an_empty_data_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(&dir.path().join("data"));
+    let project = add_project(
+        &taunote,
+        "Weekly sync",
+        "Alice: the release moves to friday",
+    );
+
+    let backups = dir.path().join("backups");
+    fs::create_dir_all(&backups).unwrap();
+    let report = taunote.backup(&backups, None).unwrap();
+    assert!(report.path.starts_with(&backups));
+    assert!(report.path.to_string_lossy().ends_with(".tar.gz"));
+    assert_eq!(report.manifest.kind, BackupKind::Full);
+    assert!(!report.manifest.encrypted);
+    assert!(report.manifest.file("db/project.db").is_some());
+    assert_eq!(report.manifest.stored.len(), report.manifest.files.len());
+    let archives = vec![report.path.clone()];
+    assert_eq!(
+        taunote.verify_backup(&archives).unwrap().id,
+        report.manifest.id
+    );
+
+    let restored = library(&dir.path().join("restored"));
+    let restore = restored.restore(&archives).unwrap();
+    assert_eq!(restore.backup_id, report.manifest.id);
+    assert_eq!(restore.files, report.manifest.files.len());
+    // nothing was there to move aside but the empty database init() created
+    assert!(restore.previous_data.is_some());
+
+    assert_eq!(restored.project(&project.id).unwrap().name, "Weekly sync");
+    let notes = restored.notes(&project.id).unwrap();
+    assert_eq!(notes[0].content, "Alice: the release moves to friday");
+    assert!(restored.project_dir(&project).unwrap().is_dir());
+    assert_eq!(restored.search("release", 10).unwrap().len(), 1);
+}
+
+#[test]
+fn incremental_backups_store_only_changed_files_and_restore_as_a_chain() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(&dir.path().join("data"));
+    let first = add_project(&taunote, "Kickoff", "Bob: we start monday");
+    let full = taunote
+        .backup(&dir.path().join("full.tar.gz"), None)
+        .unwrap();
+
+    let second = add_project(&taunote, "Review", "Carol: the budget is approved");
+    let incremental_path = dir.path().join("incremental.tar.gz");
+    let incremental = taunote
+        .backup(&incremental_path, Some(&full.path))
+        .unwrap()
+        .manifest;
+    assert_eq!(incremental.kind, BackupKind::Incremental);
+    assert_eq!(
+        incremental.base_id.as_deref(),
+        Some(full.manifest.id.as_str())
+    );
+    assert!(incremental.files.len() > full.manifest.files.len());
+    assert!(incremental.stored.contains(&"db/project.db".to_string()));
+    // the first project's transcript is unchanged and only in the full backup
+    assert!(incremental
+        .stored
+        .iter()
+        .all(|path| !path.contains(&first.relative_path)));
+    assert!(incremental
+        .stored
+        .iter()
+        .any(|path| path.contains(&second.relative_path)));
+
+    let chain = vec![full.path.clone(), incremental_path.clone()];
+    assert_eq!(taunote.verify_backup(&chain).unwrap().id, incremental.id);
+    let restored = library(&dir.path().join("restored"));
+    restored.restore(&chain).unwrap();
+    assert_eq!(restored.list_projects(None).unwrap().len(), 2);
+    assert_eq!(
+        restored.notes(&first.id).unwrap()[0].content,
+        "Bob: we start monday"
+    );
+    assert_eq!(
+        restored.notes(&second.id).unwrap()[0].content,
+        "Carol: the budget is approved"
+    );
+
+    // no backup, an incremental backup alone, or in the wrong order, is refused
+    for chain in [
+        vec![],
+        vec![incremental_path.clone()],
+        vec![incremental_path.clone(), full.path.clone()],
+    ] {
+        assert_eq!(
+            restored.verify_backup(&chain).err().unwrap().code(),
+            "invalid_input"
+        );
+    }
+}
+
+#[test]
+fn damaged_archives_are_refused_before_anything_is_replaced() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(&dir.path().join("data"));
+    let project = add_project(
+        &taunote,
+        "Weekly sync",
+        "Alice: the release moves to friday",
+    );
+    let archive = dir.path().join("backup.tar.gz");
+    let manifest = taunote.backup(&archive, None).unwrap().manifest;
+    let note = manifest
+        .files
+        .iter()
+        .find(|file| file.path.contains(&project.relative_path))
+        .unwrap()
+        .path
+        .clone();
+
+    let tampered = dir.path().join("tampered.tar.gz");
+    rewrite(&archive, &tampered, &format!("data/{note}"), |data| {
+        data[0] ^= 1
+    });
+    let without_manifest = dir.path().join("without-manifest.tar.gz");
+    rewrite(&archive, &without_manifest, MANIFEST_FILE, |data| {
+        data.clear();
+        data.extend_from_slice(b"{}")
+    });
+
+    let other = library(&dir.path().join("other"));
+    add_project(&other, "Keep me", "Dan: nothing to restore");
+    for bad in [tampered, without_manifest, PathBuf::from("missing.tar.gz")] {
+        assert!(other.restore(&[bad]).is_err());
+    }
+    assert_eq!(other.list_projects(None).unwrap()[0].name, "Keep me");
+    assert!(!other.base_dir().read_dir().unwrap().any(|entry| entry
+        .unwrap()
+        .file_name()
+        .to_string_lossy()
+        .starts_with("pre-restore-")));
+}
+
+#[test]
+fn restore_moves_the_previous_library_aside() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(&dir.path().join("data"));
+    add_project(&taunote, "Backed up", "Alice: this is in the backup");
+    let archive = dir.path().join("backup.tar.gz");
+    taunote.backup(&archive, None).unwrap();
+
+    let later = add_project(&taunote, "Not backed up", "Bob: this came later");
+    let report = taunote.restore(&[archive]).unwrap();
+    assert!(taunote.project(&later.id).is_err());
+    assert_eq!(taunote.list_projects(None).unwrap().len(), 1);
+
+    let previous = Taunote::new(report.previous_data.unwrap());
+    assert_eq!(previous.project(&later.id).unwrap().name, "Not backed up");
+    assert_eq!(
+        previous.notes(&later.id).unwrap()[0].content,
+        "Bob: this came later"
+    );
+}
+
+#[test]
+fn a_restore_that_fails_halfway_puts_the_library_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(&dir.path().join("data"));
+    let project = add_project(&taunote, "Weekly sync", "Alice: keep this");
+    let archive = dir.path().join("backup.tar.gz");
+    taunote.backup(&archive, None).unwrap();
+
+    // groups cannot be moved aside onto a folder that is not empty, after db was
+    let now = chrono::Utc::now();
+    for seconds in 0..5 {
+        let at = now + chrono::Duration::seconds(seconds);
+        let taken = taunote
+            .base_dir()
+            .join(format!("pre-restore-{}", at.format("%Y%m%d-%H%M%S")))
+            .join("groups");
+        fs::create_dir_all(taken.join("in-the-way")).unwrap();
+    }
+    assert!(taunote.restore(&[archive]).is_err());
+
+    assert_eq!(taunote.project(&project.id).unwrap().name, "Weekly sync");
+    assert_eq!(
+        taunote.notes(&project.id).unwrap()[0].content,
+        "Alice: keep this"
+    );
+    assert!(taunote.project_dir(&project).unwrap().is_dir());
+    assert!(!taunote.base_dir().read_dir().unwrap().any(|entry| entry
+        .unwrap()
+        .file_name()
+        .to_string_lossy()
+        .starts_with(".restore-")));
+}
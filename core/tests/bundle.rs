@@ -0,0 +1,235 @@
+mod support;
+
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+use support::library;
+use taunote_core::{
+    services::{
+        bundle::{
+            import::{read_bundle, GroupCollision, ImportOptions},
+            manifest::MANIFEST_FILE,
+        },
+        database::{
+            models::{AudioProject, ProjectType, VersionSource},
+            versions::{list_versions, record_user_edit},
+        },
+        notes::generate::save_transcript,
+        sync::markdown::note_file_path,
+    },
+    NewProject, Taunote,
+};
+use zip::{write::SimpleFileOptions, ZipArchive, ZipWriter};
+
+// A project with a recording, transcript segments and an edited transcript
+fn shared_project(taunote: &Taunote, dir: &Path) -> AudioProject {
+    let project = taunote
+        .create_project(NewProject {
+            group_id: "team".to_string(),
+            name: "Vendor call".to_string(),
+            project_type: ProjectType::Meeting,
+            language: Some("en".to_string()),
+        })
+        .unwrap();
+    let recording = dir.join("call.wav");
+    fs::write(&recording, b"RIFF not really audio").unwrap();
+    taunote.import_audio(&project, &recording).unwrap();
+    let segments = taunote
+        .project_dir(&project)
+        .unwrap()
+        .join("audio/segments.json");
+    fs::create_dir_all(segments.parent().unwrap()).unwrap();
+    fs::write(&segments, r#"{"segments": [{"speaker": "SPEAKER_00"}]}"#).unwrap();
+
+    let mut conn = taunote.connect().unwrap();
+    save_transcript(
+        &mut conn,
+        taunote.base_dir(),
+        &project,
+        "SPEAKER_00: the invoice is due friday",
+        "whisperx",
+        None,
+    )
+    .unwrap();
+    record_user_edit(
+        &mut conn,
+        &project.id,
+        "transcript",
+        "Alice: the invoice is due friday",
+    )
+    .unwrap();
+    taunote.project(&project.id).unwrap()
+}
+
+// Copy of a bundle with `change` applied to every entry
+fn rewrite(bundle: &Path, dest: &Path, change: impl Fn(&str, &mut Vec<u8>)) {
+    let mut source = ZipArchive::new(File::open(bundle).unwrap()).unwrap();
+    let mut out = ZipWriter::new(File::create(dest).unwrap());
+    for index in 0..source.len() {
+        let mut entry = source.by_index(index).unwrap();
+        let name = entry.name().to_string();
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).unwrap();
+        change(&name, &mut data);
+        out.start_file(name.as_str(), SimpleFileOptions::default())
+            .unwrap();
+        out.write_all(&data).unwrap();
+    }
+    out.finish().unwrap();
+}
+
+#[test]
+fn exported_projects_import_with_new_ids_and_their_history() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = library(&dir.path().join("alice"));
+    let project = shared_project(&source, dir.path());
+
+    let bundle = source.export_bundle(&project.id, dir.path()).unwrap();
+    assert_eq!(bundle, dir.path().join("Vendor call.taunote"));
+    let manifest = read_bundle(&bundle).unwrap();
+    assert_eq!(manifest.project.id, project.id);
+    assert_eq!(manifest.group.name, "team");
+    assert_eq!(manifest.versions.len(), 2);
+    assert!(manifest.media.is_some());
+    // notes travel in the manifest, not as files
+    assert!(manifest
+        .files
+        .iter()
+        .all(|file| !file.path.ends_with(".md")));
+    assert!(manifest.file("project/audio/segments.json").is_some());
+
+    let target = library(&dir.path().join("bob"));
+    let report = target
+        .import_bundle(&bundle, &ImportOptions::default())
+        .unwrap();
+    let imported = report.project;
+    assert_ne!(imported.id, project.id);
+    assert_eq!(report.original_id, project.id);
+    assert!(report.created_group);
+    assert_eq!(report.versions, 2);
+    assert_eq!(imported.name, "Vendor call");
+    assert_eq!(imported.language, "en");
+    assert_eq!(imported.date, project.date);
+    assert_eq!(imported.media_hash, project.media_hash);
+
+    let versions = list_versions(&target.connect().unwrap(), &imported.id, "transcript").unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0].content, "Alice: the invoice is due friday");
+    assert_eq!(versions[0].source, VersionSource::UserEdit);
+    assert!(versions[0].is_current);
+    assert_eq!(
+        versions[0].parent_id.as_deref(),
+        Some(versions[1].id.as_str())
+    );
+    assert_eq!(
+        fs::read_to_string(note_file_path(target.base_dir(), &imported, "transcript").unwrap())
+            .unwrap(),
+        "Alice: the invoice is due friday"
+    );
+    assert_eq!(
+        target.search("invoice", 10).unwrap()[0].project_id,
+        imported.id
+    );
+
+    let audio = target.original_audio(&imported).unwrap().unwrap();
+    assert!(audio.starts_with(target.base_dir()));
+    assert_eq!(fs::read(audio).unwrap(), b"RIFF not really audio");
+    assert!(target
+        .project_dir(&imported)
+        .unwrap()
+        .join("audio/segments.json")
+        .is_file());
+}
+
+#[test]
+fn group_name_collisions_merge_or_rename() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = library(&dir.path().join("alice"));
+    let project = shared_project(&source, dir.path());
+    let bundle = dir.path().join("call.taunote");
+    source.export_bundle(&project.id, &bundle).unwrap();
+
+    // importing into the same library is a copy, not an overwrite
+    let merged = source
+        .import_bundle(&bundle, &ImportOptions::default())
+        .unwrap();
+    assert!(!merged.created_group);
+    assert_eq!(merged.group.id, "team");
+    assert_eq!(source.list_projects(Some("team")).unwrap().len(), 2);
+
+    let options = ImportOptions {
+        on_collision: GroupCollision::Rename,
+        ..Default::default()
+    };
+    for (name, id) in [("team (2)", "team-2"), ("team (3)", "team-3")] {
+        let renamed = source.import_bundle(&bundle, &options).unwrap();
+        assert!(renamed.created_group);
+        assert_eq!(renamed.group.name, name);
+        assert_eq!(renamed.project.group_id, id);
+    }
+
+    let options = ImportOptions {
+        group: Some("Clients".to_string()),
+        ..Default::default()
+    };
+    let moved = source.import_bundle(&bundle, &options).unwrap();
+    assert_eq!(moved.project.group_id, "clients");
+    assert!(source.project_dir(&moved.project).unwrap().is_dir());
+
+    let options = ImportOptions {
+        group: Some("../outside".to_string()),
+        ..Default::default()
+    };
+    let err = source.import_bundle(&bundle, &options).err().unwrap();
+    assert_eq!(err.code(), "invalid_input");
+}
+
+#[test]
+fn damaged_or_foreign_bundles_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = library(&dir.path().join("alice"));
+    let project = shared_project(&source, dir.path());
+    let bundle = dir.path().join("call.taunote");
+    source.export_bundle(&project.id, &bundle).unwrap();
+
+    let damaged = dir.path().join("damaged.taunote");
+    rewrite(&bundle, &damaged, |name, data| {
+        if name == "project/audio/segments.json" {
+            data.push(b' ');
+        }
+    });
+    let escaping = dir.path().join("escaping.taunote");
+    rewrite(&bundle, &escaping, |name, data| {
+        if name == MANIFEST_FILE {
+            let json = String::from_utf8(data.clone()).unwrap();
+            *data = json
+                .replace("project/audio/segments.json", "../segments.json")
+                .into_bytes();
+        }
+    });
+    let broken_history = dir.path().join("history.taunote");
+    rewrite(&bundle, &broken_history, |name, data| {
+        if name == MANIFEST_FILE {
+            let mut manifest: serde_json::Value = serde_json::from_slice(data).unwrap();
+            manifest["versions"][0]["parent_id"] = "missing".into();
+            *data = serde_json::to_vec(&manifest).unwrap();
+        }
+    });
+    let not_a_bundle = dir.path().join("notes.taunote");
+    fs::write(&not_a_bundle, "plain text").unwrap();
+
+    let target = library(&dir.path().join("bob"));
+    let bundles: [PathBuf; 4] = [damaged, escaping, broken_history, not_a_bundle];
+    for bad in &bundles {
+        let err = target
+            .import_bundle(bad, &ImportOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.code(), "invalid_input", "{}", bad.display());
+    }
+    assert!(target.list_projects(None).unwrap().is_empty());
+    assert!(target.media().unwrap().is_empty());
+    assert!(!target.base_dir().join("groups").exists());
+}
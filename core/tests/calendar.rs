@@ -0,0 +1,316 @@
+mod support;
+
+use chrono::{DateTime, Local, TimeZone, Utc};
+use std::{fs, path::Path};
+use taunote_core::{
+    services::{
+        calendar::ics::parse_ics,
+        database::models::ProjectType,
+        llm::{
+            generation::GenerationParams, llama_queue::JobOptions, llama_server::LlamaServerConfig,
+            prompt_tasks::with_meeting,
+        },
+        notes::generate::save_transcript,
+    },
+    NewProject, NoteKind, Taunote,
+};
+
+const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+PRODID:-//Example//Calendar//EN\r
+BEGIN:VTIMEZONE\r
+TZID:Europe/Berlin\r
+BEGIN:STANDARD\r
+DTSTART:19701025T030000\r
+TZOFFSETFROM:+0200\r
+TZOFFSETTO:+0100\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:review@example.com\r
+DTSTART;TZID=Europe/Berlin:20250131T100000\r
+DTEND;TZID=Europe/Berlin:20250131T110000\r
+SUMMARY:Project review with the\r
+  client\r
+DESCRIPTION:Budget\\nHiring\\, maybe\r
+ORGANIZER;CN=Alice:mailto:alice@example.com\r
+ATTENDEE;CN=Alice;PARTSTAT=ACCEPTED:mailto:alice@example.com\r
+ATTENDEE;CN=Bob:mailto:bob@example.com\r
+ATTENDEE;CN=\"Carol: PhD\";PARTSTAT=DECLINED:mailto:carol@example.com\r
+ATTENDEE;CUTYPE=ROOM;CN=Room 1:mailto:room1@example.com\r
+ATTENDEE:mailto:dave@example.com\r
+CATEGORIES:Clients,Acme\r
+BEGIN:VALARM\r
+ACTION:DISPLAY\r
+DESCRIPTION:Reminder\r
+TRIGGER:-PT10M\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup@example.com\r
+DTSTART;TZID=Europe/Berlin:20250303T093000\r
+DURATION:PT15M\r
+RRULE:FREQ=WEEKLY;BYDAY=MO,TH;UNTIL=20250430T000000Z\r
+EXDATE;TZID=Europe/Berlin:20250306T093000\r
+SUMMARY:Standup\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup@example.com\r
+RECURRENCE-ID;TZID=Europe/Berlin:20250310T093000\r
+DTSTART;TZID=Europe/Berlin:20250310T140000\r
+DTEND;TZID=Europe/Berlin:20250310T141500\r
+SUMMARY:Standup (moved)\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:offsite@example.com\r
+DTSTART;VALUE=DATE:20250131\r
+SUMMARY:Offsite\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:cancelled@example.com\r
+DTSTART:20250131T093000Z\r
+DTEND:20250131T100000Z\r
+STATUS:CANCELLED\r
+SUMMARY:Cancelled\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+fn utc(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value).unwrap().into()
+}
+
+fn library(dir: &Path) -> Taunote {
+    let taunote = support::library(&dir.join("data"));
+    let ics = dir.join("work.ics");
+    fs::write(&ics, CALENDAR).unwrap();
+    let report = taunote.import_calendar(&ics).unwrap();
+    assert_eq!((report.events, report.recurring), (4, 1));
+    taunote
+}
+
+#[test]
+fn ics_events_are_read_with_their_zones_attendees_and_recurrences() {
+    let events = parse_ics(CALENDAR).unwrap();
+    let ids: Vec<&str> = events.iter().map(|event| event.id.as_str()).collect();
+    assert_eq!(
+        ids,
+        [
+            "review@example.com",
+            "standup@example.com",
+            "standup@example.com/2025-03-10T08:30:00Z",
+            "offsite@example.com",
+        ]
+    );
+
+    let review = &events[0];
+    assert_eq!(review.summary, "Project review with the client");
+    assert_eq!(review.starts_at, "2025-01-31T09:00:00Z");
+    assert_eq!(review.ends_at, "2025-01-31T10:00:00Z");
+    assert_eq!(review.timezone.as_deref(), Some("Europe/Berlin"));
+    assert_eq!(review.agenda.as_deref(), Some("Budget\nHiring, maybe"));
+    // declined invitations and rooms are not people in the meeting
+    assert_eq!(review.attendees, ["Alice", "Bob", "dave@example.com"]);
+    assert_eq!(review.categories, ["Clients", "Acme"]);
+
+    let standup = &events[1];
+    assert_eq!(standup.ends_at, "2025-03-03T08:45:00Z");
+    assert_eq!(
+        standup.exdates,
+        ["2025-03-06T08:30:00Z", "2025-03-10T08:30:00Z"]
+    );
+    assert!(events[2].rrule.is_none());
+    assert!(events[3].all_day);
+
+    let err = parse_ics("SUMMARY:not a calendar").err().unwrap();
+    assert_eq!(err.code(), "invalid_input");
+}
+
+#[test]
+fn recordings_match_the_occurrence_they_were_made_in() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let title_at = |at: &str| taunote.find_meeting(utc(at)).unwrap().map(|m| m.title);
+
+    let review = taunote
+        .find_meeting(utc("2025-01-31T09:20:00Z"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(review.event_id, "review@example.com");
+    assert_eq!(review.group(), Some("Clients"));
+    // a recording saved a few minutes after the end still counts
+    assert!(title_at("2025-01-31T10:10:00Z").is_some());
+    assert_eq!(title_at("2025-01-31T10:30:00Z"), None);
+
+    // 09:30 in Berlin is 08:30 UTC in winter and 07:30 UTC in summer
+    let standup = taunote
+        .find_meeting(utc("2025-03-31T07:35:00Z"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(standup.starts_at, "2025-03-31T07:30:00Z");
+    assert_eq!(standup.ends_at, "2025-03-31T07:45:00Z");
+    assert_eq!(title_at("2025-03-13T08:40:00Z").as_deref(), Some("Standup"));
+    // skipped, moved and past the end of the series
+    assert_eq!(title_at("2025-03-06T08:35:00Z"), None);
+    assert_eq!(title_at("2025-03-10T08:35:00Z"), None);
+    assert_eq!(
+        title_at("2025-03-10T13:05:00Z").as_deref(),
+        Some("Standup (moved)")
+    );
+    assert_eq!(title_at("2025-05-05T07:35:00Z"), None);
+
+    let named = Utc
+        .with_ymd_and_hms(2025, 1, 31, 9, 15, 0)
+        .unwrap()
+        .with_timezone(&Local)
+        .format("REC_%Y%m%d_%H%M%S.m4a")
+        .to_string();
+    let recording = dir.path().join(named);
+    fs::write(&recording, b"").unwrap();
+    let matched = taunote.recording_meeting(&recording).unwrap().unwrap();
+    assert_eq!(matched, review);
+
+    // importing the file again updates the events instead of adding them twice
+    taunote
+        .import_calendar(&dir.path().join("work.ics"))
+        .unwrap();
+    assert_eq!(taunote.calendar_events().unwrap().len(), 4);
+}
+
+#[test]
+fn meetings_stay_with_their_project_and_fill_the_templates() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let meeting = taunote
+        .find_meeting(utc("2025-01-31T09:20:00Z"))
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        with_meeting("{meeting}Summarize:\n{transcript}", Some(&meeting)),
+        "Meeting: Project review with the client\n\
+         Attendees, who may be the speakers: Alice, Bob, dave@example.com\n\
+         Agenda:\nBudget\nHiring, maybe\n\nSummarize:\n{transcript}"
+    );
+    assert_eq!(
+        with_meeting("{meeting}Summarize:\n{transcript}", None),
+        "Summarize:\n{transcript}"
+    );
+
+    let project = taunote
+        .create_project(NewProject {
+            group_id: "Clients".to_string(),
+            name: meeting.title.clone(),
+            project_type: ProjectType::Meeting,
+            language: Some("en".to_string()),
+        })
+        .unwrap();
+    assert_eq!(taunote.project_meeting(&project.id).unwrap(), None);
+    taunote.set_project_meeting(&project.id, &meeting).unwrap();
+    assert_eq!(taunote.project_meeting(&project.id).unwrap(), Some(meeting));
+
+    taunote.delete_project(&project.id).unwrap();
+    let err = taunote.project_meeting(&project.id).err().unwrap();
+    assert_eq!(err.code(), "not_found");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn generated_notes_see_the_meeting() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let server = dir.path().join("fake-llama-server");
+    fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/support/fake_llama_server.py"),
+        &server,
+    )
+    .unwrap();
+    fs::set_permissions(&server, fs::Permissions::from_mode(0o755)).unwrap();
+    let model = dir.path().join("test.gguf");
+    fs::write(&model, b"").unwrap();
+    let taunote = library(dir.path()).with_llama_config(LlamaServerConfig {
+        server_path: server,
+        model_path: model,
+        ..Default::default()
+    });
+
+    let project = taunote
+        .create_project(NewProject {
+            group_id: "Clients".to_string(),
+            name: "Review".to_string(),
+            project_type: ProjectType::Meeting,
+            language: Some("en".to_string()),
+        })
+        .unwrap();
+    save_transcript(
+        &mut taunote.connect().unwrap(),
+        taunote.base_dir(),
+        &project,
+        "[SPEAKER_00] the budget is fine",
+        "whisperx",
+        None,
+    )
+    .unwrap();
+    let meeting = taunote
+        .find_meeting(utc("2025-01-31T09:20:00Z"))
+        .unwrap()
+        .unwrap();
+    taunote.set_project_meeting(&project.id, &meeting).unwrap();
+
+    let note = taunote
+        .generate_note(
+            &project.id,
+            NoteKind::Summary,
+            &GenerationParams::default(),
+            JobOptions::default(),
+        )
+        .await
+        .unwrap();
+    // the fake server echoes the first line of the prompt
+    assert_eq!(
+        note.version.content,
+        "GENERATED Meeting: Project review with the client"
+    );
+    assert!(note
+        .version
+        .template
+        .unwrap()
+        .contains("Attendees, who may be the speakers: Alice, Bob"));
+    taunote.shutdown().await;
+}
+
+#[test]
+fn huge_recurrence_intervals_end_the_series_instead_of_overflowing() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path());
+    taunote.init().unwrap();
+    let events: String = ["DAILY", "WEEKLY", "MONTHLY", "YEARLY"]
+        .iter()
+        .map(|frequency| {
+            format!(
+                "BEGIN:VEVENT\r\nUID:{frequency}@example.com\r\n\
+                 DTSTART:20250131T090000Z\r\nDTEND:20250131T100000Z\r\n\
+                 RRULE:FREQ={frequency};INTERVAL=4000000000\r\nSUMMARY:{frequency}\r\n\
+                 END:VEVENT\r\n"
+            )
+        })
+        .collect();
+    let ics = dir.path().join("huge.ics");
+    fs::write(
+        &ics,
+        format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n{events}END:VCALENDAR\r\n"),
+    )
+    .unwrap();
+    assert_eq!(taunote.import_calendar(&ics).unwrap().recurring, 4);
+
+    // only the first occurrence is left
+    assert!(taunote
+        .find_meeting(utc("2025-01-31T09:30:00Z"))
+        .unwrap()
+        .is_some());
+    assert_eq!(
+        taunote.find_meeting(utc("2030-01-31T09:30:00Z")).unwrap(),
+        None
+    );
+}
@@ -0,0 +1,129 @@
+mod support;
+
+use std::{
+    f32::consts::TAU,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use support::library;
+use taunote_core::services::audio::capture::{InputFormat, Level, NullInput, RecordingFormat};
+
+const FORMAT: InputFormat = InputFormat {
+    sample_rate: 16_000,
+    channels: 2,
+};
+
+// A 440 Hz sine on every channel, interleaved
+fn tone(seconds: f32, amplitude: f32) -> Vec<f32> {
+    let frames = (seconds * FORMAT.sample_rate as f32) as usize;
+    (0..frames)
+        .flat_map(|frame| {
+            let t = frame as f32 / FORMAT.sample_rate as f32;
+            let sample = amplitude * (TAU * 440.0 * t).sin();
+            std::iter::repeat_n(sample, FORMAT.channels as usize)
+        })
+        .collect()
+}
+
+#[test]
+fn recordings_keep_what_was_heard_outside_pauses() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let (input, feed) = NullInput::new(FORMAT);
+    let levels: Arc<Mutex<Vec<Level>>> = Arc::default();
+    let recorder = taunote
+        .start_recording(Box::new(input), RecordingFormat::Wav, {
+            let levels = levels.clone();
+            move |level| levels.lock().unwrap().push(level)
+        })
+        .unwrap();
+    assert_eq!(recorder.input_format(), FORMAT);
+    assert!(recorder.path().starts_with(dir.path().join("recordings")));
+
+    assert!(feed.push(&tone(1.0, 0.5)));
+    recorder.pause();
+    assert!(recorder.is_paused());
+    feed.push(&tone(1.0, 1.0));
+    recorder.resume();
+    feed.push(&tone(0.5, 0.0));
+    let recording = recorder.stop().unwrap();
+    assert!(!feed.push(&tone(0.1, 0.5)), "the input is closed");
+
+    assert_eq!(recording.duration_seconds, 1.5);
+    assert_eq!(recording.sample_rate, 16_000);
+    let wav = hound::WavReader::open(&recording.path).unwrap();
+    assert_eq!(wav.spec().channels, 2);
+    assert_eq!(wav.spec().sample_rate, 16_000);
+    assert_eq!(wav.len(), 48_000);
+    let peak = wav
+        .into_samples::<i16>()
+        .map(|sample| sample.unwrap().unsigned_abs())
+        .max()
+        .unwrap();
+    assert!((16_000..=16_400).contains(&peak), "peak {peak}");
+
+    // a level for every 50 ms, the paused second left out
+    let levels = levels.lock().unwrap();
+    assert_eq!(levels.len(), 30);
+    assert!((levels[0].peak - 0.5).abs() < 0.01);
+    assert!((levels[0].rms - 0.5 / 2f32.sqrt()).abs() < 0.01);
+    assert_eq!(levels[29].peak, 0.0);
+    assert_eq!(levels[29].seconds, 1.5);
+}
+
+#[test]
+fn cancelled_or_dropped_recorders_close_their_input() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+
+    let (input, feed) = NullInput::new(FORMAT);
+    let recorder = taunote
+        .start_recording(Box::new(input), RecordingFormat::Wav, |_| {})
+        .unwrap();
+    let path = recorder.path().to_path_buf();
+    feed.push(&tone(0.2, 0.5));
+    recorder.cancel().unwrap();
+    assert!(!path.exists());
+    assert!(!feed.push(&tone(0.1, 0.5)));
+
+    let (input, feed) = NullInput::new(FORMAT);
+    let recorder = taunote
+        .start_recording(Box::new(input), RecordingFormat::Flac, |_| {})
+        .unwrap();
+    let wav = recorder.path().with_extension("wav");
+    feed.push(&tone(0.2, 0.5));
+    drop(recorder);
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while feed.push(&[]) && Instant::now() < deadline {
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(!feed.push(&[]));
+    // what was recorded is kept until someone decides about it
+    assert_eq!(hound::WavReader::open(&wav).unwrap().len(), 6_400);
+}
+
+#[cfg(not(feature = "capture"))]
+#[test]
+fn builds_without_capture_cannot_record() {
+    use taunote_core::services::audio::microphone::{input_devices, Microphone};
+
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    assert_eq!(input_devices().unwrap_err().code(), "config");
+    let err = taunote
+        .start_recording(
+            Box::new(Microphone::new(None)),
+            RecordingFormat::Wav,
+            |_| {},
+        )
+        .err()
+        .unwrap();
+    assert_eq!(err.code(), "config");
+    assert_eq!(
+        std::fs::read_dir(dir.path().join("recordings"))
+            .unwrap()
+            .count(),
+        0
+    );
+}
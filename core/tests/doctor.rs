@@ -0,0 +1,52 @@
+use std::fs;
+use taunote_core::services::{
+    doctor::checks::{check_environment, Check, CheckStatus, EnvironmentReport},
+    llm::llama_server::LlamaServerConfig,
+};
+
+fn check<'a>(report: &'a EnvironmentReport, name: &str) -> &'a Check {
+    report
+        .checks
+        .iter()
+        .find(|check| check.name == name)
+        .unwrap_or_else(|| panic!("no {name} check in the report"))
+}
+
+#[test]
+fn missing_llama_server_and_model_are_errors_with_fixes() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = LlamaServerConfig {
+        server_path: dir.path().join("llama-server"),
+        model_path: dir.path().join("model.gguf"),
+        ..Default::default()
+    };
+    let report = check_environment(&config, dir.path());
+
+    for name in ["llama-server", "model"] {
+        let check = check(&report, name);
+        assert_eq!(check.status, CheckStatus::Error);
+        assert!(check.fix.is_some());
+    }
+    assert!(!report.is_ready());
+}
+
+#[test]
+fn model_must_be_a_gguf_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let bogus = dir.path().join("bogus.gguf");
+    fs::write(&bogus, b"not a model").unwrap();
+    let valid = dir.path().join("valid.gguf");
+    fs::write(&valid, b"GGUF\x03\x00\x00\x00").unwrap();
+
+    let report = |model_path| {
+        check_environment(
+            &LlamaServerConfig {
+                model_path,
+                ..Default::default()
+            },
+            dir.path(),
+        )
+    };
+    assert_eq!(check(&report(bogus), "model").status, CheckStatus::Error);
+    assert_eq!(check(&report(valid), "model").status, CheckStatus::Ok);
+}
@@ -0,0 +1,148 @@
+use std::fs;
+use taunote_core::{
+    services::crypto::{
+        keys::{is_encrypted, KdfParams, Keys},
+        vault,
+    },
+    Taunote, TaunoteError,
+};
+
+// Cheap enough for tests, the real defaults take a noticeable moment
+const TEST_KDF: KdfParams = KdfParams {
+    m_cost: 64,
+    t_cost: 1,
+    p_cost: 1,
+};
+
+#[test]
+fn keys_unlock_only_with_the_same_passphrase() {
+    let (key_file, keys) = Keys::create("correct horse", TEST_KDF).unwrap();
+    let ciphertext = keys.encrypt(b"meeting notes").unwrap();
+    assert!(is_encrypted(&ciphertext));
+
+    let unlocked = Keys::unlock(&key_file, "correct horse").unwrap();
+    assert_eq!(unlocked.decrypt(&ciphertext).unwrap(), b"meeting notes");
+    assert_eq!(unlocked.to_hex(), keys.to_hex());
+    let restored = Keys::from_hex(&key_file, &keys.to_hex()).unwrap();
+    assert_eq!(restored.decrypt(&ciphertext).unwrap(), b"meeting notes");
+
+    let err = Keys::unlock(&key_file, "wrong horse").err().unwrap();
+    assert_eq!(err.code(), "locked");
+    assert!(matches!(
+        Keys::create("", TEST_KDF).err().unwrap(),
+        TaunoteError::InvalidInput(_)
+    ));
+
+    let mut tampered = ciphertext.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert!(keys.decrypt(&tampered).is_err());
+    assert!(keys.decrypt(b"plain text").is_err());
+}
+
+#[test]
+fn notes_are_encrypted_on_disk_once_unlocked() {
+    let dir = tempfile::tempdir().unwrap();
+    let (key_file, keys) = Keys::create("passphrase", TEST_KDF).unwrap();
+    key_file.save(dir.path()).unwrap();
+    let path = dir.path().join("summary.md.enc");
+
+    // every access fails until the data directory is unlocked
+    assert!(vault::is_encrypted_dir(dir.path()));
+    assert_eq!(vault::note_extension(dir.path()), "md.enc");
+    let taunote = Taunote::new(dir.path());
+    assert!(taunote.is_locked());
+    assert_eq!(taunote.connect().err().unwrap().code(), "locked");
+    assert_eq!(
+        vault::write_note(dir.path(), &path, "secret")
+            .err()
+            .unwrap()
+            .code(),
+        "locked"
+    );
+
+    vault::insert(dir.path(), keys);
+    assert!(!taunote.is_locked());
+    vault::write_note(dir.path(), &path, "Alice: the budget is secret").unwrap();
+    let raw = fs::read(&path).unwrap();
+    assert!(is_encrypted(&raw));
+    assert!(!String::from_utf8_lossy(&raw).contains("budget"));
+    assert_eq!(
+        vault::read_note(dir.path(), &path).unwrap(),
+        "Alice: the budget is secret"
+    );
+
+    // plaintext files written before encryption can still be read
+    let plain = dir.path().join("email.md");
+    fs::write(&plain, "Hi team").unwrap();
+    assert_eq!(vault::read_note(dir.path(), &plain).unwrap(), "Hi team");
+
+    taunote.unlock("passphrase").unwrap();
+    assert_eq!(taunote.unlock("other").err().unwrap().code(), "locked");
+    vault::remove(dir.path());
+    assert!(taunote.is_locked());
+}
+
+#[cfg(not(feature = "encryption"))]
+#[test]
+fn encrypting_needs_the_encryption_feature() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path());
+    taunote.init().unwrap();
+
+    let err = taunote.encrypt("passphrase").err().unwrap();
+    assert!(matches!(err, TaunoteError::Config(_)));
+    assert!(!taunote.is_encrypted());
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn encrypted_data_directory_keeps_working_after_unlock() {
+    use taunote_core::{
+        services::{database::models::ProjectType, notes::generate::save_transcript},
+        NewProject,
+    };
+
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path());
+    taunote.init().unwrap();
+    let project = taunote
+        .create_project(NewProject {
+            group_id: "team".to_string(),
+            name: "Budget review".to_string(),
+            project_type: ProjectType::Meeting,
+            language: None,
+        })
+        .unwrap();
+    save_transcript(
+        &mut taunote.connect().unwrap(),
+        taunote.base_dir(),
+        &project,
+        "Alice: the budget grows next quarter",
+        "whisperx",
+        None,
+    )
+    .unwrap();
+
+    let report = taunote.encrypt("passphrase").unwrap();
+    assert!(report.database.is_some());
+    assert_eq!(report.note_files, 1);
+    let project_dir = taunote.project_dir(&project).unwrap();
+    assert!(!project_dir.join("transcript.md").exists());
+    assert!(is_encrypted(
+        &fs::read(project_dir.join("transcript.md.enc")).unwrap()
+    ));
+    let database = fs::read(dir.path().join("db").join("project.db")).unwrap();
+    assert!(!database.starts_with(b"SQLite format 3"));
+
+    // a fresh process has to unlock first
+    vault::remove(dir.path());
+    assert_eq!(taunote.init().err().unwrap().code(), "locked");
+    taunote.unlock("passphrase").unwrap();
+    taunote.init().unwrap();
+    assert_eq!(taunote.search("budget", 10).unwrap().len(), 1);
+    assert_eq!(
+        taunote.notes(&project.id).unwrap()[0].content,
+        "Alice: the budget grows next quarter"
+    );
+}
@@ -0,0 +1,192 @@
+mod support;
+
+use rusqlite::Connection;
+use std::fs;
+use support::library;
+use taunote_core::{
+    services::database::{
+        groups::group_path,
+        models::{AudioProject, ProjectType},
+        queries::insert_audio_project,
+        schema::db_path,
+    },
+    NewProject,
+};
+
+fn new_project(group: &str, name: &str) -> NewProject {
+    NewProject {
+        group_id: group.to_string(),
+        name: name.to_string(),
+        project_type: ProjectType::Meeting,
+        language: None,
+    }
+}
+
+#[test]
+fn group_paths_create_nested_groups_and_folders_use_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+
+    let kickoff = taunote
+        .create_project(new_project("Clients/Acme", "Kickoff"))
+        .unwrap();
+    let acme = taunote.group("Clients/Acme").unwrap();
+    let clients = taunote.group("Clients").unwrap();
+    assert_eq!(kickoff.group_id, acme.id);
+    assert_eq!(acme.parent_id.as_deref(), Some(clients.id.as_str()));
+    assert_eq!(clients.parent_id, None);
+    assert_eq!(
+        kickoff.relative_path,
+        format!("groups/{}/{}", acme.id, kickoff.id)
+    );
+    assert!(dir.path().join(&kickoff.relative_path).is_dir());
+
+    // the same name elsewhere in the tree is another group with its own id
+    taunote
+        .create_project(new_project("Internal / Acme", "Retro"))
+        .unwrap();
+    let other = taunote.group("Internal/Acme").unwrap();
+    assert_ne!(other.id, acme.id);
+    let groups = taunote.list_groups().unwrap();
+    assert_eq!(groups.len(), 4);
+    assert_eq!(group_path(&groups, &other.id), "Internal/Acme");
+
+    // ids work as well as paths, and existing groups are reused
+    let again = taunote
+        .create_project(new_project(&acme.id, "Review"))
+        .unwrap();
+    assert_eq!(again.group_id, acme.id);
+    assert_eq!(
+        taunote.list_projects(Some("Clients/Acme")).unwrap().len(),
+        2
+    );
+    assert!(taunote.list_projects(Some("Clients")).unwrap().is_empty());
+    assert_eq!(
+        taunote
+            .list_projects(Some("Clients/Nope"))
+            .unwrap_err()
+            .code(),
+        "not_found"
+    );
+
+    let err = taunote
+        .create_project(new_project("Clients/../outside", "Escape"))
+        .unwrap_err();
+    assert_eq!(err.code(), "invalid_input");
+    assert_eq!(taunote.list_groups().unwrap().len(), 4);
+}
+
+#[test]
+fn moving_a_project_moves_its_folder_with_the_database() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let project = taunote
+        .create_project(new_project("team", "Standup"))
+        .unwrap();
+    fs::write(
+        dir.path()
+            .join(&project.relative_path)
+            .join("transcript.md"),
+        "hi",
+    )
+    .unwrap();
+
+    let moved = taunote.move_project(&project.id, "Archive/2025").unwrap();
+    let archive = taunote.group("Archive/2025").unwrap();
+    assert_eq!(moved.group_id, archive.id);
+    assert_eq!(
+        fs::read_to_string(dir.path().join(&moved.relative_path).join("transcript.md")).unwrap(),
+        "hi"
+    );
+    assert!(!dir.path().join(&project.relative_path).exists());
+
+    // a folder in the way leaves both the project and the files where they were
+    let blocked = dir.path().join("groups/team").join(&project.id);
+    fs::create_dir_all(&blocked).unwrap();
+    let err = taunote.move_project(&project.id, "team").unwrap_err();
+    assert_eq!(err.code(), "invalid_input");
+    let unchanged = taunote.project(&project.id).unwrap();
+    assert_eq!(unchanged.relative_path, moved.relative_path);
+    assert!(dir
+        .path()
+        .join(&moved.relative_path)
+        .join("transcript.md")
+        .is_file());
+
+    // moving into a group that does not exist yet creates it, or nothing at all
+    let err = taunote.move_project(&project.id, "New/..").unwrap_err();
+    assert_eq!(err.code(), "invalid_input");
+    assert!(taunote.group("New").is_err());
+}
+
+#[test]
+fn groups_are_renamed_nested_and_deleted_without_touching_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let project = taunote
+        .create_project(new_project("Clients/Acme", "Kickoff"))
+        .unwrap();
+    let acme = taunote.group("Clients/Acme").unwrap();
+
+    let moved = taunote.move_group(&acme.id, Some("Archive")).unwrap();
+    assert_eq!(moved.parent_id, Some(taunote.group("Archive").unwrap().id));
+    let renamed = taunote.rename_group("Archive/Acme", "Acme Corp").unwrap();
+    assert_eq!(renamed.id, acme.id);
+    assert_eq!(
+        taunote.project(&project.id).unwrap().relative_path,
+        project.relative_path
+    );
+
+    // a group cannot end up inside itself
+    let err = taunote
+        .move_group("Archive", Some("Archive/Acme Corp"))
+        .unwrap_err();
+    assert_eq!(err.code(), "invalid_input");
+    // nor next to a group of the same name
+    taunote.create_group("Archive/Old").unwrap();
+    let err = taunote
+        .rename_group("Archive/Old", "Acme Corp")
+        .unwrap_err();
+    assert_eq!(err.code(), "invalid_input");
+
+    let err = taunote.delete_group("Archive").unwrap_err();
+    assert_eq!(err.code(), "invalid_input");
+    taunote.delete_group("Archive/Old").unwrap();
+    taunote.move_group(&acme.id, None).unwrap();
+    assert_eq!(taunote.group("Acme Corp").unwrap().id, acme.id);
+    assert_eq!(taunote.group("Clients").unwrap().parent_id, None);
+}
+
+#[test]
+fn projects_need_an_existing_group() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let orphan = AudioProject {
+        id: "orphan".to_string(),
+        group_id: "ghost".to_string(),
+        name: "Orphan".to_string(),
+        relative_path: "groups/ghost/orphan".to_string(),
+        date: "2025-01-31".to_string(),
+        project_type: ProjectType::Meeting,
+        language: "en".to_string(),
+        media_hash: None,
+    };
+    let conn = taunote.connect().unwrap();
+    assert!(insert_audio_project(&conn, &orphan).is_err());
+    drop(conn);
+
+    // connections that do not check foreign keys left projects without their
+    // group in older libraries, migrating brings the group back
+    let conn = Connection::open(db_path(dir.path())).unwrap();
+    insert_audio_project(&conn, &orphan).unwrap();
+    conn.execute_batch(
+        "DROP INDEX project_groups_by_parent;
+         ALTER TABLE project_groups DROP COLUMN parent_id;
+         PRAGMA user_version = 5;",
+    )
+    .unwrap();
+    drop(conn);
+    taunote.init().unwrap();
+    assert_eq!(taunote.group("ghost").unwrap().name, "ghost");
+    assert_eq!(taunote.list_projects(Some("ghost")).unwrap().len(), 1);
+}
@@ -0,0 +1,179 @@
+use std::fs;
+use taunote_core::{
+    services::{
+        config::settings::Settings, database::models::ProjectType, media::store::hash_file,
+        notes::generate::save_transcript,
+    },
+    NewProject, NoteKind, ReprocessOptions, Taunote, TaunoteError,
+};
+
+fn new_project(name: &str) -> NewProject {
+    NewProject {
+        group_id: "team".to_string(),
+        name: name.to_string(),
+        project_type: ProjectType::Meeting,
+        language: None,
+    }
+}
+
+#[test]
+fn created_projects_can_be_searched_and_exported() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path());
+    taunote.init().unwrap();
+
+    let project = taunote.create_project(new_project("Weekly sync")).unwrap();
+    assert!(dir.path().join(&project.relative_path).is_dir());
+    save_transcript(
+        &mut taunote.connect().unwrap(),
+        taunote.base_dir(),
+        &project,
+        "Alice: the release moves to friday",
+        "whisperx",
+        None,
+    )
+    .unwrap();
+
+    let hits = taunote.search("release", 10).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].project_id, project.id);
+    assert_eq!(hits[0].project_name, "Weekly sync");
+    assert!(hits[0].snippet.contains("[release]"));
+
+    let out = dir.path().join("export");
+    let written = taunote.export(&project.id, &out).unwrap();
+    assert_eq!(written, vec![out.join(NoteKind::Transcript.file_name())]);
+    assert_eq!(
+        fs::read_to_string(&written[0]).unwrap(),
+        "Alice: the release moves to friday"
+    );
+}
+
+#[test]
+fn unknown_projects_are_not_found() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path());
+    taunote.init().unwrap();
+
+    let err = taunote.export("missing", dir.path()).unwrap_err();
+    assert!(matches!(err, TaunoteError::NotFound(_)));
+    assert_eq!(err.code(), "not_found");
+}
+
+#[test]
+fn recordings_are_stored_once_and_removed_when_unused() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path());
+    taunote.init().unwrap();
+    let interview = taunote.create_project(new_project("Interview")).unwrap();
+    let copy = taunote
+        .create_project(new_project("Interview copy"))
+        .unwrap();
+    assert_eq!(taunote.original_audio(&interview).unwrap(), None);
+
+    let first = dir.path().join("first.WAV");
+    let second = dir.path().join("second.m4a");
+    fs::write(&first, b"first").unwrap();
+    fs::write(&second, b"second").unwrap();
+
+    let media = taunote.import_audio(&interview, &first).unwrap();
+    assert_eq!(media.hash, hash_file(&first).unwrap());
+    assert_eq!(media.original_name, "first.WAV");
+    let stored = taunote.original_audio(&interview).unwrap().unwrap();
+    assert!(stored.ends_with(format!("media/{}/{}.wav", &media.hash[..2], media.hash)));
+    assert_eq!(
+        taunote.project(&interview.id).unwrap().media_hash,
+        Some(media.hash.clone())
+    );
+
+    // the same bytes imported into another project are not stored again
+    assert_eq!(taunote.import_audio(&copy, &first).unwrap(), media);
+    let listed = taunote.media().unwrap();
+    assert_eq!(listed.len(), 1);
+    assert_eq!(listed[0].projects, 2);
+
+    taunote.import_audio(&interview, &second).unwrap();
+    assert!(stored.exists(), "still used by the copy");
+    taunote.delete_project(&copy.id).unwrap();
+    assert!(!stored.exists());
+    assert_eq!(taunote.media().unwrap().len(), 1);
+    assert!(first.exists() && second.exists());
+}
+
+#[test]
+fn retention_removes_old_recordings_but_keeps_notes() {
+    let dir = tempfile::tempdir().unwrap();
+    let settings = Settings {
+        media_retention_days: Some(0),
+        ..Default::default()
+    };
+    let taunote = Taunote::new(dir.path()).with_settings(&settings);
+    taunote.init().unwrap();
+    let project = taunote.create_project(new_project("Lecture")).unwrap();
+    save_transcript(
+        &mut taunote.connect().unwrap(),
+        taunote.base_dir(),
+        &project,
+        "the lecture",
+        "whisperx",
+        None,
+    )
+    .unwrap();
+    let recording = dir.path().join("lecture.ogg");
+    fs::write(&recording, b"lecture").unwrap();
+    let media = taunote.import_audio(&project, &recording).unwrap();
+
+    let report = taunote.prune_media().unwrap();
+    assert_eq!(report.expired_projects, vec![project.id.clone()]);
+    assert_eq!(report.removed, vec![media]);
+    assert_eq!(report.freed_bytes, 7);
+    assert_eq!(taunote.original_audio(&project).unwrap(), None);
+    assert_eq!(
+        taunote.notes(&project.id).unwrap()[0].content,
+        "the lecture"
+    );
+}
+
+#[tokio::test]
+async fn reprocessing_needs_a_stored_recording_and_segments() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path());
+    taunote.init().unwrap();
+    let project = taunote.create_project(new_project("Interview")).unwrap();
+
+    let err = taunote
+        .reprocess(&project.id, &ReprocessOptions::default())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), "invalid_input");
+
+    let recording = dir.path().join("interview.wav");
+    fs::write(&recording, b"audio").unwrap();
+    taunote.import_audio(&project, &recording).unwrap();
+    let options = ReprocessOptions {
+        diarize_only: true,
+        ..Default::default()
+    };
+    let err = taunote.reprocess(&project.id, &options).await.unwrap_err();
+    assert_eq!(err.code(), "invalid_input");
+    assert!(err.to_string().contains("segments"));
+}
+
+#[tokio::test]
+async fn recordings_that_cannot_be_transcribed_leave_no_project() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path());
+    taunote.init().unwrap();
+    let recording = dir.path().join("broken.wav");
+    fs::write(&recording, b"not audio").unwrap();
+
+    taunote
+        .process_recording(&recording, new_project("Standup"), &Default::default())
+        .await
+        .unwrap_err();
+    assert!(taunote.list_projects(None).unwrap().is_empty());
+    assert!(taunote.media().unwrap().is_empty());
+    let group = dir.path().join("groups").join("team");
+    assert_eq!(fs::read_dir(group).unwrap().count(), 0);
+    assert!(recording.is_file());
+}
@@ -0,0 +1,163 @@
+#![cfg(unix)]
+
+mod support;
+
+use std::{fs, path::Path};
+use support::{fake_llama_server, library};
+use taunote_core::services::llm::{
+    generation::GenerationParams,
+    llama_queue::{JobOptions, JobPriority, JobState, LlmService},
+    llama_server::{wait_until_ready, LlamaServerConfig, LlamaSupervisor, ServerStatus},
+};
+use tokio::time::{sleep, timeout, Duration};
+
+fn config(dir: &Path, args: &str) -> LlamaServerConfig {
+    let model = dir.join("test.gguf");
+    fs::write(&model, b"").unwrap();
+    LlamaServerConfig {
+        server_path: fake_llama_server(dir, args),
+        model_path: model,
+        ..Default::default()
+    }
+}
+
+fn background(label: &str) -> JobOptions {
+    JobOptions {
+        priority: JobPriority::Background,
+        label: label.to_string(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn interactive_jobs_go_first_and_cancelled_ones_never_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let requests = dir.path().join("requests.log");
+    let llm = LlmService::new();
+    llm.start(config(
+        dir.path(),
+        &format!("--requests '{}'", requests.display()),
+    ))
+    .unwrap();
+
+    // the only worker takes the first job and waits with it for the server
+    let first = llm
+        .submit(
+            "first".to_string(),
+            GenerationParams::default(),
+            background("first"),
+        )
+        .unwrap();
+    while llm.snapshot().unwrap().running.is_empty() {
+        sleep(Duration::from_millis(1)).await;
+    }
+    let queued = llm
+        .submit(
+            "queued".to_string(),
+            GenerationParams::default(),
+            background("queued"),
+        )
+        .unwrap();
+    let cancelled = llm
+        .submit(
+            "cancelled".to_string(),
+            GenerationParams::default(),
+            background("cancelled"),
+        )
+        .unwrap();
+    let interactive = llm
+        .submit(
+            "interactive".to_string(),
+            GenerationParams::default(),
+            JobOptions::interactive("interactive"),
+        )
+        .unwrap();
+
+    let snapshot = llm.snapshot().unwrap();
+    let pending: Vec<_> = snapshot
+        .pending
+        .iter()
+        .map(|job| job.label.as_str())
+        .collect();
+    assert_eq!(pending, ["interactive", "queued", "cancelled"]);
+    assert_eq!(snapshot.running[0].state, JobState::Running);
+
+    assert!(llm.cancel(cancelled.id).unwrap());
+    assert!(!llm.cancel(cancelled.id).unwrap());
+    let err = cancelled.wait().await.unwrap_err();
+    assert_eq!(err.code(), "llm");
+    assert!(err.to_string().contains("cancelled"));
+
+    assert_eq!(queued.wait().await.unwrap().text, "GENERATED queued");
+    interactive.wait().await.unwrap();
+    first.wait().await.unwrap();
+    assert_eq!(
+        fs::read_to_string(&requests).unwrap(),
+        "first\ninteractive\nqueued\n"
+    );
+    llm.shutdown().await;
+}
+
+#[tokio::test]
+async fn servers_that_keep_dying_are_given_up_after_max_restarts() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let server = dir.path().join("llama-server");
+    fs::write(&server, "#!/bin/sh\nexit 1\n").unwrap();
+    fs::set_permissions(&server, fs::Permissions::from_mode(0o755)).unwrap();
+    let supervisor = LlamaSupervisor::spawn(LlamaServerConfig {
+        server_path: server,
+        max_restarts: 1,
+        ..Default::default()
+    });
+
+    let mut status = supervisor.subscribe();
+    let mut seen = vec![status.borrow_and_update().clone()];
+    while !matches!(seen.last(), Some(ServerStatus::Failed { .. })) {
+        timeout(Duration::from_secs(20), status.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        seen.push(status.borrow_and_update().clone());
+    }
+    let restarts: Vec<_> = seen
+        .iter()
+        .filter_map(|status| match status {
+            ServerStatus::Restarting { attempt, .. } => Some(*attempt),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(restarts, [1], "{seen:?}");
+    assert!(!seen.iter().any(|s| matches!(s, ServerStatus::Ready { .. })));
+
+    let err = wait_until_ready(&mut supervisor.subscribe())
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), "llm_unavailable");
+    assert!(err.to_string().contains("exited during startup"), "{err}");
+    supervisor.shutdown().await;
+}
+
+#[tokio::test]
+async fn initialising_the_llm_again_keeps_it_running() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(&dir.path().join("data")).with_llama_config(config(dir.path(), ""));
+
+    taunote.start_llm().await.unwrap();
+    // count_tokens waits for the server
+    taunote.llm().count_tokens("ready", false).await.unwrap();
+    let ServerStatus::Ready { port } = taunote.llm().status() else {
+        panic!("not ready: {:?}", taunote.llm().status());
+    };
+    // e.g. setup after a frontend reload
+    taunote.init().unwrap();
+    taunote.start_llm().await.unwrap();
+    assert_eq!(taunote.llm().status(), ServerStatus::Ready { port });
+
+    // starting the service directly a second time is an error, not a panic
+    let err = taunote.llm().start(taunote.llama_config()).unwrap_err();
+    assert_eq!(err.code(), "config");
+    assert_eq!(taunote.llm().status(), ServerStatus::Ready { port });
+    taunote.shutdown().await;
+}
@@ -0,0 +1,201 @@
+mod support;
+
+use std::collections::BTreeMap;
+use support::library;
+use taunote_core::{
+    services::{
+        database::models::{ProjectMetadata, ProjectType, SearchFilter},
+        notes::generate::save_transcript,
+    },
+    NewProject, NoteKind,
+};
+
+fn new_project(group: &str, name: &str, project_type: ProjectType) -> NewProject {
+    NewProject {
+        group_id: group.to_string(),
+        name: name.to_string(),
+        project_type,
+        language: None,
+    }
+}
+
+#[test]
+fn metadata_is_normalized_and_stays_with_the_project() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let project = taunote
+        .create_project(new_project("team", "Planning", ProjectType::Meeting))
+        .unwrap();
+    assert_eq!(
+        taunote.project_metadata(&project.id).unwrap(),
+        ProjectMetadata::default()
+    );
+
+    let stored = taunote
+        .set_project_metadata(
+            &project.id,
+            ProjectMetadata {
+                tags: vec![
+                    "#Budget".to_string(),
+                    " budget ".to_string(),
+                    "Q3".to_string(),
+                    "".to_string(),
+                ],
+                description: Some("  ".to_string()),
+                participants: vec!["Alice".to_string(), "alice".to_string()],
+                duration_seconds: Some(1830.5),
+                source_device: Some(" Zoom H1n ".to_string()),
+                fields: BTreeMap::from([("room".to_string(), "4.01".to_string())]),
+            },
+        )
+        .unwrap();
+    assert_eq!(stored.tags, ["Budget", "Q3"]);
+    assert_eq!(stored.description, None);
+    assert_eq!(stored.participants, ["Alice"]);
+    assert_eq!(stored.source_device.as_deref(), Some("Zoom H1n"));
+    assert_eq!(taunote.project_metadata(&project.id).unwrap(), stored);
+
+    let updated = taunote
+        .update_metadata(&project.id, |metadata| {
+            metadata.tags.retain(|tag| tag != "Q3");
+            metadata.tags.push("hiring".to_string());
+        })
+        .unwrap();
+    assert_eq!(updated.tags, ["Budget", "hiring"]);
+    assert_eq!(updated.fields["room"], "4.01");
+
+    let err = taunote
+        .update_metadata(&project.id, |metadata| {
+            metadata.fields.insert(" ".to_string(), "x".to_string());
+        })
+        .unwrap_err();
+    assert_eq!(err.code(), "invalid_input");
+    // a rejected change leaves the stored metadata alone
+    assert_eq!(taunote.project_metadata(&project.id).unwrap(), updated);
+
+    let moved = taunote
+        .set_project_type(&project.id, ProjectType::Interview)
+        .unwrap();
+    assert_eq!(moved.project_type, ProjectType::Interview);
+
+    taunote.delete_project(&project.id).unwrap();
+    assert_eq!(
+        taunote.project_metadata(&project.id).unwrap_err().code(),
+        "not_found"
+    );
+    assert!(taunote.tags().unwrap().is_empty());
+}
+
+#[test]
+fn projects_are_found_by_tags_type_participants_and_date() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let standup = taunote
+        .create_project(new_project("team", "Standup", ProjectType::Meeting))
+        .unwrap();
+    let lecture = taunote
+        .create_project(new_project("school", "Algebra", ProjectType::Lecture))
+        .unwrap();
+    for (project, transcript) in [
+        (&standup, "the release moves to friday"),
+        (
+            &lecture,
+            "a group is a set with an operation, release the chalk",
+        ),
+    ] {
+        save_transcript(
+            &mut taunote.connect().unwrap(),
+            taunote.base_dir(),
+            project,
+            transcript,
+            "whisperx",
+            None,
+        )
+        .unwrap();
+    }
+    taunote
+        .update_metadata(&standup.id, |metadata| {
+            metadata.tags = vec!["work".to_string(), "weekly".to_string()];
+            metadata.participants = vec!["Alice Smith".to_string()];
+        })
+        .unwrap();
+    taunote
+        .update_metadata(&lecture.id, |metadata| {
+            metadata.tags = vec!["Work".to_string()];
+        })
+        .unwrap();
+
+    let found = |query: &str, filter: SearchFilter| -> Vec<String> {
+        taunote
+            .search_filtered(query, &filter, 10)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.project_name)
+            .collect()
+    };
+    assert_eq!(found("release", SearchFilter::default()).len(), 2);
+    assert_eq!(
+        found(
+            "release",
+            SearchFilter {
+                project_type: Some(ProjectType::Lecture),
+                ..Default::default()
+            }
+        ),
+        ["Algebra"]
+    );
+    // tags ignore case and all of them are required
+    let tagged = |tags: &[&str]| SearchFilter {
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+        ..Default::default()
+    };
+    assert_eq!(found("", tagged(&["WORK"])).len(), 2);
+    assert_eq!(found("", tagged(&["work", "weekly"])), ["Standup"]);
+    assert_eq!(
+        found(
+            "",
+            SearchFilter {
+                participant: Some("smith".to_string()),
+                group_id: Some("team".to_string()),
+                ..Default::default()
+            }
+        ),
+        ["Standup"]
+    );
+    let past = SearchFilter {
+        to: Some("2000-12".to_string()),
+        ..Default::default()
+    };
+    assert!(found("release", past).is_empty());
+
+    let tags = taunote.tags().unwrap();
+    let counts: Vec<(&str, u32)> = tags
+        .iter()
+        .map(|count| (count.tag.as_str(), count.projects))
+        .collect();
+    assert_eq!(counts, [("work", 2), ("weekly", 1)]);
+}
+
+#[test]
+fn the_project_type_picks_the_default_notes() {
+    assert_eq!(
+        NoteKind::defaults(ProjectType::Meeting),
+        [NoteKind::Summary, NoteKind::Email]
+    );
+    assert_eq!(
+        NoteKind::defaults(ProjectType::Lecture),
+        [NoteKind::LectureNotes]
+    );
+    assert_eq!(
+        NoteKind::defaults(ProjectType::VoiceMemo),
+        [NoteKind::Summary]
+    );
+    assert_eq!(
+        ProjectType::parse("voice_memo"),
+        Some(ProjectType::VoiceMemo)
+    );
+    assert_eq!(
+        serde_json::from_str::<ProjectType>("\"podcast\"").unwrap(),
+        ProjectType::Other
+    );
+}
@@ -0,0 +1,248 @@
+mod support;
+
+use rusqlite::{params, Connection};
+use std::{fs, path::Path};
+use support::fake_llama_server;
+use taunote_core::services::{
+    database::{
+        models::{AudioProject, ProjectType, TokenUsage},
+        queries::{ensure_project_group, insert_audio_project},
+        schema::init_db,
+        versions::current_version,
+    },
+    llm::{
+        generation::GenerationParams,
+        llama_queue::{JobOptions, LlmService},
+        llama_server::LlamaServerConfig,
+        prompt_tasks::Generated,
+    },
+    notes::{
+        generate::{generate_note, save_generated_note, save_transcript},
+        kinds::NoteKind,
+    },
+};
+use tempfile::TempDir;
+
+const TRANSCRIPT: &str = "Alice: we ship on friday\nBob: I will write the release notes\n";
+
+fn setup() -> (TempDir, Connection, AudioProject) {
+    let dir = tempfile::tempdir().unwrap();
+    init_db(dir.path()).unwrap();
+    let mut conn = Connection::open(dir.path().join("db").join("project.db")).unwrap();
+    let project = AudioProject {
+        id: "project-1".to_string(),
+        group_id: "default".to_string(),
+        name: "standup".to_string(),
+        relative_path: "groups/default/standup".to_string(),
+        date: "2025-01-01T00:00:00Z".to_string(),
+        project_type: ProjectType::Meeting,
+        language: "en".to_string(),
+        media_hash: None,
+    };
+    ensure_project_group(&conn, &project.group_id, &project.group_id).unwrap();
+    insert_audio_project(&conn, &project).unwrap();
+    save_transcript(
+        &mut conn,
+        dir.path(),
+        &project,
+        TRANSCRIPT,
+        "whisperx",
+        None,
+    )
+    .unwrap();
+    (dir, conn, project)
+}
+
+fn fake_output(text: &str) -> Generated {
+    Generated {
+        text: text.to_string(),
+        usage: TokenUsage::default(),
+        template: "{transcript}".to_string(),
+        model: Some("test.gguf".to_string()),
+        params: GenerationParams::default(),
+    }
+}
+
+fn assert_transcript_intact(conn: &Connection, base: &Path, project: &AudioProject) {
+    let on_disk =
+        fs::read_to_string(base.join(&project.relative_path).join("transcript.md")).unwrap();
+    assert_eq!(on_disk, TRANSCRIPT);
+    let stored = current_version(conn, &project.id, "transcript")
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored.content, TRANSCRIPT);
+    let indexed: String = conn
+        .query_row(
+            "SELECT transcript FROM project_notes WHERE project_id = ?1",
+            params![project.id],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(indexed, TRANSCRIPT);
+}
+
+#[test]
+fn generated_kinds_have_their_own_files() {
+    let transcript_file = NoteKind::Transcript.file_name();
+    let mut files: Vec<String> = NoteKind::generated().map(|kind| kind.file_name()).collect();
+    assert!(!files.contains(&transcript_file));
+    files.sort();
+    files.dedup();
+    assert_eq!(files.len(), NoteKind::generated().count());
+}
+
+#[test]
+fn saving_generated_notes_never_overwrites_the_transcript() {
+    let (dir, mut conn, project) = setup();
+
+    for kind in NoteKind::generated() {
+        let text = format!("{kind} output");
+        let note = save_generated_note(&mut conn, dir.path(), &project, kind, &fake_output(&text))
+            .unwrap();
+        assert_eq!(
+            note.file_path.file_name().unwrap().to_str().unwrap(),
+            kind.file_name()
+        );
+        assert_eq!(fs::read_to_string(&note.file_path).unwrap(), text);
+        assert_transcript_intact(&conn, dir.path(), &project);
+    }
+
+    let rows: i64 = conn
+        .query_row("SELECT COUNT(*) FROM project_notes", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(rows, 1);
+    let summary: String = conn
+        .query_row("SELECT summary FROM project_notes", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(summary, "summary output");
+}
+
+#[test]
+fn transcript_cannot_be_saved_as_generated_note() {
+    let (dir, mut conn, project) = setup();
+
+    let result = save_generated_note(
+        &mut conn,
+        dir.path(),
+        &project,
+        NoteKind::Transcript,
+        &fake_output("not a transcript"),
+    );
+
+    assert!(result.is_err());
+    assert_transcript_intact(&conn, dir.path(), &project);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn generate_note_with_llm_keeps_the_transcript() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let (dir, mut conn, project) = setup();
+    let server = dir.path().join("fake-llama-server");
+    fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/support/fake_llama_server.py"),
+        &server,
+    )
+    .unwrap();
+    fs::set_permissions(&server, fs::Permissions::from_mode(0o755)).unwrap();
+    let model = dir.path().join("test.gguf");
+    fs::write(&model, b"").unwrap();
+
+    let llm = LlmService::new();
+    llm.start(LlamaServerConfig {
+        server_path: server,
+        model_path: model,
+        ..Default::default()
+    })
+    .unwrap();
+
+    for kind in NoteKind::generated() {
+        let note = generate_note(
+            &llm,
+            &mut conn,
+            dir.path(),
+            &project.id,
+            kind,
+            &GenerationParams::default(),
+            JobOptions::default(),
+        )
+        .await
+        .unwrap();
+        assert!(note.version.content.starts_with("GENERATED"));
+        assert_eq!(
+            fs::read_to_string(&note.file_path).unwrap(),
+            note.version.content
+        );
+        assert_transcript_intact(&conn, dir.path(), &project);
+    }
+    llm.shutdown().await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn servers_that_crash_after_getting_ready_keep_being_restarted() {
+    use taunote_core::services::llm::llama_server::{LlamaSupervisor, ServerStatus};
+    use tokio::time::{timeout, Duration};
+
+    let dir = tempfile::tempdir().unwrap();
+    let server = fake_llama_server(dir.path(), "--exit-after 0.5");
+
+    // more crashes than max_restarts, but never two without a ready run between
+    let supervisor = LlamaSupervisor::spawn(LlamaServerConfig {
+        server_path: server,
+        model_path: dir.path().join("test.gguf"),
+        max_restarts: 1,
+        health_interval: Duration::from_millis(100),
+        ..Default::default()
+    });
+    let mut status = supervisor.subscribe();
+    let mut ready = 0;
+    while ready < 3 {
+        timeout(Duration::from_secs(20), status.changed())
+            .await
+            .unwrap()
+            .unwrap();
+        match &*status.borrow_and_update() {
+            ServerStatus::Ready { .. } => ready += 1,
+            ServerStatus::Failed { reason } => panic!("gave up: {reason}"),
+            _ => {}
+        }
+    }
+    supervisor.shutdown().await;
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn chunks_take_a_char_even_when_it_alone_is_over_the_budget() {
+    use taunote_core::services::llm::budget::{chunk_transcript, fit_transcript};
+
+    let dir = tempfile::tempdir().unwrap();
+    let model = dir.path().join("test.gguf");
+    fs::write(&model, b"").unwrap();
+    let llm = LlmService::new();
+    // 100 tokens for the transcript, every char is worth 1000
+    llm.start(LlamaServerConfig {
+        server_path: fake_llama_server(dir.path(), "--tokens-per-char 1000"),
+        model_path: model,
+        ctx_size: 16 + 32 + 100,
+        ..Default::default()
+    })
+    .unwrap();
+
+    let chunks = tokio::time::timeout(
+        std::time::Duration::from_secs(30),
+        chunk_transcript(&llm, "{transcript}", "abc", 16),
+    )
+    .await
+    .expect("chunking finishes")
+    .unwrap();
+    assert_eq!(chunks, ["a", "b", "c"]);
+
+    let fitted = fit_transcript(&llm, "{transcript}", "abc", 16)
+        .await
+        .unwrap();
+    assert_eq!(fitted.prompt, "a");
+    assert!(fitted.budget.truncated);
+    llm.shutdown().await;
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use taunote_core::{
+    services::{
+        database::{
+            models::{AudioProject, ProjectType},
+            versions::record_user_edit,
+        },
+        notes::generate::save_transcript,
+    },
+    NewProject, Taunote,
+};
+
+fn meeting(taunote: &Taunote, summary: &str) -> AudioProject {
+    let project = taunote
+        .create_project(NewProject {
+            group_id: "Team".to_string(),
+            name: "Vendor call".to_string(),
+            project_type: ProjectType::Meeting,
+            language: Some("en".to_string()),
+        })
+        .unwrap();
+    let segments = taunote
+        .project_dir(&project)
+        .unwrap()
+        .join("audio/segments.json");
+    fs::create_dir_all(segments.parent().unwrap()).unwrap();
+    fs::write(
+        &segments,
+        r#"{"segments": [{"start": 0.0, "end": 12.5}, {"start": 12.5, "end": 3725.2}]}"#,
+    )
+    .unwrap();
+    let mut conn = taunote.connect().unwrap();
+    save_transcript(
+        &mut conn,
+        taunote.base_dir(),
+        &project,
+        "[SPEAKER_00] the invoice is due friday\n[SPEAKER_01] ok\n[SPEAKER_00] thanks",
+        "whisperx",
+        None,
+    )
+    .unwrap();
+    record_user_edit(&mut conn, &project.id, "summary", summary).unwrap();
+    project
+}
+
+#[test]
+fn projects_become_notes_with_front_matter_links_and_action_items() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path().join("data"));
+    taunote.init().unwrap();
+    let project = meeting(
+        &taunote,
+        "The invoice was discussed.\n\n## Action items\n- Send the invoice\n- **Book the room**\n\nTODO: call Bob",
+    );
+
+    let vault = dir.path().join("vault");
+    let report = taunote.export_obsidian(&vault, None).unwrap();
+    let note_path = vault.join("Team").join("Vendor call.md");
+    let group_path = vault.join("Team.md");
+    assert_eq!(report.written, vec![note_path.clone(), group_path.clone()]);
+
+    let note = fs::read_to_string(&note_path).unwrap();
+    let date = &project.date[..10];
+    assert!(note.starts_with(&format!(
+        "---\ntaunote_id: \"{}\"\ndate: {date}\ngroup: \"[[Team|Team]]\"\ntype: \"meeting\"\n\
+         language: \"en\"\nspeakers: [\"SPEAKER_00\", \"SPEAKER_01\"]\nduration: \"1:02:05\"\n\
+         tags: [\"taunote\", \"meeting\", \"team\"]\n---\n",
+        project.id
+    )));
+    assert!(note.contains(
+        "## Action items\n\n- [ ] Send the invoice\n- [ ] Book the room\n- [ ] call Bob\n"
+    ));
+    assert!(note.contains("## Summary\n\nThe invoice was discussed."));
+    assert!(note.contains("## Transcript\n\n[SPEAKER_00] the invoice is due friday"));
+
+    let group = fs::read_to_string(&group_path).unwrap();
+    assert!(group.contains("taunote_group: \"team\""));
+    assert!(group.contains(&format!("- [[Team/Vendor call|Vendor call]] · {date}\n")));
+}
+
+#[test]
+fn exporting_again_updates_notes_in_place_and_keeps_vault_edits() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path().join("data"));
+    taunote.init().unwrap();
+    let project = meeting(&taunote, "Next steps:\n- Send the invoice\n- Book the room");
+    let vault = dir.path().join("vault");
+    taunote.export_obsidian(&vault, None).unwrap();
+
+    let again = taunote.export_obsidian(&vault, None).unwrap();
+    assert!(again.written.is_empty());
+    assert_eq!(again.unchanged, 2);
+
+    // the note is moved, a box ticked, a property and a paragraph added
+    let moved = vault.join("Archive").join("Call with the vendor.md");
+    fs::create_dir_all(moved.parent().unwrap()).unwrap();
+    let edited = fs::read_to_string(vault.join("Team").join("Vendor call.md"))
+        .unwrap()
+        .replace("- [ ] Send the invoice", "- [x] Send the invoice")
+        .replacen("---\n", "---\naliases: [\"Vendor\"]\n", 1)
+        + "\nMy own thoughts.\n";
+    fs::write(&moved, edited).unwrap();
+    fs::remove_file(vault.join("Team").join("Vendor call.md")).unwrap();
+
+    record_user_edit(
+        &mut taunote.connect().unwrap(),
+        &project.id,
+        "summary",
+        "Next steps:\n- Send the invoice\n- Book the room\n- Pay the deposit",
+    )
+    .unwrap();
+    let report = taunote.export_obsidian(&vault, None).unwrap();
+    assert_eq!(report.written, vec![moved.clone(), vault.join("Team.md")]);
+    assert!(!vault.join("Team").join("Vendor call.md").exists());
+
+    let note = fs::read_to_string(&moved).unwrap();
+    assert!(note.contains("aliases: [\"Vendor\"]\n"));
+    assert!(note.contains("- [x] Send the invoice\n- [ ] Book the room\n- [ ] Pay the deposit\n"));
+    assert!(note.ends_with("<!-- taunote:end -->\n\nMy own thoughts.\n"));
+    assert_eq!(note.matches("taunote_id:").count(), 1);
+    let group = fs::read_to_string(vault.join("Team.md")).unwrap();
+    assert!(group.contains("[[Archive/Call with the vendor|Vendor call]]"));
+
+    assert!(taunote
+        .export_obsidian(&vault, None)
+        .unwrap()
+        .written
+        .is_empty());
+}
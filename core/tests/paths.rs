@@ -0,0 +1,142 @@
+use proptest::prelude::*;
+use std::{collections::HashSet, path::Component};
+use taunote_core::{
+    services::{
+        database::{
+            models::{AudioProject, ProjectType},
+            queries::insert_audio_project,
+        },
+        paths::{
+            root::inside,
+            slug::{file_name, slug, unique_slug, MAX_FILE_NAME_BYTES, MAX_SLUG_BYTES},
+        },
+    },
+    NewProject, Taunote,
+};
+use unicode_normalization::UnicodeNormalization;
+
+const FORBIDDEN: &str = r#"/\:*?"<>|"#;
+
+// Names as people type them, with separators, dots, accents and other scripts
+fn names() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<String>(),
+        "[a-zA-Z0-9 ./\\\\:_-]{0,80}",
+        "[ -~éÉüßøçñ日本語한국어ﬁＡ\u{301}\u{200b}]{0,200}",
+    ]
+}
+
+proptest! {
+    #[test]
+    fn slugs_are_short_lowercase_words_with_dashes(name in names()) {
+        let slug = slug(&name, "group");
+        prop_assert!(!slug.is_empty());
+        prop_assert!(slug.len() <= MAX_SLUG_BYTES + 1);
+        prop_assert!(!slug.starts_with('-') && !slug.ends_with('-'));
+        prop_assert!(!slug.contains("--"));
+        prop_assert!(slug
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-'));
+        prop_assert_eq!(self::slug(&slug, "group"), slug);
+    }
+
+    #[test]
+    fn composed_and_decomposed_names_share_a_slug(name in names()) {
+        let composed: String = name.nfc().collect();
+        let decomposed: String = name.nfd().collect();
+        prop_assert_eq!(slug(&composed, "group"), slug(&decomposed, "group"));
+    }
+
+    #[test]
+    fn unique_slugs_avoid_taken_ones(name in names(), count in 1usize..20) {
+        let base = slug(&name, "group");
+        let mut taken = HashSet::new();
+        for _ in 0..count {
+            let next = unique_slug(&base, |candidate| taken.contains(candidate));
+            prop_assert!(next.len() <= MAX_SLUG_BYTES + 1);
+            prop_assert!(taken.insert(next));
+        }
+    }
+
+    #[test]
+    fn file_names_are_valid_everywhere(name in names()) {
+        let file = file_name(&name, "Untitled");
+        prop_assert!(!file.is_empty());
+        prop_assert!(file.len() <= MAX_FILE_NAME_BYTES + 1);
+        prop_assert!(!file.contains(|c: char| c.is_control() || FORBIDDEN.contains(c)));
+        prop_assert!(!file.starts_with('.') && !file.ends_with('.'));
+        prop_assert!(!file.starts_with(char::is_whitespace));
+        prop_assert!(!file.ends_with(char::is_whitespace));
+        let stem = file.split('.').next().unwrap().to_lowercase();
+        prop_assert!(!["con", "prn", "aux", "nul", "com1", "lpt1"].contains(&stem.as_str()));
+        prop_assert_eq!(file_name(&file, "Untitled"), file);
+    }
+
+    #[test]
+    fn resolved_paths_stay_inside_the_root(relative in names()) {
+        let root = std::env::temp_dir().join("taunote-paths");
+        if let Ok(path) = inside(&root, &relative) {
+            let rest = path.strip_prefix(&root).unwrap();
+            prop_assert!(rest
+                .components()
+                .all(|component| matches!(component, Component::Normal(_))));
+            prop_assert!(rest.components().next().is_some());
+        }
+    }
+}
+
+#[test]
+fn slugs_fold_unicode_and_keep_words_apart() {
+    assert_eq!(slug("Clients: Acme GmbH", "group"), "clients-acme-gmbh");
+    assert_eq!(slug("Café", "group"), slug("Cafe\u{301}", "group"));
+    assert_eq!(slug("ﬁnance ＡＢ", "group"), "finance-ab");
+    assert_eq!(slug("../..", "group"), "group");
+    assert_eq!(slug("CON", "group"), "con_");
+    assert_eq!(unique_slug("team", |id| id == "team"), "team-2");
+    assert_eq!(
+        file_name("Q3: plan/review?", "Untitled"),
+        "Q3- plan-review-"
+    );
+    assert_eq!(file_name(" .. ", "Untitled"), "Untitled");
+    assert_eq!(file_name("nul.txt", "Untitled"), "nul_.txt");
+}
+
+#[test]
+fn project_paths_outside_the_library_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let library = dir.path().join("library");
+    let taunote = Taunote::new(&library);
+    taunote.init().unwrap();
+    let new_project = |group: &str| NewProject {
+        group_id: group.to_string(),
+        name: "Kickoff".to_string(),
+        project_type: ProjectType::Meeting,
+        language: None,
+    };
+    let err = taunote
+        .create_project(new_project("Ünïcode / ../Team"))
+        .unwrap_err();
+    assert_eq!(err.code(), "invalid_input");
+
+    let project = taunote
+        .create_project(new_project("Ünïcode: Team?"))
+        .unwrap();
+    assert!(project.relative_path.starts_with("groups/ünïcode-team/"));
+    assert!(taunote.project_dir(&project).unwrap().is_dir());
+
+    // a path from a database edited by hand or an older version
+    let victim = dir.path().join("victim");
+    std::fs::create_dir_all(&victim).unwrap();
+    let escaping = AudioProject {
+        id: "escaping".to_string(),
+        relative_path: "../victim".to_string(),
+        ..project.clone()
+    };
+    insert_audio_project(&taunote.connect().unwrap(), &escaping).unwrap();
+    assert_eq!(
+        taunote.project_dir(&escaping).unwrap_err().code(),
+        "invalid_input"
+    );
+    taunote.delete_project("escaping").unwrap();
+    assert!(victim.is_dir());
+}
@@ -0,0 +1,177 @@
+use std::fs;
+use taunote_core::{
+    services::{
+        database::models::{EntityKind, ProjectType},
+        notes::generate::save_transcript,
+        redact::{
+            detect::{detect, RedactionRules},
+            mapping::RedactionMap,
+            names::parse_names,
+        },
+    },
+    NewProject, RedactOptions, Taunote,
+};
+
+fn entities(text: &str, rules: &RedactionRules) -> Vec<(EntityKind, String)> {
+    detect(text, rules, &[])
+        .into_iter()
+        .map(|d| (d.entity, text[d.start..d.end].to_string()))
+        .collect()
+}
+
+#[test]
+fn rules_find_contact_and_payment_details() {
+    let text = "Mail alice.smith@example.co.uk or call +1 (555) 010-4477. \
+                Pay to DE89 3704 0044 0532 0130 00 with card 4111 1111 1111 1111.";
+    assert_eq!(
+        entities(text, &RedactionRules::default()),
+        vec![
+            (EntityKind::Email, "alice.smith@example.co.uk".to_string()),
+            (EntityKind::Phone, "+1 (555) 010-4477".to_string()),
+            (EntityKind::Iban, "DE89 3704 0044 0532 0130 00".to_string()),
+            (EntityKind::CreditCard, "4111 1111 1111 1111".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn rules_skip_numbers_that_fail_their_checksum_or_are_dates() {
+    let rules = RedactionRules::default();
+    // wrong IBAN check digits, wrong Luhn digit
+    assert!(entities("IBAN DE88 3704 0044 0532 0130 00", &rules).is_empty());
+    assert!(entities("card 4111 1111 1111 1112", &rules)
+        .iter()
+        .all(|(entity, _)| *entity != EntityKind::CreditCard));
+    assert!(entities("We ship on 2025-01-31 at 10:30, ticket 1234", &rules).is_empty());
+    assert!(entities("order AB12345678901", &rules).is_empty());
+
+    let rules = RedactionRules {
+        emails: false,
+        ..Default::default()
+    };
+    assert!(entities("bob@example.com", &rules).is_empty());
+}
+
+#[test]
+fn custom_words_match_whole_words_ignoring_case() {
+    let rules = RedactionRules {
+        words: vec!["Project Falcon".to_string(), "acme".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        entities(
+            "ACME signed; project falcon starts. Acmes are fine.",
+            &rules
+        ),
+        vec![
+            (EntityKind::Term, "ACME".to_string()),
+            (EntityKind::Term, "project falcon".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn same_value_gets_the_same_placeholder_and_can_be_restored() {
+    let rules = RedactionRules::default();
+    let names = vec!["Alice".to_string()];
+    let text = "Alice: write to bob@example.com. alice: bob@example.com, not carol@example.com";
+    let mut map = RedactionMap::default();
+    let redacted = map.redact(text, &detect(text, &rules, &names));
+    assert_eq!(
+        redacted,
+        "[NAME_1]: write to [EMAIL_1]. [NAME_1]: [EMAIL_1], not [EMAIL_2]"
+    );
+    assert_eq!(map.added().len(), 3);
+    // spellings of one value come back as the first one seen
+    assert_eq!(
+        map.restore(&redacted),
+        "Alice: write to bob@example.com. Alice: bob@example.com, not carol@example.com"
+    );
+
+    // a later run continues the numbering of the stored placeholders
+    let mut later = RedactionMap::new(map.entries().to_vec());
+    assert!(later.added().is_empty());
+    assert_eq!(
+        later.placeholder(EntityKind::Email, "BOB@example.com"),
+        "[EMAIL_1]"
+    );
+    assert_eq!(
+        later.placeholder(EntityKind::Email, "dan@example.com"),
+        "[EMAIL_3]"
+    );
+    assert_eq!(
+        later.restore("ask [EMAIL_3] or [EMAIL_9]"),
+        "ask dan@example.com or [EMAIL_9]"
+    );
+}
+
+#[test]
+fn names_from_the_llm_must_occur_in_the_text() {
+    let output = "1. Alice Smith\n- \"Bob\"\n* Mallory\nSPEAKER_00\nNONE\nbob\n";
+    assert_eq!(
+        parse_names(output, "SPEAKER_00: Alice Smith met Bob"),
+        vec!["Alice Smith".to_string(), "Bob".to_string()]
+    );
+    assert!(parse_names("NONE", "nobody here").is_empty());
+}
+
+#[tokio::test]
+async fn redacted_copies_are_written_and_mappings_kept_per_project() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = Taunote::new(dir.path());
+    taunote.init().unwrap();
+    let project = taunote
+        .create_project(NewProject {
+            group_id: "team".to_string(),
+            name: "Vendor call".to_string(),
+            project_type: ProjectType::Meeting,
+            language: None,
+        })
+        .unwrap();
+    save_transcript(
+        &mut taunote.connect().unwrap(),
+        taunote.base_dir(),
+        &project,
+        "Alice: send the invoice to billing@vendor.com, Acme pays by friday",
+        "whisperx",
+        None,
+    )
+    .unwrap();
+
+    let options = RedactOptions {
+        rules: RedactionRules {
+            words: vec!["Acme".to_string()],
+            ..Default::default()
+        },
+        names: false,
+    };
+    let redacted = taunote.redact(&project.id, &options).await.unwrap();
+    assert_eq!(redacted.notes.len(), 1);
+    let note = &redacted.notes[0];
+    assert_eq!(
+        note.content,
+        "Alice: send the invoice to [EMAIL_1], [TERM_1] pays by friday"
+    );
+    assert_eq!(fs::read_to_string(&note.file_path).unwrap(), note.content);
+    assert!(note
+        .file_path
+        .starts_with(taunote.project_dir(&project).unwrap().join("redacted")));
+    assert_eq!(redacted.redactions.len(), 2);
+
+    // running again reuses the stored placeholders
+    let again = taunote.redact(&project.id, &options).await.unwrap();
+    assert_eq!(again.notes[0].content, note.content);
+    assert_eq!(again.redactions, redacted.redactions);
+
+    assert_eq!(
+        taunote
+            .unredact(&project.id, "Reminder for [TERM_1]: reply to [EMAIL_1]")
+            .unwrap(),
+        "Reminder for Acme: reply to billing@vendor.com"
+    );
+    taunote.delete_project(&project.id).unwrap();
+    assert_eq!(
+        taunote.unredact(&project.id, "").err().unwrap().code(),
+        "not_found"
+    );
+}
@@ -0,0 +1,132 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+use taunote_core::{
+    services::server::{
+        jobs::JobRegistry,
+        routes::{router, AppState},
+    },
+    Taunote,
+};
+
+// Serves the API for a fresh data directory on a free port and returns its base url
+async fn spawn_server(dir: &std::path::Path) -> String {
+    let taunote = Taunote::new(dir);
+    taunote.init().unwrap();
+    let state = AppState {
+        taunote,
+        jobs: JobRegistry::default(),
+    };
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router(state)).await });
+    format!("http://{addr}")
+}
+
+#[tokio::test]
+async fn projects_can_be_created_renamed_and_deleted() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = spawn_server(dir.path()).await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(format!("{base}/projects"))
+        .json(&json!({ "group_id": "team", "name": "Standup", "project_type": "meeting" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    let project: Value = response.json().await.unwrap();
+    let id = project["id"].as_str().unwrap();
+
+    let renamed: Value = client
+        .patch(format!("{base}/projects/{id}"))
+        .json(&json!({ "name": "Daily" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(renamed["name"], "Daily");
+
+    let groups: Value = reqwest::get(format!("{base}/groups"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(groups[0]["id"], "team");
+
+    let response = client
+        .delete(format!("{base}/projects/{id}"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 204);
+
+    let response = reqwest::get(format!("{base}/projects/{id}")).await.unwrap();
+    assert_eq!(response.status(), 404);
+    let error: Value = response.json().await.unwrap();
+    assert_eq!(error["code"], "not_found");
+}
+
+#[tokio::test]
+async fn failed_jobs_report_their_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = spawn_server(dir.path()).await;
+    let client = reqwest::Client::new();
+
+    let project: Value = client
+        .post(format!("{base}/projects"))
+        .json(&json!({ "group_id": "team", "name": "Standup", "project_type": "meeting" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let id = project["id"].as_str().unwrap();
+
+    let response = client
+        .post(format!("{base}/projects/{id}/notes/summary"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 202);
+    let job: Value = response.json().await.unwrap();
+    let job_id = job["id"].as_str().unwrap().to_string();
+
+    // there is neither a llama-server nor a transcript here, so the job fails quickly
+    let mut status = job;
+    for _ in 0..50 {
+        if status["state"] != "running" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        status = reqwest::get(format!("{base}/jobs/{job_id}"))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    }
+    assert_eq!(status["state"], "failed");
+    assert!(status["error"]["code"].is_string());
+
+    // the event stream of a finished job sends its status once and ends
+    let events = reqwest::get(format!("{base}/jobs/{job_id}/events"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(events.starts_with("event: status"));
+    assert!(events.contains("\"state\":\"failed\""));
+
+    let response = client
+        .post(format!("{base}/projects/{id}/notes/unknown"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 400);
+}
@@ -0,0 +1,68 @@
+#!/usr/bin/env python3
+# Minimal stand-in for llama-server used by the integration tests: one token
+# per whitespace separated word, completions echo the start of the prompt.
+# `--exit-after SECONDS` makes it crash that long after it starts,
+# `--tokens-per-char N` counts every char as N tokens instead and
+# `--requests FILE` appends the first prompt line of every completion to FILE.
+import json
+import os
+import sys
+import threading
+from http.server import BaseHTTPRequestHandler, HTTPServer
+
+
+def arg(name, default):
+    if name in sys.argv:
+        return sys.argv[sys.argv.index(name) + 1]
+    return default
+
+
+class Handler(BaseHTTPRequestHandler):
+    def log_message(self, *args):
+        pass
+
+    def reply(self, body):
+        data = json.dumps(body).encode()
+        self.send_response(200)
+        self.send_header("Content-Type", "application/json")
+        self.send_header("Content-Length", str(len(data)))
+        self.end_headers()
+        self.wfile.write(data)
+
+    def do_GET(self):
+        if self.path == "/health":
+            self.reply({"status": "ok"})
+        else:
+            self.send_error(404)
+
+    def do_POST(self):
+        length = int(self.headers.get("Content-Length", 0))
+        body = json.loads(self.rfile.read(length) or b"{}")
+        if self.path == "/tokenize":
+            content = body.get("content", "")
+            per_char = arg("--tokens-per-char", None)
+            if per_char is None:
+                count = len(content.split())
+            else:
+                count = len(content) * int(per_char)
+            self.reply({"tokens": list(range(count))})
+        elif self.path == "/completions":
+            prompt = body.get("prompt", "")
+            requests = arg("--requests", None)
+            if requests is not None:
+                with open(requests, "a") as log:
+                    log.write(prompt.splitlines()[0] + "\n")
+            self.reply({
+                "content": "GENERATED " + prompt.splitlines()[0],
+                "tokens_evaluated": len(prompt.split()),
+                "tokens_predicted": 3,
+            })
+        else:
+            self.send_error(404)
+
+
+exit_after = arg("--exit-after", None)
+if exit_after is not None:
+    threading.Timer(float(exit_after), lambda: os._exit(1)).start()
+
+HTTPServer((arg("--host", "127.0.0.1"), int(arg("--port", "8081"))), Handler).serve_forever()
@@ -0,0 +1,35 @@
+// Every test binary uses only some of these
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use taunote_core::Taunote;
+
+// An initialized library in `dir`
+pub fn library(dir: &Path) -> Taunote {
+    let taunote = Taunote::new(dir);
+    taunote.init().unwrap();
+    taunote
+}
+
+// fake_llama_server.py behind a script that passes it `args`, to be used as
+// the llama-server binary
+#[cfg(unix)]
+pub fn fake_llama_server(dir: &Path, args: &str) -> PathBuf {
+    use std::os::unix::fs::PermissionsExt;
+
+    let server = dir.join("fake-llama-server");
+    let script = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/support/fake_llama_server.py");
+    fs::write(
+        &server,
+        format!(
+            "#!/bin/sh\nexec python3 '{}' {args} \"$@\"\n",
+            script.display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&server, fs::Permissions::from_mode(0o755)).unwrap();
+    server
+}
@@ -0,0 +1,152 @@
+mod support;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use support::library;
+use taunote_core::{
+    services::{
+        database::{
+            models::{AudioProject, ProjectType},
+            versions::{current_version, record_user_edit},
+        },
+        sync::markdown::{export_note, note_file_path, resolve_conflict, sync_all, Resolution},
+    },
+    NewProject, Taunote,
+};
+
+// A project with a synced summary "first draft"
+fn synced_summary(taunote: &Taunote) -> (AudioProject, PathBuf) {
+    let project = taunote
+        .create_project(NewProject {
+            group_id: "team".to_string(),
+            name: "Planning".to_string(),
+            project_type: ProjectType::Meeting,
+            language: None,
+        })
+        .unwrap();
+    let mut conn = taunote.connect().unwrap();
+    record_user_edit(&mut conn, &project.id, "summary", "first draft").unwrap();
+    export_note(&conn, taunote.base_dir(), &project, "summary").unwrap();
+    let path = note_file_path(taunote.base_dir(), &project, "summary").unwrap();
+    (project, path)
+}
+
+// Edits the file as an editor would, later than the last sync
+fn edit_file(path: &Path, content: &str) {
+    fs::write(path, content).unwrap();
+    fs::File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(10))
+        .unwrap();
+}
+
+fn current(taunote: &Taunote, project: &AudioProject) -> String {
+    current_version(&taunote.connect().unwrap(), &project.id, "summary")
+        .unwrap()
+        .unwrap()
+        .content
+}
+
+#[test]
+fn file_edits_become_versions_and_database_edits_are_written_out() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let (project, path) = synced_summary(&taunote);
+    let mut conn = taunote.connect().unwrap();
+
+    let report = sync_all(&mut conn, taunote.base_dir()).unwrap();
+    assert_eq!((report.imported.len(), report.exported.len()), (0, 0));
+    assert_eq!(report.unchanged, 1);
+
+    edit_file(&path, "edited in the editor");
+    let report = sync_all(&mut conn, taunote.base_dir()).unwrap();
+    assert_eq!(report.imported.len(), 1);
+    assert_eq!(report.imported[0].note_kind, "summary");
+    assert_eq!(current(&taunote, &project), "edited in the editor");
+
+    record_user_edit(&mut conn, &project.id, "summary", "edited in the app").unwrap();
+    let report = sync_all(&mut conn, taunote.base_dir()).unwrap();
+    assert_eq!(report.exported.len(), 1);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "edited in the app");
+
+    // a deleted file is written again from the database
+    fs::remove_file(&path).unwrap();
+    let report = sync_all(&mut conn, taunote.base_dir()).unwrap();
+    assert_eq!(report.exported.len(), 1);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "edited in the app");
+    assert!(report.conflicts.is_empty());
+}
+
+#[test]
+fn edits_on_both_sides_wait_for_a_resolution() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let (project, path) = synced_summary(&taunote);
+    let mut conn = taunote.connect().unwrap();
+
+    for (resolution, file, app) in [
+        (Resolution::KeepFile, "file edit", "app edit"),
+        (
+            Resolution::KeepDatabase,
+            "second file edit",
+            "second app edit",
+        ),
+    ] {
+        edit_file(&path, file);
+        record_user_edit(&mut conn, &project.id, "summary", app).unwrap();
+        let report = sync_all(&mut conn, taunote.base_dir()).unwrap();
+        assert_eq!(report.conflicts.len(), 1);
+        assert_eq!(report.conflicts[0].file_path, path);
+        assert!(report.imported.is_empty() && report.exported.is_empty());
+        // nothing is overwritten until someone decides
+        assert_eq!(fs::read_to_string(&path).unwrap(), file);
+        assert_eq!(current(&taunote, &project), app);
+
+        resolve_conflict(
+            &mut conn,
+            taunote.base_dir(),
+            &project.id,
+            "summary",
+            resolution,
+        )
+        .unwrap();
+        let kept = match resolution {
+            Resolution::KeepFile => file,
+            Resolution::KeepDatabase => app,
+        };
+        assert_eq!(fs::read_to_string(&path).unwrap(), kept);
+        assert_eq!(current(&taunote, &project), kept);
+        let report = sync_all(&mut conn, taunote.base_dir()).unwrap();
+        assert!(report.conflicts.is_empty());
+        assert_eq!(report.unchanged, 1);
+    }
+}
+
+#[test]
+fn other_markdown_files_in_a_project_are_not_notes() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let (project, path) = synced_summary(&taunote);
+    let readme = path.with_file_name("README.md");
+    fs::write(&readme, "not a note").unwrap();
+    let email = note_file_path(taunote.base_dir(), &project, "email").unwrap();
+    fs::write(&email, "written by hand").unwrap();
+
+    let mut conn = taunote.connect().unwrap();
+    let report = sync_all(&mut conn, taunote.base_dir()).unwrap();
+    let imported: Vec<_> = report
+        .imported
+        .iter()
+        .map(|note| note.note_kind.as_str())
+        .collect();
+    assert_eq!(imported, ["email"]);
+    assert!(current_version(&conn, &project.id, "README")
+        .unwrap()
+        .is_none());
+    assert_eq!(fs::read_to_string(&readme).unwrap(), "not a note");
+}
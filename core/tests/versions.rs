@@ -0,0 +1,71 @@
+mod support;
+
+use support::library;
+use taunote_core::{
+    services::database::{
+        models::{ProjectType, VersionSource},
+        versions::{
+            current_version, diff_versions, list_versions, record_user_edit, restore_version,
+        },
+    },
+    NewProject,
+};
+
+#[test]
+fn every_save_is_kept_and_restores_copy_an_old_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let taunote = library(dir.path());
+    let project = taunote
+        .create_project(NewProject {
+            group_id: "team".to_string(),
+            name: "Retro".to_string(),
+            project_type: ProjectType::Meeting,
+            language: None,
+        })
+        .unwrap();
+    let mut conn = taunote.connect().unwrap();
+
+    let first = record_user_edit(
+        &mut conn,
+        &project.id,
+        "summary",
+        "we keep standups\nwe ship weekly\n",
+    )
+    .unwrap();
+    let second = record_user_edit(
+        &mut conn,
+        &project.id,
+        "summary",
+        "we keep standups\nwe ship monthly\n",
+    )
+    .unwrap();
+    let versions = list_versions(&conn, &project.id, "summary").unwrap();
+    assert_eq!(versions.len(), 2);
+    assert!(versions.iter().any(|v| v.id == first.id && !v.is_current));
+    assert!(versions.iter().any(|v| v.id == second.id && v.is_current));
+    assert_eq!(second.parent_id.as_deref(), Some(first.id.as_str()));
+
+    let diff = diff_versions(&conn, &first.id, &second.id).unwrap();
+    assert!(diff.contains("\n-we ship weekly\n"), "{diff}");
+    assert!(diff.contains("\n+we ship monthly\n"), "{diff}");
+    assert!(!diff.contains("-we keep standups"), "{diff}");
+
+    assert_eq!(taunote.search("monthly", 10).unwrap().len(), 1);
+    let restored = restore_version(&mut conn, &first.id).unwrap();
+    assert_eq!(restored.source, VersionSource::Restored);
+    assert_eq!(restored.content, first.content);
+    let current = current_version(&conn, &project.id, "summary")
+        .unwrap()
+        .unwrap();
+    assert_eq!(current.id, restored.id);
+    assert_eq!(
+        list_versions(&conn, &project.id, "summary").unwrap().len(),
+        3
+    );
+
+    // the search index follows the current version only
+    assert!(taunote.search("monthly", 10).unwrap().is_empty());
+    let hits = taunote.search("weekly", 10).unwrap();
+    assert_eq!(hits.len(), 1);
+    assert_eq!(hits[0].project_id, project.id);
+}
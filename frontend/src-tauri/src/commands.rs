@@ -1,21 +1,48 @@
-use directories_next::ProjectDirs;
-use rusqlite::{params, Connection};
+use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use std::fs;
+use std::sync::{Mutex, MutexGuard};
+use tauri::{AppHandle, Emitter, State};
 
 use taunote_core::services::{
-    audio::ffmpeg::preprocess_audio,
+    audio::{
+        capture::{Recorder, RecordingFormat},
+        microphone::{input_devices, InputDevice, Microphone},
+    },
+    backup::{
+        archive::{BackupReport, RestoreReport},
+        manifest::Manifest,
+    },
+    bundle::import::{ImportOptions, ImportReport},
     database::{
-        models::AudioProject,
-        queries::{insert_audio_project, insert_project_notes},
-        schema::init_db,
+        groups::project_relative_path,
+        models::{
+            AudioProject, Meeting, NoteVersion, ProjectGroup as CoreProjectGroup, ProjectMetadata,
+            ProjectType, SearchFilter, SearchHit, TagCount,
+        },
+        queries::insert_audio_project,
+        versions::{
+            diff_versions, list_versions, record_user_edit, restore_version, set_current_version,
+        },
     },
+    doctor::checks::{check_environment as probe_environment, EnvironmentReport},
     llm::{
-        llama_queue::init_llama_queue,
-        prompt_tasks::{generate_email, generate_lecture_notes, summarize},
+        generation::GenerationParams,
+        llama_queue::{JobId, JobOptions, QueueSnapshot},
+        llama_server::ServerStatus,
+    },
+    notes::{generate::GeneratedNote, kinds::NoteKind},
+    paths::root::inside,
+    sync::{
+        markdown::{
+            export_note, note_file_path, resolve_conflict, sync_all, Resolution, SyncReport,
+        },
+        obsidian::VaultReport,
     },
-    transcribe::whisperx::run_whisperx,
+};
+use taunote_core::{
+    CalendarImport, NewProject, ProcessOptions, ProcessedRecording, RedactOptions, RedactedProject,
+    ReprocessOptions, Result, Taunote, TaunoteError,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -23,222 +50,552 @@ use taunote_core::services::{
 pub struct ProjectGroup {
     pub id: String,
     pub name: String,
+    // None for top level groups
+    pub parentId: Option<String>,
     pub audioProjects: Vec<AudioProject>,
 }
 
-fn connect_to_db() -> Result<Connection, String> {
-    let proj_dirs = ProjectDirs::from("com", "andrea", "taunote")
-        .expect("Failed to find platform data directory");
-    let base_path = proj_dirs.data_local_dir();
-    let db_path = base_path.join("db").join("project.db");
-    Connection::open(db_path).map_err(|e| e.to_string())
+// Writes the current version of a note back to its markdown file
+fn write_back(taunote: &Taunote, conn: &Connection, version: &NoteVersion) -> Result<()> {
+    let project = taunote.project(&version.project_id)?;
+    export_note(conn, taunote.base_dir(), &project, &version.note_kind)
 }
 
 #[tauri::command]
-pub fn get_project_groups() -> std::result::Result<Vec<ProjectGroup>, String> {
-    // Find db
-    let conn = connect_to_db()?;
-
-    // Load project groups
-    let mut grp_stmt = conn
-        .prepare("SELECT id, name FROM project_groups")
-        .map_err(|e| e.to_string())?;
-    let group_iter = grp_stmt
-        .query_map([], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-        })
-        .map_err(|e| e.to_string())?;
-
-    // Collect into vector group with empty audioProjects
-    let mut groups = group_iter
-        .map(|res| {
-            res.map(|(id, name)| ProjectGroup {
-                id,
-                name,
-                audioProjects: Vec::new(),
+pub fn get_project_groups(taunote: State<'_, Taunote>) -> Result<Vec<ProjectGroup>> {
+    taunote
+        .list_groups()?
+        .into_iter()
+        .map(|group| {
+            Ok(ProjectGroup {
+                audioProjects: taunote.list_projects(Some(&group.id))?,
+                id: group.id,
+                name: group.name,
+                parentId: group.parent_id,
             })
         })
-        .collect::<std::result::Result<Vec<_>, rusqlite::Error>>()
-        .map_err(|e| e.to_string())?;
-
-    // for each group, load its audioProjects
-    for group in &mut groups {
-        let mut ap_stmt = conn
-            .prepare(
-                "SELECT id, group_id, name, relative_path, date, type, language
-             FROM audio_projects WHERE group_id = ?1",
-            )
-            .map_err(|e| e.to_string())?;
-
-        let audio_iter = ap_stmt
-            .query_map(params![&group.id], |r| {
-                Ok(AudioProject {
-                    id: r.get(0)?,
-                    group_id: r.get(1)?,
-                    name: r.get(2)?,
-                    relative_path: r.get(3)?,
-                    date: r.get(4)?,
-                    project_type: r.get(5)?,
-                    language: r.get(6)?,
-                })
-            })
-            .map_err(|e| e.to_string())?;
+        .collect()
+}
+
+// Generates one note kind for a project, each kind goes to its own file and never
+// touches transcript.md
+#[tauri::command]
+pub async fn generate_note(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    note_kind: NoteKind,
+    params: Option<GenerationParams>,
+) -> Result<GeneratedNote> {
+    let params = params.unwrap_or_default();
+    taunote
+        .generate_note(
+            &project_id,
+            note_kind,
+            &params,
+            JobOptions::interactive(note_kind.as_str()),
+        )
+        .await
+}
+
+// Transcribes the project's stored recording again with new settings, the old
+// transcript stays in the version history
+#[tauri::command]
+pub async fn reprocess_project(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    options: Option<ReprocessOptions>,
+) -> Result<ProcessedRecording> {
+    taunote
+        .reprocess(&project_id, &options.unwrap_or_default())
+        .await
+}
+
+// Redacted copies of the project's notes for sharing, placeholders are kept so
+// `unredact_text` can put the values back
+#[tauri::command]
+pub async fn redact_project(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    options: Option<RedactOptions>,
+) -> Result<RedactedProject> {
+    taunote
+        .redact(&project_id, &options.unwrap_or_default())
+        .await
+}
+
+#[tauri::command]
+pub fn unredact_text(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    text: String,
+) -> Result<String> {
+    taunote.unredact(&project_id, &text)
+}
+
+// All projects, or those of `group_id`, as notes in an Obsidian vault
+#[tauri::command]
+pub fn export_obsidian_vault(
+    taunote: State<'_, Taunote>,
+    vault: String,
+    group_id: Option<String>,
+) -> Result<VaultReport> {
+    taunote.export_obsidian(Path::new(&vault), group_id.as_deref())
+}
+
+// Returns the path of the written .taunote file, `dest` may be a folder
+#[tauri::command]
+pub fn export_project_bundle(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    dest: String,
+) -> Result<String> {
+    let path = taunote.export_bundle(&project_id, Path::new(&dest))?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub fn import_project_bundle(
+    taunote: State<'_, Taunote>,
+    bundle_path: String,
+    options: Option<ImportOptions>,
+) -> Result<ImportReport> {
+    taunote.import_bundle(Path::new(&bundle_path), &options.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn import_calendar(taunote: State<'_, Taunote>, ics_path: String) -> Result<CalendarImport> {
+    taunote.import_calendar(Path::new(&ics_path))
+}
+
+// The imported calendar event a recording was made in, to name a new project
+// after it
+#[tauri::command]
+pub fn match_recording_meeting(
+    taunote: State<'_, Taunote>,
+    audio_path: String,
+) -> Result<Option<Meeting>> {
+    taunote.recording_meeting(Path::new(&audio_path))
+}
+
+#[tauri::command]
+pub fn get_project_meeting(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+) -> Result<Option<Meeting>> {
+    taunote.project_meeting(&project_id)
+}
+
+#[tauri::command]
+pub fn set_project_meeting(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    meeting: Meeting,
+) -> Result<()> {
+    taunote.set_project_meeting(&project_id, &meeting)
+}
+
+#[tauri::command]
+pub fn get_project_metadata(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+) -> Result<ProjectMetadata> {
+    taunote.project_metadata(&project_id)
+}
+
+// Returns the metadata as stored, with tags and participants deduplicated
+#[tauri::command]
+pub fn set_project_metadata(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    metadata: ProjectMetadata,
+) -> Result<ProjectMetadata> {
+    taunote.set_project_metadata(&project_id, metadata)
+}
+
+#[tauri::command]
+pub fn set_project_type(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    project_type: ProjectType,
+) -> Result<AudioProject> {
+    taunote.set_project_type(&project_id, project_type)
+}
+
+#[tauri::command]
+pub fn backup_library(
+    taunote: State<'_, Taunote>,
+    dest: String,
+    since: Option<String>,
+) -> Result<BackupReport> {
+    taunote.backup(&PathBuf::from(dest), since.as_deref().map(Path::new))
+}
+
+#[tauri::command]
+pub fn verify_backup(taunote: State<'_, Taunote>, archives: Vec<String>) -> Result<Manifest> {
+    let archives: Vec<PathBuf> = archives.into_iter().map(PathBuf::from).collect();
+    taunote.verify_backup(&archives)
+}
+
+// The app has to reload its groups afterwards and, for an encrypted backup,
+// call `unlock` again
+#[tauri::command]
+pub fn restore_library(
+    taunote: State<'_, Taunote>,
+    archives: Vec<String>,
+) -> Result<RestoreReport> {
+    let archives: Vec<PathBuf> = archives.into_iter().map(PathBuf::from).collect();
+    taunote.restore(&archives)
+}
+
+// The microphone recording in progress, there is at most one
+#[derive(Default)]
+pub struct ActiveRecording(Mutex<Option<Recorder>>);
+
+impl ActiveRecording {
+    fn lock(&self) -> Result<MutexGuard<'_, Option<Recorder>>> {
+        self.0
+            .lock()
+            .map_err(|_| TaunoteError::Internal("The recorder state is poisoned".to_string()))
+    }
 
-        group.audioProjects = audio_iter
-            .map(|res| res.map_err(|e| e.to_string()))
-            .collect::<Result<_, _>>()?;
+    fn take(&self) -> Result<Recorder> {
+        self.lock()?
+            .take()
+            .ok_or_else(|| TaunoteError::InvalidInput("Nothing is being recorded".to_string()))
     }
 
-    Ok(groups)
+    fn with(&self, run: impl FnOnce(&Recorder)) -> Result<()> {
+        let active = self.lock()?;
+        let recorder = active
+            .as_ref()
+            .ok_or_else(|| TaunoteError::InvalidInput("Nothing is being recorded".to_string()))?;
+        run(recorder);
+        Ok(())
+    }
 }
 
-fn write_to_file(text: &str, dir_path: &str, filename: &str) -> Result<(), String> {
-    let project_folder = PathBuf::from(dir_path);
-    fs::create_dir_all(&project_folder)
-        .map_err(|e| format!("Could not create directory {}: {}", dir_path, e))?;
-    fs::write(project_folder.join(filename), text)
-        .map_err(|e| format!("Could not write to file {}: {}", filename, e))?;
+#[tauri::command]
+pub fn list_input_devices() -> Result<Vec<InputDevice>> {
+    input_devices()
+}
+
+// `device` is a name from list_input_devices, the system's default for None.
+// The loudness is emitted as `recording-level` events while recording.
+#[tauri::command]
+pub fn start_recording(
+    app: AppHandle,
+    taunote: State<'_, Taunote>,
+    recording: State<'_, ActiveRecording>,
+    device: Option<String>,
+    format: Option<RecordingFormat>,
+) -> Result<()> {
+    let mut active = recording.lock()?;
+    if active.is_some() {
+        return Err(TaunoteError::InvalidInput(
+            "A recording is already running".to_string(),
+        ));
+    }
+    let recorder = taunote.start_recording(
+        Box::new(Microphone::new(device.as_deref())),
+        format.unwrap_or_default(),
+        move |level| {
+            let _ = app.emit("recording-level", level);
+        },
+    )?;
+    *active = Some(recorder);
     Ok(())
 }
 
-// TODO: rethink what each of these 3 functions is going to do differently
-// If not, condense with hof or match case
 #[tauri::command]
-pub async fn summarize_transcript(transcript_path: String) -> Result<(String, String), String> {
-    let path = PathBuf::from(transcript_path);
-    let summary = summarize(&path).await
-        .map_err(|e| e.to_string())?;
-    let filename = "transcript.md";
-    let dir_path = path
-        .parent().ok_or("How tf is there no parent dir?")?
-        .to_str().ok_or("Could not convert path to string")?;
-    write_to_file(summary.as_str(), dir_path, &filename)?;
-    Ok((filename.to_string(), summary))
+pub fn pause_recording(recording: State<'_, ActiveRecording>) -> Result<()> {
+    recording.with(Recorder::pause)
 }
 
 #[tauri::command]
-pub async fn write_email(transcript_path: String) -> Result<(String, String), String> {
-    let path = PathBuf::from(transcript_path);
-    let text = generate_email(&path).await
-        .map_err(|e| e.to_string())?;
-    let filename = "email.md";
-    let dir_path = path
-        .parent().ok_or("How tf is there no parent dir?")?
-        .to_str().ok_or("Could not convert path to string")?;
-    write_to_file(text.as_str(), dir_path, &filename)?;
-    Ok((filename.to_string(), text))
+pub fn resume_recording(recording: State<'_, ActiveRecording>) -> Result<()> {
+    recording.with(Recorder::resume)
 }
 
+// Creates the project and runs the recording through the same pipeline as an
+// imported file, emitting `recording-progress` events as each step starts
 #[tauri::command]
-pub async fn write_lecture_notes(transcript_path: String) -> Result<(String, String), String> {
-    let path = PathBuf::from(transcript_path);
-    let text = generate_lecture_notes(&path)
-        .await.map_err(|e| e.to_string())?;
-    let filename = "lecture_notes.md";
-    let dir_path = path
-        .parent().ok_or("How tf is there no parent dir?")?
-        .to_str().ok_or("Could not convert path to string")?;
-    write_to_file(text.as_str(), dir_path, &filename)?;
-    Ok((filename.to_string(), text))
+pub async fn stop_recording(
+    app: AppHandle,
+    taunote: State<'_, Taunote>,
+    recording: State<'_, ActiveRecording>,
+    project: NewProject,
+    options: Option<ProcessOptions>,
+) -> Result<ProcessedRecording> {
+    let recorder = recording.take()?;
+    taunote
+        .finish_recording(
+            recorder,
+            project,
+            &options.unwrap_or_default(),
+            move |progress| {
+                let _ = app.emit("recording-progress", progress);
+            },
+        )
+        .await
+}
+
+// Throws away what was recorded
+#[tauri::command]
+pub fn cancel_recording(recording: State<'_, ActiveRecording>) -> Result<()> {
+    recording.take()?.cancel()
 }
 
 #[tauri::command]
 pub async fn transcribe_audio(
+    taunote: State<'_, Taunote>,
     audio_path: String,
     lang: String,
-    group_name: String,
-    project_name: String
-) -> Result<(String, String), String> {
+    project_id: String,
+) -> Result<(String, String)> {
     let path = PathBuf::from(audio_path);
-    let tmp_preprocessed_audio_path_name = "../tmp/preprocessed.wav";
-    let tmp_preprocessed_audio_path = Path::new(tmp_preprocessed_audio_path_name);
+    let lang_input = Some(lang.as_str()).filter(|lang| !lang.eq_ignore_ascii_case("auto"));
+
+    // stored as the project's current transcript version, in the project's folder
+    let version = taunote
+        .transcribe_blocking(&project_id, &path, lang_input)
+        .await?;
+    let project = taunote.project(&project_id)?;
+    let filename = note_file_path(taunote.base_dir(), &project, &version.note_kind)?;
+    Ok((filename.to_string_lossy().into_owned(), version.content))
+}
 
-    preprocess_audio(&path, tmp_preprocessed_audio_path).map_err(|e| e.to_string())?;
+// Safe to call more than once, e.g. after a frontend reload. Fails with a
+// `locked` error when the data is encrypted and the OS keyring has no key,
+// call `unlock` then
+#[tauri::command]
+pub async fn setup_backend(taunote: State<'_, Taunote>) -> Result<()> {
+    if taunote.is_locked() && !taunote.unlock_from_keyring()? {
+        return Err(TaunoteError::Locked(
+            "enter your passphrase to open your notes".to_string(),
+        ));
+    }
+    taunote.init()?;
+    // a failed cleanup must not keep the app from starting
+    if let Err(err) = taunote.prune_media() {
+        eprintln!("Failed to prune media: {err}");
+    }
+    // start llama server + queue, keeps the running one if nothing changed
+    taunote.start_llm().await
+}
 
-    let proj_dirs = directories_next::ProjectDirs::from("com", "andrea", "taunote")
-        .expect("Failed to find platform data directory");
-    let base_path = proj_dirs.data_local_dir();
-    let relative_path = format!("groups/{group_name}/{project_name}");
-    let project_folder = base_path.join(&relative_path);
-    fs::create_dir_all(&project_folder)
-        .map_err(|e| format!("Unable to create directory: {}", e))?;
+// Unlocks the encrypted data directory, `remember` saves the key in the OS keyring
+#[tauri::command]
+pub fn unlock(taunote: State<'_, Taunote>, passphrase: String, remember: bool) -> Result<()> {
+    taunote.unlock(&passphrase)?;
+    if remember {
+        taunote.remember_key()?;
+    }
+    Ok(())
+}
 
-    let filename = project_folder.join("transcript.md");
+// Path of the project's stored recording for playback, None if it has none
+#[tauri::command]
+pub fn get_project_audio(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+) -> Result<Option<String>> {
+    let project = taunote.project(&project_id)?;
+    Ok(taunote
+        .original_audio(&project)?
+        .map(|path| path.to_string_lossy().into_owned()))
+}
 
-    let lang_input = if lang.eq_ignore_ascii_case("auto") { None } else { Some(lang) };
+// Restarts the llama server with another GGUF model, queued jobs are failed
+#[tauri::command]
+pub async fn switch_llm_model(taunote: State<'_, Taunote>, model_path: String) -> Result<()> {
+    taunote.switch_model(PathBuf::from(model_path)).await
+}
 
-    run_whisperx(
-        tmp_preprocessed_audio_path,
-        &Some(filename.clone()),
-        &lang_input,
-    )
-    .map_err(|e| e.to_string())?;
+// Probes ffmpeg, whisperx, llama-server, the model, disk and memory for the setup screen
+#[tauri::command]
+pub async fn check_environment(taunote: State<'_, Taunote>) -> Result<EnvironmentReport> {
+    let config = taunote.llama_config();
+    let base_path = taunote.base_dir().to_path_buf();
+    // the probes spawn subprocesses, keep them off the async runtime
+    tauri::async_runtime::spawn_blocking(move || probe_environment(&config, &base_path))
+        .await
+        .map_err(|e| TaunoteError::Internal(e.to_string()))
+}
 
-    let transcript = String::from_utf8(
-        fs::read(&filename).map_err(|e| format!("Could not read from file: {}", e))?
-    ).map_err(|e| format!("Could not convert to utf8 string: {}", e))?;
+#[tauri::command]
+pub fn get_llm_status(taunote: State<'_, Taunote>) -> ServerStatus {
+    taunote.llm().status()
+}
 
-    let filename_string = filename.to_string_lossy().into_owned();
+#[tauri::command]
+pub fn get_llm_queue(taunote: State<'_, Taunote>) -> Result<QueueSnapshot> {
+    taunote.llm().snapshot()
+}
 
-    Ok((filename_string, transcript))
+#[tauri::command]
+pub fn cancel_llm_job(taunote: State<'_, Taunote>, id: JobId) -> Result<bool> {
+    taunote.llm().cancel(id)
 }
 
+#[tauri::command]
+pub fn search_projects(
+    taunote: State<'_, Taunote>,
+    query: String,
+    filter: Option<SearchFilter>,
+    limit: Option<usize>,
+) -> Result<Vec<SearchHit>> {
+    taunote.search_filtered(&query, &filter.unwrap_or_default(), limit.unwrap_or(50))
+}
 
 #[tauri::command]
-pub async fn setup_backend() -> Result<(), String> {
-    // start + open db
-    let proj_dirs = directories_next::ProjectDirs::from("com", "andrea", "taunote")
-        .expect("Failed to find platform data directory");
-    let base_path = proj_dirs.data_local_dir();
-    init_db(base_path).map_err(|e| e.to_string())?;
+pub fn list_tags(taunote: State<'_, Taunote>) -> Result<Vec<TagCount>> {
+    taunote.tags()
+}
 
-    // start llama queue
-    // TODO: might want to actually make it return errors @sp
-    init_llama_queue().await;
+// Copies the project's current notes as markdown files into `dest`
+#[tauri::command]
+pub fn export_project(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    dest: String,
+) -> Result<Vec<PathBuf>> {
+    taunote.export(&project_id, &PathBuf::from(dest))
+}
 
-    Ok(())
+// Groups are given by id or by a path of names like Clients/Acme, missing
+// groups of a path are created
+#[tauri::command]
+pub fn create_group(taunote: State<'_, Taunote>, path: String) -> Result<CoreProjectGroup> {
+    taunote.create_group(&path)
 }
 
 #[tauri::command]
-pub fn insert_project_group_to_db(id: String, name: String) -> Result<(), String> {
-    let conn = connect_to_db()?;
-    conn.execute(
-        "INSERT INTO project_groups (id, name) VALUES (?1, ?2)",
-        params![id, name],
-    )
-    .map_err(|e| e.to_string())?;
-    Ok(())
+pub fn rename_group(
+    taunote: State<'_, Taunote>,
+    group: String,
+    name: String,
+) -> Result<CoreProjectGroup> {
+    taunote.rename_group(&group, &name)
+}
+
+// `parent` None makes it a top level group, its projects keep their folders
+#[tauri::command]
+pub fn move_group(
+    taunote: State<'_, Taunote>,
+    group: String,
+    parent: Option<String>,
+) -> Result<CoreProjectGroup> {
+    taunote.move_group(&group, parent.as_deref())
+}
+
+// Fails for groups that still have projects or subgroups
+#[tauri::command]
+pub fn delete_group(taunote: State<'_, Taunote>, group: String) -> Result<()> {
+    taunote.delete_group(&group)
+}
+
+// Moves the project's folder along, the database is left alone if that fails
+#[tauri::command]
+pub fn move_project(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    group: String,
+) -> Result<AudioProject> {
+    taunote.move_project(&project_id, &group)
 }
 
 #[tauri::command]
 pub fn insert_audio_project_to_db(
-    // TODO: this is absolutely awful and it makes my eyes bleed
-    // but yeah... quick fix?
-    audio_project: Option<AudioProject>,
-    audioProject: Option<AudioProject>,
-) -> Result<(), String> {
-    let conn = connect_to_db()?;
-
-    let ap = audio_project
-        .or(audioProject)
-        .ok_or_else(|| "missing arg: audio_project / audioProject".to_string())?;
-
-    insert_audio_project(&conn, &ap).map_err(|e| e.to_string())?;
+    taunote: State<'_, Taunote>,
+    audio_project: AudioProject,
+) -> Result<()> {
+    let conn = taunote.connect()?;
+
+    // folders are named by id, whatever path the app sent
+    let ap = AudioProject {
+        relative_path: project_relative_path(&audio_project.group_id, &audio_project.id),
+        ..audio_project
+    };
+    inside(taunote.base_dir(), &ap.relative_path)?;
+    insert_audio_project(&conn, &ap)?;
     Ok(())
 }
 
+#[tauri::command]
+pub fn list_note_versions(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    note_kind: String,
+) -> Result<Vec<NoteVersion>> {
+    let conn = taunote.connect()?;
+    Ok(list_versions(&conn, &project_id, &note_kind)?)
+}
+
+#[tauri::command]
+pub fn diff_note_versions(
+    taunote: State<'_, Taunote>,
+    from_id: String,
+    to_id: String,
+) -> Result<String> {
+    let conn = taunote.connect()?;
+    Ok(diff_versions(&conn, &from_id, &to_id)?)
+}
+
+#[tauri::command]
+pub fn restore_note_version(
+    taunote: State<'_, Taunote>,
+    version_id: String,
+) -> Result<NoteVersion> {
+    let mut conn = taunote.connect()?;
+    let version = restore_version(&mut conn, &version_id)?;
+    write_back(&taunote, &conn, &version)?;
+    Ok(version)
+}
+
+#[tauri::command]
+pub fn set_current_note_version(
+    taunote: State<'_, Taunote>,
+    version_id: String,
+) -> Result<NoteVersion> {
+    let mut conn = taunote.connect()?;
+    let version = set_current_version(&mut conn, &version_id)?;
+    write_back(&taunote, &conn, &version)?;
+    Ok(version)
+}
 
 #[tauri::command]
-pub fn insert_project_notes_to_db(
+pub fn save_note_edit(
+    taunote: State<'_, Taunote>,
     project_id: String,
-    transcript: String,
-    summary: String,
-    email: String,
-    lecture_notes: String
-) -> Result<(), String> {
-    let conn = connect_to_db()?;
-    insert_project_notes(&conn, &project_id, &transcript, &summary, &email, &lecture_notes)
-        .map_err(|e| e.to_string())?;
+    note_kind: String,
+    content: String,
+) -> Result<NoteVersion> {
+    let mut conn = taunote.connect()?;
+    let version = record_user_edit(&mut conn, &project_id, &note_kind, &content)?;
+    write_back(&taunote, &conn, &version)?;
+    Ok(version)
+}
 
-    Ok(())
-}
\ No newline at end of file
+// Imports external edits of the markdown files and writes database edits back
+#[tauri::command]
+pub fn sync_notes(taunote: State<'_, Taunote>) -> Result<SyncReport> {
+    let mut conn = taunote.connect()?;
+    sync_all(&mut conn, taunote.base_dir())
+}
+
+#[tauri::command]
+pub fn resolve_note_conflict(
+    taunote: State<'_, Taunote>,
+    project_id: String,
+    note_kind: String,
+    resolution: Resolution,
+) -> Result<()> {
+    let mut conn = taunote.connect()?;
+    resolve_conflict(
+        &mut conn,
+        taunote.base_dir(),
+        &project_id,
+        &note_kind,
+        resolution,
+    )
+}
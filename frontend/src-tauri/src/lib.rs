@@ -1,28 +1,79 @@
-
 pub mod commands;
 
+use taunote_core::services::config::settings::Settings;
+use taunote_core::Taunote;
+
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 #[tauri::command]
 fn greet(name: &str) -> String {
     format!("Hello, {name}! You've been greeted from Rust!")
 }
 
+// Same data directory and settings as the taunote CLI
+fn taunote() -> Taunote {
+    let base_dir = Taunote::default_dir().expect("Failed to find platform data directory");
+    let settings = Settings::load(&base_dir).unwrap_or_default();
+    Taunote::new(base_dir).with_settings(&settings)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_opener::init())
+        .manage(taunote())
+        .manage(commands::ActiveRecording::default())
         .invoke_handler(tauri::generate_handler![
             greet,
-            commands::summarize_transcript,
-            commands::write_email,
-            commands::write_lecture_notes,
+            commands::generate_note,
+            commands::reprocess_project,
+            commands::redact_project,
+            commands::unredact_text,
+            commands::export_obsidian_vault,
+            commands::export_project_bundle,
+            commands::import_project_bundle,
+            commands::import_calendar,
+            commands::match_recording_meeting,
+            commands::get_project_meeting,
+            commands::set_project_meeting,
+            commands::get_project_metadata,
+            commands::set_project_metadata,
+            commands::set_project_type,
+            commands::backup_library,
+            commands::verify_backup,
+            commands::restore_library,
+            commands::get_project_audio,
             commands::transcribe_audio,
+            commands::list_input_devices,
+            commands::start_recording,
+            commands::pause_recording,
+            commands::resume_recording,
+            commands::stop_recording,
+            commands::cancel_recording,
             commands::setup_backend,
+            commands::unlock,
+            commands::check_environment,
+            commands::switch_llm_model,
+            commands::get_llm_status,
+            commands::get_llm_queue,
+            commands::cancel_llm_job,
             commands::get_project_groups,
-            commands::insert_project_group_to_db,
             commands::insert_audio_project_to_db,
-            commands::insert_project_notes_to_db
+            commands::list_note_versions,
+            commands::diff_note_versions,
+            commands::restore_note_version,
+            commands::set_current_note_version,
+            commands::save_note_edit,
+            commands::sync_notes,
+            commands::resolve_note_conflict,
+            commands::search_projects,
+            commands::list_tags,
+            commands::create_group,
+            commands::rename_group,
+            commands::move_group,
+            commands::delete_group,
+            commands::move_project,
+            commands::export_project
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
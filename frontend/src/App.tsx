@@ -8,6 +8,9 @@ import {
   AudioProject as DBAudioProject,
   ProjectGroup as DBProjectGroup,
   AppView,
+  GeneratedNote,
+  ProjectType,
+  TaunoteError,
 } from "./types";
 
 // UI-facing types for Sidebar and views
@@ -15,7 +18,7 @@ type UIAudioProject = {
   id: string;
   name: string;
   date: string;
-  type: "meeting" | "lecture" | "other";
+  type: ProjectType;
 };
 
 type UIProjectGroup = {
@@ -32,11 +35,33 @@ function App() {
   >(null);
   const [currentView, setCurrentView] = useState<AppView>("welcome");
 
+  // Starts the backend, asking for the passphrase while the data is encrypted
+  // and locked
+  const setupBackend = async () => {
+    for (;;) {
+      try {
+        await invoke("setup_backend");
+        return;
+      } catch (err) {
+        if ((err as TaunoteError).code !== "locked") throw err;
+        const passphrase = window.prompt(
+          `${(err as TaunoteError).message}\nPassphrase:`,
+        );
+        if (passphrase === null) throw err;
+        try {
+          await invoke("unlock", { passphrase, remember: true });
+        } catch (unlockErr) {
+          if ((unlockErr as TaunoteError).code !== "locked") throw unlockErr;
+        }
+      }
+    }
+  };
+
   // Load existing project groups from DB and map to UI types
   useEffect(() => {
     (async () => {
       try {
-        await invoke("setup_backend");
+        await setupBackend();
         const groups = await invoke<DBProjectGroup[]>("get_project_groups");
         const uiGroups = groups.map((g) => ({
           id: g.id,
@@ -45,34 +70,33 @@ function App() {
             id: ap.id,
             name: ap.name,
             date: ap.date,
-            type: ap.project_type as "meeting" | "lecture" | "other",
+            type: ap.project_type,
           })),
         }));
         setProjectGroups(uiGroups);
       } catch (err) {
-        console.error("Failed to init/load groups:", err);
+        const error = err as TaunoteError;
+        console.error("Failed to init/load groups:", error.message);
+        if (error.remediation) console.error(error.remediation);
       }
     })();
   }, []);
 
-  // Create a new project group (in-memory)
+  // Create a new project group, the backend picks its id
   const handleNewProjectGroup = async () => {
-    const newGroup: UIProjectGroup = {
-      id: uuid(),
-      name: `New Group ${projectGroups.length + 1}`,
-      audioProjects: [],
-    };
-
+    let newGroup: UIProjectGroup;
     try {
-      await invoke("insert_project_group_to_db", {
-        id: newGroup.id,
-        name: newGroup.name,
+      const group = await invoke<{ id: string; name: string }>("create_group", {
+        path: `New Group ${projectGroups.length + 1}`,
       });
+      newGroup = { id: group.id, name: group.name, audioProjects: [] };
     } catch (e) {
       console.error("DB insert failed:", e);
       return;
     }
-    setProjectGroups((prev) => [...prev, newGroup]);
+    setProjectGroups((prev) =>
+      prev.some((g) => g.id === newGroup.id) ? prev : [...prev, newGroup],
+    );
     setSelectedGroupId(newGroup.id);
     setSelectedAudioProjectId(null);
     setCurrentView("welcome");
@@ -107,13 +131,13 @@ function App() {
       id: uiAudio.id,
       group_id: groupId,
       name: uiAudio.name,
-      relative_path: `projects/${groupId}/${uiAudio.id}`,
+      relative_path: `groups/${groupId}/${uiAudio.id}`,
       date: uiAudio.date,
       project_type: uiAudio.type,
       language: "en",
     };
     try {
-      await invoke("insert_audio_project_to_db", { audio_project: dbAudio });
+      await invoke("insert_audio_project_to_db", { audioProject: dbAudio });
     } catch (e) {
       console.error("DB insert failed:", e);
       // TODO: Optionally roll back UI change
@@ -134,18 +158,19 @@ function App() {
   const handleFileUploaded = async (
     filePath: string,
     lang: string,
-    type: "meeting" | "lecture" | "other",
+    type: ProjectType,
   ) => {
     // Make sure a group exists to put this audio in
     let groupId = selectedGroupId;
     if (!groupId) {
-      // Auto-create a group if none selected
-      const newGroup = { id: uuid(), name: "Imported", audioProjects: [] };
-      await invoke("insert_project_group_to_db", {
-        id: newGroup.id,
-        name: newGroup.name,
+      // Auto-create a group if none selected, or reuse the one made before
+      const group = await invoke<{ id: string; name: string }>("create_group", {
+        path: "Imported",
       });
-      setProjectGroups((prev) => [...prev, newGroup]);
+      const newGroup = { id: group.id, name: group.name, audioProjects: [] };
+      setProjectGroups((prev) =>
+        prev.some((g) => g.id === newGroup.id) ? prev : [...prev, newGroup],
+      );
       groupId = newGroup.id;
     }
 
@@ -170,51 +195,33 @@ function App() {
       id: uiAudio.id,
       group_id: groupId!,
       name: uiAudio.name,
-      relative_path: filePath,
+      relative_path: `groups/${groupId}/${uiAudio.id}`,
       date: uiAudio.date,
       project_type: type,
       language: lang,
     };
-    await invoke("insert_audio_project_to_db", { audio_project: dbAudio });
+    await invoke("insert_audio_project_to_db", { audioProject: dbAudio });
 
     // Transcribe
     const [transcriptPath, transcriptText] = await invoke<[string, string]>(
       "transcribe_audio",
       {
-        audio_path: filePath,
+        audioPath: filePath,
         lang,
-        group_name: groupId!,
-        project_name: uiAudio.name,
+        projectId: uiAudio.id,
       },
     );
 
-    // Summarize + Email + Lecture Notes
-    const [summaryPath, summaryText] = await invoke<[string, string]>(
-      "summarize_transcript",
-      { transcript_path: transcriptPath },
-    );
-    const [emailPath, emailText] = await invoke<[string, string]>(
-      "write_email",
-      { transcript_path: transcriptPath },
-    );
-    const [lectureNotesPath, lectureNotesText] = await invoke<[string, string]>(
-      "write_lecture_notes",
-      { transcript_path: transcriptPath },
-    );
-
-    // Save to db
-    await invoke<string>("insert_project_notes_to_db", {
-      project_id: uiAudio.id,
-      transcript: transcriptText,
-      summary: summaryText,
-      email: emailText,
-      lecture_notes: lectureNotesText,
-    });
-
+    // Summary + Email + Lecture Notes, each stored as a version and written to its own file
+    for (const noteKind of ["summary", "email", "lecture_notes"] as const) {
+      const note = await invoke<GeneratedNote>("generate_note", {
+        projectId: uiAudio.id,
+        noteKind,
+      });
+      console.log(`${noteKind} at:`, note.file_path);
+    }
     console.log("Transcript at:", transcriptPath);
-    console.log("Summary at: ", summaryPath);
-    console.log("Email path:", emailPath);
-    console.log("Lecture Notes path: ", lectureNotesPath);
+    console.log("Transcript length:", transcriptText.length);
 
     // Switch view
     setSelectedGroupId(groupId!);
@@ -11,7 +11,13 @@ interface ProjectViewProps {
 }
 
 // TODO: this should not be hardcoded here
-const recordingTypes = ["meeting", "lecture", "other"] as const;
+const recordingTypes = [
+  "meeting",
+  "lecture",
+  "interview",
+  "voice_memo",
+  "other",
+] as const;
 type RecordingType = (typeof recordingTypes)[number];
 
 const languages = ["Auto", "English", "Spanish"] as const;
@@ -6,7 +6,7 @@ interface AudioProject {
   id: string;
   name: string;
   date: string;
-  type: "meeting" | "lecture" | "other";
+  type: "meeting" | "lecture" | "interview" | "voice_memo" | "other";
 }
 
 interface ProjectGroup {
@@ -1,8 +1,9 @@
 import React, { useState } from "react";
 import { UploadCloud } from "lucide-react";
 import { open } from "@tauri-apps/plugin-dialog";
+import { ProjectType } from "../types";
 
-type RecordingType = "meeting" | "lecture" | "other";
+type RecordingType = ProjectType;
 type LanguageType = "auto" | "en" | "es" | "fr";
 
 interface WelcomeViewProps {
@@ -57,6 +58,8 @@ const WelcomeView: React.FC<WelcomeViewProps> = ({ onFileUploaded }) => {
           >
             <option value="meeting">Meeting</option>
             <option value="lecture">Lecture</option>
+            <option value="interview">Interview</option>
+            <option value="voice_memo">Voice memo</option>
             <option value="other">Other</option>
           </select>
         </div>
@@ -4,14 +4,232 @@ export interface AudioProject {
   name: string;
   relative_path: string;
   date: string;
-  project_type: string;
+  project_type: ProjectType;
   language: string;
+  // recording in the media store, see get_project_audio
+  media_hash?: string | null;
 }
 
+// Picks the notes generated when none are asked for
+export type ProjectType =
+  | "meeting"
+  | "lecture"
+  | "interview"
+  | "voice_memo"
+  | "other";
+
 export interface ProjectGroup {
   id: string;
   name: string;
+  // null for top level groups
+  parentId: string | null;
   audioProjects: AudioProject[];
 }
 
+export type NoteKind = "transcript" | "summary" | "email" | "lecture_notes";
+
+export interface GeneratedNote {
+  project_id: string;
+  kind: NoteKind;
+  file_path: string;
+  version: { id: string; content: string; created_at: string };
+  usage: { prompt_tokens: number; completion_tokens: number; truncated: boolean };
+}
+
+export interface WhisperxOptions {
+  language?: string | null;
+  model?: string;
+  diarize?: boolean;
+  min_speakers?: number | null;
+  max_speakers?: number | null;
+}
+
+// Arguments of reprocess_project, missing fields keep their defaults
+export interface ReprocessOptions {
+  whisperx?: WhisperxOptions;
+  diarize_only?: boolean;
+  regenerate?: boolean;
+}
+
+export interface ProcessedRecording {
+  project: AudioProject;
+  transcript: { id: string; content: string; created_at: string };
+  notes: GeneratedNote[];
+}
+
+export interface RedactionRules {
+  emails?: boolean;
+  phones?: boolean;
+  ibans?: boolean;
+  credit_cards?: boolean;
+  words?: string[];
+}
+
+export interface RedactOptions {
+  rules?: RedactionRules;
+  // also ask the LLM for the names of people
+  names?: boolean;
+}
+
+export interface Redaction {
+  placeholder: string;
+  entity: "email" | "phone" | "iban" | "credit_card" | "term" | "name";
+  original: string;
+}
+
+export interface RedactedProject {
+  project_id: string;
+  notes: { kind: string; content: string; file_path: string }[];
+  redactions: Redaction[];
+}
+
+export interface VaultReport {
+  // notes created or changed, group pages included
+  written: string[];
+  unchanged: number;
+}
+
+export interface ImportOptions {
+  // group name to import into instead of the bundle's
+  group?: string;
+  // "rename" creates "name (2)" when the group already exists
+  on_collision?: "merge" | "rename";
+}
+
+export interface ImportReport {
+  project: AudioProject;
+  group: { id: string; name: string };
+  created_group: boolean;
+  // id the project had in the exporting library
+  original_id: string;
+  versions: number;
+}
+
+export interface CalendarImport {
+  events: number;
+  recurring: number;
+}
+
+// The calendar event a project was recorded in, times are RFC 3339 in UTC
+export interface Meeting {
+  event_id: string;
+  title: string;
+  starts_at: string;
+  ends_at: string;
+  // candidates for the speakers in the transcript
+  attendees: string[];
+  agenda: string | null;
+  location: string | null;
+  categories: string[];
+}
+
+export interface BackupManifest {
+  format_version: number;
+  id: string;
+  kind: "full" | "incremental";
+  // id of the backup an incremental one builds on
+  base_id: string | null;
+  created_at: string;
+  schema_version: number;
+  encrypted: boolean;
+  files: { path: string; sha256: string; size: number }[];
+  // paths of `files` stored in this archive
+  stored: string[];
+}
+
+export interface BackupReport {
+  path: string;
+  manifest: BackupManifest;
+}
+
+export interface RestoreReport {
+  backup_id: string;
+  created_at: string;
+  files: number;
+  // where the replaced library was moved
+  previous_data: string | null;
+}
+
+// Shape of every error returned by a Tauri command
+export interface TaunoteError {
+  code:
+    | "missing_dependency"
+    | "audio_decode"
+    | "transcription"
+    | "llm_unavailable"
+    | "llm"
+    | "database"
+    | "io"
+    | "config"
+    | "not_found"
+    | "locked"
+    | "invalid_input"
+    | "internal";
+  message: string;
+  remediation: string | null;
+}
+
+export interface EnvironmentCheck {
+  name: string;
+  status: "ok" | "warning" | "error";
+  detail: string;
+  fix: string | null;
+}
+
+export interface EnvironmentReport {
+  checks: EnvironmentCheck[];
+}
+
+export interface SearchHit {
+  project_id: string;
+  project_name: string;
+  snippet: string;
+}
+
+export interface ProjectMetadata {
+  tags: string[];
+  description: string | null;
+  participants: string[];
+  duration_seconds: number | null;
+  source_device: string | null;
+  fields: Record<string, string>;
+}
+
+// Argument of search_projects, an empty query lists the matching projects
+export interface SearchFilter {
+  group_id?: string | null;
+  project_type?: ProjectType | null;
+  // projects with every one of these tags
+  tags?: string[];
+  participant?: string | null;
+  // dates like 2025-01 or 2025-01-31, both inclusive
+  from?: string | null;
+  to?: string | null;
+}
+
+export interface TagCount {
+  tag: string;
+  projects: number;
+}
+
+export type RecordingFormat = "wav" | "flac";
+
+export interface InputDevice {
+  name: string;
+  is_default: boolean;
+}
+
+// Payload of `recording-level` events, peak and rms are 0 to 1 of full scale
+export interface RecordingLevel {
+  peak: number;
+  rms: number;
+  seconds: number;
+}
+
+// Payload of `recording-progress` events while stop_recording processes
+export type RecordingProgress =
+  | { stage: "created"; project_id: string }
+  | { stage: "transcribing" }
+  | { stage: "generating"; kind: string };
+
 export type AppView = "welcome" | "project";
@@ -5,11 +5,11 @@ from dotenv import load_dotenv
 import os
 from pathlib import Path
 import argparse
+import json
+import sys
 
 load_dotenv()
 hf_token = os.getenv("HUGGINGFACE_TOKEN")
-if not hf_token:
-    raise EnvironmentError("Missing HUGGINGFACE_TOKEN in .env")
 
 def whisperx_runner(
         audio_file: str,
@@ -17,53 +17,75 @@ def whisperx_runner(
         device: str = "cuda" if torch.cuda.is_available() else ("mps" if torch.backends.mps.is_available() else "cpu"),
         batch_size: int = 8, # reduce if low on GPU mem
         compute_type: str = None,
-        language = None
+        language = None,
+        model_name: str = "small",
+        diarize: bool = True,
+        min_speakers: int = None,
+        max_speakers: int = None,
+        save_segments: str = None,
+        from_segments: str = None
 ):
         try:
                 device = "cpu" # TODO: fix the GPU dynamic issues, for now force it to work on cpu
                 compute_type = compute_type or ("float16" if device == "cuda" else "int8")
-                # Transcribe with original whisper (batched)
-                print("[INFO] Loading STT model and audio...")
-                model_dir = os.path.join('../models/whisperx/')
-                model = whisperx.load_model("small", device, compute_type=compute_type, download_root=model_dir)
-
                 audio = whisperx.load_audio(audio_file)
-                print(f"[INFO] Transcribing: {audio_file}...")
-                result = model.transcribe(audio, batch_size=batch_size, language=language) if language else model.transcribe(audio, batch_size=batch_size)
-                print(result["segments"]) # before alignment
-                print(f"[INFO] Transcription complete. Language: {result['language']}")
 
-                # delete model if low on GPU resources
-                cleanup_model(model, device)
+                if from_segments:
+                        # only diarization is redone, e.g. with a known number of speakers
+                        print(f"[INFO] Reusing aligned segments from {from_segments}")
+                        with open(from_segments, encoding="utf-8") as f:
+                                result = json.load(f)
+                else:
+                        # Transcribe with original whisper (batched)
+                        print(f"[INFO] Loading STT model {model_name}...")
+                        model_dir = os.path.join('../models/whisperx/')
+                        model = whisperx.load_model(model_name, device, compute_type=compute_type, download_root=model_dir)
+
+                        print(f"[INFO] Transcribing: {audio_file}...")
+                        result = model.transcribe(audio, batch_size=batch_size, language=language) if language else model.transcribe(audio, batch_size=batch_size)
+                        print(result["segments"]) # before alignment
+                        print(f"[INFO] Transcription complete. Language: {result['language']}")
+
+                        # delete model if low on GPU resources
+                        cleanup_model(model, device)
+
+                        # Align whisper output
+                        print("[INFO] Loading alignment model...")
+                        model_a, metadata = whisperx.load_align_model(language_code=result["language"], device=device)
+                        result = whisperx.align(result["segments"], model_a, metadata, audio, device, return_char_alignments=False)
 
-                # Align whisper output
-                print("[INFO] Loading alignment model...")
-                model_a, metadata = whisperx.load_align_model(language_code=result["language"], device=device)
-                result = whisperx.align(result["segments"], model_a, metadata, audio, device, return_char_alignments=False)
+                        print(result["segments"]) # after alignment
 
-                print(result["segments"]) # after alignment
+                        # delete model if low on GPU resources
+                        cleanup_model(model_a, device)
 
-                # delete model if low on GPU resources
-                cleanup_model(model_a, device)
+                        if save_segments:
+                                Path(save_segments).parent.mkdir(parents=True, exist_ok=True)
+                                with open(save_segments, "w", encoding="utf-8") as f:
+                                        json.dump(result, f, default=float)
 
-                # Assign speaker labels
-                print("[INFO] Performing speaker diarization...")
-                diarize_model = whisperx.diarize.DiarizationPipeline(use_auth_token=hf_token, device=device)
+                if diarize:
+                        # Assign speaker labels
+                        if not hf_token:
+                                raise EnvironmentError("Missing HUGGINGFACE_TOKEN in .env")
+                        print("[INFO] Performing speaker diarization...")
+                        diarize_model = whisperx.diarize.DiarizationPipeline(use_auth_token=hf_token, device=device)
 
-                # add min/max number of speakers if known
-                diarize_segments = diarize_model(audio)
-                # diarize_model(audio, min_speakers=min_speakers, max_speakers=max_speakers)
+                        diarize_segments = diarize_model(audio, min_speakers=min_speakers, max_speakers=max_speakers)
 
-                result = whisperx.assign_word_speakers(diarize_segments, result)
-                print("[INFO] Transcription with speaker labels complete.")
+                        result = whisperx.assign_word_speakers(diarize_segments, result)
+                        print("[INFO] Transcription with speaker labels complete.")
 
                 # save to output path
                 Path(output_path).parent.mkdir(parents=True, exist_ok=True)
                 with open(output_path, "w", encoding="utf-8") as f:
                         for segment in result["segments"]:
-                                speaker = segment.get("speaker", "unknown")
                                 text = segment["text"].strip()
-                                f.write(f"[{speaker}] {text}\n")
+                                if diarize:
+                                        speaker = segment.get("speaker", "unknown")
+                                        f.write(f"[{speaker}] {text}\n")
+                                else:
+                                        f.write(f"{text}\n")
                 
                 print(f"[INFO] Saved transcript to {output_path}")
                 return result
@@ -86,10 +108,23 @@ if __name__ == '__main__':
         parser.add_argument("-o", "--output", required=False, help="Set custom output path for the transcript")
         parser.add_argument("-m", "--model", required=False, help="Set a custom whisper model")
         parser.add_argument("-l", "--lang", required=False, help="Set a language for the audio")
+        parser.add_argument("--no-diarize", action="store_true", help="Skip speaker diarization")
+        parser.add_argument("--min-speakers", type=int, required=False, help="Lower bound on the number of speakers")
+        parser.add_argument("--max-speakers", type=int, required=False, help="Upper bound on the number of speakers")
+        parser.add_argument("--save-segments", required=False, help="Write the aligned segments to this JSON file")
+        parser.add_argument("--from-segments", required=False, help="Skip transcription and diarize the segments saved in this JSON file")
 
         args = parser.parse_args()
-        whisperx_runner(
+        result = whisperx_runner(
                 audio_file=args.input,
                 output_path=args.output or "tmp/transcript.txt",
-                language=args.lang or None
-        )
\ No newline at end of file
+                language=args.lang or None,
+                model_name=args.model or "small",
+                diarize=not args.no_diarize,
+                min_speakers=args.min_speakers,
+                max_speakers=args.max_speakers,
+                save_segments=args.save_segments,
+                from_segments=args.from_segments
+        )
+        if result is None:
+                sys.exit(1)
\ No newline at end of file